@@ -0,0 +1,233 @@
+use std::path::PathBuf;
+
+pub static USAGE: &str = r#"Usage:
+  funge-it-together [--save <folder>] [--levels <folder>] [--keybindings <file>] [command]
+
+  funge-it-together
+      Start the game
+
+  funge-it-together verify --level <uuid|index> [options]
+      Score the saved solutions for a level without opening the game
+
+      --level <uuid|index>  Level UUID or displayed index (like "1A" or "2B-1")
+      --pack <N>            Only search for the level index in level pack N
+      --solution <N>        Only verify solution N (default: all solutions)
+      --max-cycles <N>      Fail any test case that runs longer than N cycles
+                            (default: the level's cycle limit)
+      --json                Print the results as JSON
+
+  funge-it-together export --level <uuid|index> [options]
+      Print a saved solution in the plain-text ".fit" format
+
+      --level <uuid|index>  Level UUID or displayed index (like "1A" or "2B-1")
+      --pack <N>            Only search for the level index in level pack N
+      --solution <N>        Export solution N (default: 1)
+      --output <file>       Write the solution to a file instead
+
+  funge-it-together import <file>...
+      Add the solutions from ".fit" files to the save file
+
+  funge-it-together lint-pack <folder> [options]
+      Check a level pack for mistakes before sharing it
+      Each level's reference solution is run against every test case
+
+      --seeds <N>           Number of random seeds to generate test cases with (default: 50)
+
+  funge-it-together help
+      Show this message
+
+Global options:
+  --save <folder>           Folder for save files (default: $XDG_DATA_HOME/funge-it-together)
+  --levels <folder>         Folder containing the level packs (default: ./levels if it exists)
+  --keybindings <file>      File with the keys for each action
+                            (default: $XDG_CONFIG_HOME/funge-it-together/keybindings.toml)"#;
+
+/// Everything selected from the command-line arguments
+pub struct Options {
+  pub command: Command,
+  pub save_folder: Option<PathBuf>,
+  pub levels_folder: Option<PathBuf>,
+  pub keybindings_file: Option<PathBuf>,
+}
+
+/// Action selected from the command-line arguments
+pub enum Command {
+  Play,
+  Verify(VerifyArgs),
+  Export(ExportArgs),
+  Import(ImportArgs),
+  LintPack(LintPackArgs),
+  Help,
+}
+
+pub struct VerifyArgs {
+  pub level: String,
+  pub pack: Option<usize>,
+  pub solution: Option<usize>,
+  pub max_cycles: Option<u32>,
+  pub json: bool,
+}
+
+pub struct ExportArgs {
+  pub level: String,
+  pub pack: Option<usize>,
+  pub solution: usize,
+  pub output: Option<String>,
+}
+
+pub struct ImportArgs {
+  pub files: Vec<String>,
+}
+
+pub struct LintPackArgs {
+  pub folder: String,
+  pub seeds: u32,
+}
+
+impl Options {
+  /// The global options can go anywhere, everything else is passed to the command
+  pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+    let mut save_folder = None;
+    let mut levels_folder = None;
+    let mut keybindings_file = None;
+    let mut command_args = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--save" => save_folder = Some(next_value(&mut args, &arg)?.into()),
+        "--levels" => levels_folder = Some(next_value(&mut args, &arg)?.into()),
+        "--keybindings" => keybindings_file = Some(next_value(&mut args, &arg)?.into()),
+        _ => command_args.push(arg),
+      }
+    }
+
+    Ok(Self {
+      command: Command::parse(command_args)?,
+      save_folder,
+      levels_folder,
+      keybindings_file,
+    })
+  }
+}
+
+impl Command {
+  fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+    let mut args = args.into_iter();
+
+    match args.next().as_deref() {
+      None => Ok(Self::Play),
+      Some("verify") => Ok(Self::Verify(VerifyArgs::parse(args)?)),
+      Some("export") => Ok(Self::Export(ExportArgs::parse(args)?)),
+      Some("import") => Ok(Self::Import(ImportArgs::parse(args)?)),
+      Some("lint-pack") => Ok(Self::LintPack(LintPackArgs::parse(args)?)),
+      Some("help" | "-h" | "--help") => Ok(Self::Help),
+      Some(other) => Err(format!("Unknown command \"{other}\"")),
+    }
+  }
+}
+
+impl VerifyArgs {
+  fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+    let mut level = None;
+    let mut pack = None;
+    let mut solution = None;
+    let mut max_cycles = None;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--level" => level = Some(next_value(&mut args, &arg)?),
+        "--pack" => pack = Some(parse_index(&next_value(&mut args, &arg)?, &arg)?),
+        "--solution" => solution = Some(parse_index(&next_value(&mut args, &arg)?, &arg)?),
+        "--max-cycles" => max_cycles = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+        "--json" => json = true,
+        _ => return Err(format!("Unknown option \"{arg}\"")),
+      }
+    }
+
+    Ok(Self {
+      level: level.ok_or("Missing required option --level")?,
+      pack,
+      solution,
+      max_cycles,
+      json,
+    })
+  }
+}
+
+impl ExportArgs {
+  fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+    let mut level = None;
+    let mut pack = None;
+    let mut solution = 0;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--level" => level = Some(next_value(&mut args, &arg)?),
+        "--pack" => pack = Some(parse_index(&next_value(&mut args, &arg)?, &arg)?),
+        "--solution" => solution = parse_index(&next_value(&mut args, &arg)?, &arg)?,
+        "--output" => output = Some(next_value(&mut args, &arg)?),
+        _ => return Err(format!("Unknown option \"{arg}\"")),
+      }
+    }
+
+    Ok(Self {
+      level: level.ok_or("Missing required option --level")?,
+      pack,
+      solution,
+      output,
+    })
+  }
+}
+
+impl ImportArgs {
+  fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+    let files: Vec<String> = args.collect();
+    if files.is_empty() {
+      return Err("Missing file to import".into());
+    }
+
+    Ok(Self { files })
+  }
+}
+
+impl LintPackArgs {
+  fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+    let mut folder = None;
+    let mut seeds = 50;
+
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--seeds" => seeds = parse_number(&next_value(&mut args, &arg)?, &arg)?,
+        _ if arg.starts_with("--") => return Err(format!("Unknown option \"{arg}\"")),
+        _ if folder.is_none() => folder = Some(arg),
+        _ => return Err(format!("Unexpected argument \"{arg}\"")),
+      }
+    }
+
+    Ok(Self {
+      folder: folder.ok_or("Missing level pack folder")?,
+      seeds,
+    })
+  }
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String> {
+  args.next().ok_or_else(|| format!("Missing value for {option}"))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, option: &str) -> Result<T, String> {
+  value
+    .parse()
+    .map_err(|_| format!("Invalid value \"{value}\" for {option}"))
+}
+
+// Indexes are 1-based on the command line, but 0-based everywhere else
+fn parse_index(value: &str, option: &str) -> Result<usize, String> {
+  match parse_number::<usize>(value, option)? {
+    0 => Err(format!("Invalid value \"{value}\" for {option}, must be at least 1")),
+    n => Ok(n - 1),
+  }
+}
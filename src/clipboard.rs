@@ -0,0 +1,144 @@
+use std::io::{self, Write};
+
+use crate::grid::{Grid, Rect};
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+///
+/// Cells copied in the program editor, shared by every level
+///   The cells are kept as symbols, so they can be pasted into a level of another type
+///   Symbols the level doesn't have or allow are skipped when pasting
+///
+#[derive(Debug, Clone, Default)]
+pub struct Clipboard {
+  rows: Vec<Vec<char>>,
+}
+
+impl Clipboard {
+  pub fn copy<C: Default + Clone>(grid: &Grid<C>, rect: &Rect, to_char: impl Fn(&C) -> char) -> Self {
+    Self {
+      rows: grid
+        .copy_rect(rect)
+        .iter()
+        .map(|row| row.iter().map(&to_char).collect())
+        .collect(),
+    }
+  }
+
+  ///
+  /// Read cells from text pasted into the terminal, one row per line
+  ///   Terminals often turn line breaks into carriage returns when pasting, so either one starts a new row
+  ///   Short lines are padded with spaces, so the cells stay a rectangle
+  ///
+  pub fn from_text(text: &str) -> Self {
+    let mut rows: Vec<Vec<char>> = text
+      .replace("\r\n", "\n")
+      .split(['\r', '\n'])
+      .map(|line| line.chars().collect())
+      .collect();
+    while rows.last().is_some_and(|row| row.is_empty()) {
+      rows.pop();
+    }
+
+    let cols = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if cols == 0 {
+      return Self::default();
+    }
+    for row in &mut rows {
+      row.resize(cols, ' ');
+    }
+    Self { rows }
+  }
+
+  /// One line per row, without the trailing spaces or empty lines
+  pub fn to_text(&self) -> String {
+    let lines: Vec<String> = self
+      .rows
+      .iter()
+      .map(|row| row.iter().collect::<String>().trim_end().to_string())
+      .collect();
+    lines.join("\n").trim_end().to_string()
+  }
+
+  ///
+  /// Copy the cells to the system clipboard as text, with an OSC 52 escape sequence
+  ///   Terminals that don't support it just ignore the sequence
+  ///
+  pub fn copy_to_terminal(&self) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(self.to_text().as_bytes()))?;
+    stdout.flush()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.rows.is_empty()
+  }
+
+  /// Convert the symbols back into commands, None where the symbol can't be pasted
+  pub fn to_values<C>(&self, from_char: impl Fn(char) -> Option<C>) -> Vec<Vec<Option<C>>> {
+    self
+      .rows
+      .iter()
+      .map(|row| row.iter().map(|c| from_char(*c)).collect())
+      .collect()
+  }
+}
+
+fn base64(bytes: &[u8]) -> String {
+  let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let bits = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |bits, (i, byte)| bits | (*byte as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      if i <= chunk.len() {
+        encoded.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
+  encoded
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rows(clipboard: &Clipboard) -> Vec<String> {
+    clipboard.rows.iter().map(|row| row.iter().collect()).collect()
+  }
+
+  #[test]
+  fn base64_padding() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"foob"), "Zm9vYg==");
+    assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    assert_eq!(base64("↑→".as_bytes()), "4oaR4oaS");
+    assert_eq!(base64(&[0xff, 0xfe, 0xfd]), "//79");
+  }
+
+  #[test]
+  fn pasted_lines_become_a_rectangle() {
+    for text in ["i1\n+\n\no  7", "i1\r\n+\r\n\r\no  7\r\n", "i1\r+\r\ro  7\r\r"] {
+      let clipboard = Clipboard::from_text(text);
+      assert_eq!(rows(&clipboard), vec!["i1  ", "+   ", "    ", "o  7"], "{text:?}");
+      assert_eq!(clipboard.to_text(), "i1\n+\n\no  7");
+    }
+
+    assert!(Clipboard::from_text("").is_empty());
+    assert!(Clipboard::from_text("\r\n\n").is_empty());
+  }
+
+  #[test]
+  fn values_keep_their_places() {
+    let clipboard = Clipboard::from_text("ab\nc");
+    let values = clipboard.to_values(|c| (c != 'b').then_some(c));
+    assert_eq!(values, vec![vec![Some('a'), None], vec![Some('c'), Some(' ')]]);
+  }
+}
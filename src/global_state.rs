@@ -1,52 +1,252 @@
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs::File;
-use std::hash::Hash;
-use std::io::{self, BufReader, BufWriter};
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
+use crate::clipboard::Clipboard;
 use crate::isa::{self, SolutionManager};
+use crate::keybindings::KeyBindings;
 use crate::level::{Level, LevelIndex, LevelPack};
-use crate::statistics::Statistics;
+use crate::migration::{self, SAVE_VERSION};
+use crate::solution_file;
+use crate::statistics::{SolutionRun, Statistics};
 
 static SAVE_FILE: &str = "save.json";
+static PROFILES_FOLDER: &str = "profiles";
+static LAST_PROFILE_FILE: &str = "profile.txt";
+static CACHE_FOLDER: &str = "cache";
+
+pub const DEFAULT_PROFILE: &str = "Default";
+pub const MAX_PROFILE_NAME_LEN: usize = 20;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GlobalState {
-  #[serde(default, deserialize_with = "deserialize_one_or_many_map")]
+  #[serde(default)]
   solutions: HashMap<Uuid, Vec<isa::standard::Solution>>,
   #[serde(default)]
   parallel_solutions: HashMap<Uuid, Vec<isa::parallel::Solution>>,
   #[serde(default)]
   unlocked: HashMap<Uuid, Statistics>,
+  #[serde(default)]
+  run_history: HashMap<Uuid, Vec<SolutionRun>>,
 
   #[serde(skip)]
   level_packs: Vec<LevelPack>,
+
+  // Current version of each level's test cases, found when the game starts
+  #[serde(skip)]
+  test_case_versions: HashMap<Uuid, String>,
+
+  // Shared by the editors for every level, but not saved
+  #[serde(skip)]
+  clipboard: Clipboard,
+
+  // Loaded from their own file, since they don't belong to a profile
+  #[serde(skip)]
+  keybindings: KeyBindings,
+
+  // Set when the save file could not be loaded or backed up, so it never gets overwritten
+  #[serde(skip)]
+  save_locked: bool,
+
+  #[serde(skip)]
+  save_folder: PathBuf,
+  #[serde(skip)]
+  profile: String,
+}
+
+/// Adds the version number when writing the save file
+#[derive(Serialize)]
+struct VersionedSave<'a> {
+  version: u32,
+  #[serde(flatten)]
+  state: &'a GlobalState,
 }
 
 impl GlobalState {
-  pub fn load(level_packs: Vec<LevelPack>) -> Self {
-    let mut state = Self::from_file(SAVE_FILE).unwrap_or_default();
+  ///
+  /// Load the last used profile from the save folder
+  ///   Older versions kept "save.json" in the current directory, so fall back to that for the default profile
+  ///
+  pub fn load(level_packs: Vec<LevelPack>, save_folder: PathBuf) -> Self {
+    let profile = fs::read_to_string(save_folder.join(LAST_PROFILE_FILE))
+      .map(|name| name.trim().to_string())
+      .ok()
+      .filter(|name| name != DEFAULT_PROFILE && profile_path(&save_folder, name).exists())
+      .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+
+    let save_file = profile_path(&save_folder, &profile);
+    let legacy_save_file = Path::new(SAVE_FILE);
+    let mut state = if profile == DEFAULT_PROFILE && !save_file.exists() && legacy_save_file.exists() {
+      eprintln!(
+        "Loading {SAVE_FILE} from the current directory, progress will be saved to {}",
+        save_file.display()
+      );
+      Self::load_file(legacy_save_file)
+    } else {
+      Self::load_file(&save_file)
+    };
+
     state.level_packs = level_packs;
+    state.save_folder = save_folder;
+    state.profile = profile;
     state
   }
 
+  ///
+  /// Load a single save file, or start a new game if there isn't one
+  ///   A save file that fails to load is backed up before it can be overwritten
+  ///
+  fn load_file(save_file: &Path) -> Self {
+    match Self::from_file(save_file) {
+      Ok(state) => state,
+      Err(e) if e.kind() == ErrorKind::NotFound => Self::default(),
+      Err(e) => {
+        eprintln!("Failed to load {}: {e}", save_file.display());
+
+        let mut state = Self::default();
+        match backup_save_file(save_file) {
+          Ok(backup) => eprintln!("Starting a new game, the old save file was backed up to {backup}"),
+          Err(e) => {
+            eprintln!(
+              "Failed to back up {}: {e}, progress will not be saved",
+              save_file.display()
+            );
+            state.save_locked = true;
+          },
+        }
+        state
+      },
+    }
+  }
+
   fn from_file<P: AsRef<Path>>(json_save_file: P) -> io::Result<Self> {
-    // Parse the level as a JSON file
-    let file = File::open(json_save_file)?;
-    let reader = BufReader::new(file);
-    Ok(serde_json::from_reader(reader)?)
+    // Parse the save as a JSON file, then bring it up to date
+    let file_data = fs::read_to_string(json_save_file)?;
+    let mut save: serde_json::Value = serde_json::from_str(&file_data)?;
+    migration::migrate(&mut save).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+    Ok(serde_json::from_value(save)?)
   }
 
+  ///
+  /// Save the game
+  ///   Writes to a temporary file first, so a crash can never leave a half-written save
+  ///
   pub fn save(&self) -> io::Result<()> {
-    let file = File::create(SAVE_FILE)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, self)?;
+    let save_file = profile_path(&self.save_folder, &self.profile);
+    if self.save_locked {
+      return Err(io::Error::other(format!(
+        "Refusing to overwrite {} that failed to load",
+        save_file.display()
+      )));
+    }
+
+    if let Some(folder) = save_file.parent() {
+      fs::create_dir_all(folder)?;
+    }
+
+    let temp_file = save_file.with_extension("json.tmp");
+    let mut writer = BufWriter::new(File::create(&temp_file)?);
+    serde_json::to_writer_pretty(
+      &mut writer,
+      &VersionedSave {
+        version: SAVE_VERSION,
+        state: self,
+      },
+    )?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+
+    fs::rename(temp_file, save_file)
+  }
+
+  pub fn profile(&self) -> &str {
+    &self.profile
+  }
+
+  /// Generated files that can be safely deleted, shared by every profile
+  pub fn cache_folder(&self) -> PathBuf {
+    self.save_folder.join(CACHE_FOLDER)
+  }
+
+  /// Where solutions are exported from the game, shared by every profile
+  pub fn export_folder(&self) -> PathBuf {
+    self.save_folder.join(solution_file::EXPORT_FOLDER)
+  }
+
+  /// List every profile in the save folder, the default profile always comes first
+  pub fn list_profiles(&self) -> Vec<String> {
+    let mut profiles: Vec<String> = fs::read_dir(self.save_folder.join(PROFILES_FOLDER))
+      .into_iter()
+      .flatten()
+      .filter_map(|entry| {
+        let path = entry.ok()?.path();
+        (path.extension()? == "json").then_some(path.file_stem()?.to_str()?.to_string())
+      })
+      .filter(|name| is_valid_profile_name(name))
+      .collect();
+    profiles.sort();
+
+    // Include the current profile, even if it hasn't been saved yet
+    if self.profile != DEFAULT_PROFILE && !profiles.contains(&self.profile) {
+      profiles.push(self.profile.clone());
+    }
+
+    profiles.insert(0, DEFAULT_PROFILE.to_string());
+    profiles
+  }
+
+  ///
+  /// Save the current profile, then load a different one
+  ///   New profiles start with a fresh game
+  ///
+  pub fn switch_profile(&mut self, profile: &str) -> Result<(), String> {
+    if !is_valid_profile_name(profile) {
+      return Err(format!(
+        "Profile names must be 1 to {MAX_PROFILE_NAME_LEN} letters, numbers, spaces, '-' or '_'"
+      ));
+    }
+    if profile == self.profile {
+      return Ok(());
+    }
+
+    if !self.save_locked {
+      self.save().map_err(|e| format!("Failed to save game: {e}"))?;
+    }
+
+    let mut state = Self::load_file(&profile_path(&self.save_folder, profile));
+    state.level_packs = std::mem::take(&mut self.level_packs);
+    state.clipboard = std::mem::take(&mut self.clipboard);
+    state.keybindings = std::mem::take(&mut self.keybindings);
+    state.save_folder = std::mem::take(&mut self.save_folder);
+    state.profile = profile.to_string();
+    *self = state;
+
+    // Remembering the profile is only a convenience, so ignore any errors
+    fs::write(self.save_folder.join(LAST_PROFILE_FILE), &self.profile).ok();
     Ok(())
   }
 
+  pub fn clipboard(&self) -> &Clipboard {
+    &self.clipboard
+  }
+
+  pub fn set_clipboard(&mut self, clipboard: Clipboard) {
+    self.clipboard = clipboard;
+  }
+
+  pub fn keybindings(&self) -> &KeyBindings {
+    &self.keybindings
+  }
+
+  pub fn set_keybindings(&mut self, keybindings: KeyBindings) {
+    self.keybindings = keybindings;
+  }
+
   #[inline]
   pub fn num_level_packs(&self) -> usize {
     self.level_packs.len()
@@ -58,13 +258,37 @@ impl GlobalState {
   }
 
   pub fn level(&self, index: LevelIndex) -> &Level {
-    let main_level = self.level_packs[index.get_level_pack_index()]
-      .level_group(index.get_group())
-      .main_level(index.get_level_in_group());
-    if let Some(challenge) = index.get_challenge() {
-      main_level.challenge_level(challenge)
-    } else {
-      main_level.level()
+    self.level_packs[index.get_level_pack_index()].level(index)
+  }
+
+  ///
+  /// Find a level by its UUID or by its displayed index (like "1A" or "2B-1")
+  ///   The index is searched in every level pack unless a pack index is given
+  ///
+  pub fn find_level(&self, query: &str, pack_index: Option<usize>) -> Result<LevelIndex, String> {
+    let pack_indexes: Vec<usize> = match pack_index {
+      Some(i) if i < self.level_packs.len() => vec![i],
+      Some(i) => return Err(format!("Level pack {} does not exist", i + 1)),
+      None => (0..self.level_packs.len()).collect(),
+    };
+
+    let all_levels = pack_indexes
+      .into_iter()
+      .flat_map(|i| self.level_packs[i].level_indexes(i));
+
+    let matches: Vec<LevelIndex> = match Uuid::parse_str(query) {
+      Ok(id) => all_levels.filter(|index| self.level(*index).id() == id).collect(),
+      Err(_) => all_levels
+        .filter(|index| index.to_string().eq_ignore_ascii_case(query))
+        .collect(),
+    };
+
+    match matches[..] {
+      [index] => Ok(index),
+      [] => Err(format!("No level found matching \"{query}\"")),
+      _ => Err(format!(
+        "Level \"{query}\" exists in multiple level packs, use --pack to select one"
+      )),
     }
   }
 
@@ -76,6 +300,44 @@ impl GlobalState {
     self.unlocked.get(&level_id).cloned()
   }
 
+  pub fn test_case_version(&self, level_id: Uuid) -> Option<&str> {
+    self.test_case_versions.get(&level_id).map(String::as_str)
+  }
+
+  ///
+  /// Remember the current version of the level's test cases
+  ///   Statistics saved before versions existed are assumed to match, since there's nothing to compare them to
+  ///
+  pub fn set_test_case_version(&mut self, level_id: Uuid, version: String) {
+    if let Some(statistics) = self.unlocked.get_mut(&level_id) {
+      if statistics.test_case_version().is_none() {
+        statistics.set_test_case_version(Some(version.clone()));
+      }
+    }
+    self.test_case_versions.insert(level_id, version);
+  }
+
+  /// Statistics are stale when they were scored against test cases the level no longer has
+  pub fn is_stale(&self, level_id: Uuid, statistics: &Statistics) -> bool {
+    match (self.test_case_version(level_id), statistics.test_case_version()) {
+      (Some(current), Some(version)) => current != version,
+      _ => false,
+    }
+  }
+
+  ///
+  /// Replace the statistics for a level after running its solutions again
+  ///   None means no solution passes anymore, so the old statistics are kept and stay out of date
+  ///   The level stays complete either way, so the levels after it are never locked again
+  ///
+  pub fn replace_statistics(&mut self, level_id: Uuid, statistics: Option<Statistics>) {
+    if let Some(statistics) = statistics {
+      self.unlocked.insert(level_id, statistics);
+    }
+
+    self.save().ok();
+  }
+
   // Returns the best statistics overall
   pub fn complete_level(&mut self, level_id: Uuid, statistics: Statistics) -> Statistics {
     let best = self
@@ -102,6 +364,14 @@ impl SolutionManager<isa::Standard> for GlobalState {
   fn get_all_solutions_mut(&mut self, level_id: Uuid) -> &mut Vec<isa::standard::Solution> {
     self.solutions.entry(level_id).or_default()
   }
+
+  fn get_run_history(&self) -> &HashMap<Uuid, Vec<SolutionRun>> {
+    &self.run_history
+  }
+
+  fn get_run_history_mut(&mut self) -> &mut HashMap<Uuid, Vec<SolutionRun>> {
+    &mut self.run_history
+  }
 }
 
 impl SolutionManager<isa::Parallel> for GlobalState {
@@ -113,35 +383,59 @@ impl SolutionManager<isa::Parallel> for GlobalState {
   fn get_all_solutions_mut(&mut self, level_id: Uuid) -> &mut Vec<isa::parallel::Solution> {
     self.parallel_solutions.entry(level_id).or_default()
   }
+
+  fn get_run_history(&self) -> &HashMap<Uuid, Vec<SolutionRun>> {
+    &self.run_history
+  }
+
+  fn get_run_history_mut(&mut self) -> &mut HashMap<Uuid, Vec<SolutionRun>> {
+    &mut self.run_history
+  }
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq)]
-#[serde(untagged)]
-enum OneOrManyMap<K, V>
-where
-  K: Eq + Hash,
-{
-  One(HashMap<K, V>),
-  Vec(HashMap<K, Vec<V>>),
+fn profile_path(save_folder: &Path, profile: &str) -> PathBuf {
+  if profile == DEFAULT_PROFILE {
+    save_folder.join(SAVE_FILE)
+  } else {
+    save_folder.join(PROFILES_FOLDER).join(format!("{profile}.json"))
+  }
 }
 
-impl<K, V> From<OneOrManyMap<K, V>> for HashMap<K, Vec<V>>
-where
-  K: Eq + Hash,
-{
-  fn from(from: OneOrManyMap<K, V>) -> Self {
-    match from {
-      OneOrManyMap::One(val) => val.into_iter().map(|(k, v)| (k, vec![v])).collect(),
-      OneOrManyMap::Vec(vec) => vec,
+// Profile names become file names, so keep them simple
+pub fn is_valid_profile_name(name: &str) -> bool {
+  !name.trim().is_empty()
+    && name.chars().count() <= MAX_PROFILE_NAME_LEN
+    && name
+      .chars()
+      .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+}
+
+// Copy the save file next to itself with a timestamp, returns the name of the copy
+fn backup_save_file(save_file: &Path) -> io::Result<String> {
+  let timestamp = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or_default();
+
+  let contents = fs::read(save_file)?;
+
+  // Never overwrite an older backup, even from the same second
+  for attempt in 0.. {
+    let backup = match attempt {
+      0 => format!("{}.{timestamp}.bak", save_file.display()),
+      n => format!("{}.{timestamp}-{n}.bak", save_file.display()),
+    };
+
+    match File::options().write(true).create_new(true).open(&backup) {
+      Ok(mut file) => {
+        file.write_all(&contents)?;
+        file.sync_all()?;
+        return Ok(backup);
+      },
+      Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+      Err(e) => return Err(e),
     }
   }
-}
 
-fn deserialize_one_or_many_map<'de, D, K, V>(deserializer: D) -> Result<HashMap<K, Vec<V>>, D::Error>
-where
-  D: Deserializer<'de>,
-  K: Eq + Hash + Deserialize<'de>,
-  V: Deserialize<'de>,
-{
-  Ok(OneOrManyMap::<K, V>::deserialize(deserializer)?.into())
+  unreachable!()
 }
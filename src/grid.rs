@@ -1,7 +1,7 @@
 use crossterm::style::{self, Color};
 use crossterm::{cursor, QueueableCommand};
-use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::io::{self, Write};
 
 use crate::printable::Printable;
@@ -12,6 +12,199 @@ pub struct Grid<C> {
 
   #[serde(default)]
   breakpoints: HashSet<(usize, usize)>,
+
+  // Kept sorted by cell, so the same annotations always compare equal
+  #[serde(
+    default,
+    skip_serializing_if = "Vec::is_empty",
+    deserialize_with = "sorted_annotations"
+  )]
+  annotations: Vec<Annotation>,
+
+  // Display only, so it's left out of saves and comparisons
+  #[serde(skip)]
+  highlight_annotations: bool,
+}
+
+// Undo compares grids, which shouldn't notice the display toggle
+impl<C: PartialEq> PartialEq for Grid<C> {
+  fn eq(&self, other: &Self) -> bool {
+    self.values == other.values && self.breakpoints == other.breakpoints && self.annotations == other.annotations
+  }
+}
+
+/// Text attached to a cell, it doesn't change how the program runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+  pub row: usize,
+  pub col: usize,
+  pub text: String,
+}
+
+// Saves edited by hand might be out of order or have two for one cell, the last one wins like in .fit files
+fn sorted_annotations<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Annotation>, D::Error> {
+  let annotations: BTreeMap<(usize, usize), Annotation> = Vec::<Annotation>::deserialize(deserializer)?
+    .into_iter()
+    .filter(|annotation| !annotation.text.is_empty())
+    .map(|annotation| ((annotation.row, annotation.col), annotation))
+    .collect();
+  Ok(annotations.into_values().collect())
+}
+
+/// Cells from the top-left to the bottom-right corner, including both corners
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+  pub top: usize,
+  pub left: usize,
+  pub bottom: usize,
+  pub right: usize,
+}
+
+impl Rect {
+  /// Smallest rectangle holding both cells, which can be any two opposite corners
+  pub fn from_corners((row_1, col_1): (usize, usize), (row_2, col_2): (usize, usize)) -> Self {
+    Self {
+      top: row_1.min(row_2),
+      left: col_1.min(col_2),
+      bottom: row_1.max(row_2),
+      right: col_1.max(col_2),
+    }
+  }
+
+  pub fn rows(&self) -> usize {
+    self.bottom - self.top + 1
+  }
+
+  pub fn cols(&self) -> usize {
+    self.right - self.left + 1
+  }
+
+  pub fn contains(&self, row: usize, col: usize) -> bool {
+    (self.top..=self.bottom).contains(&row) && (self.left..=self.right).contains(&col)
+  }
+
+  /// Every cell in the rectangle, row by row
+  pub fn cells(&self) -> impl Iterator<Item = (usize, usize)> {
+    let (left, right) = (self.left, self.right);
+    (self.top..=self.bottom).flat_map(move |row| (left..=right).map(move |col| (row, col)))
+  }
+
+  ///
+  /// Where the rectangle ends up after the transform, in a grid of the given size
+  ///   It keeps the same top-left corner, unless it has to move up or left to fit
+  ///   Returns None if it's too big for the grid
+  ///
+  pub fn transformed(&self, transform: Transform, grid_rows: usize, grid_cols: usize) -> Option<Rect> {
+    let (rows, cols) = match transform {
+      Transform::RotateClockwise | Transform::RotateCounterClockwise => (self.cols(), self.rows()),
+      Transform::MirrorHorizontally | Transform::MirrorVertically => (self.rows(), self.cols()),
+    };
+    if rows > grid_rows || cols > grid_cols {
+      return None;
+    }
+
+    let top = self.top.min(grid_rows - rows);
+    let left = self.left.min(grid_cols - cols);
+    Some(Rect::from_corners((top, left), (top + rows - 1, left + cols - 1)))
+  }
+
+  /// Where a cell inside this rectangle ends up inside the target, after the transform
+  pub fn transform_cell(&self, target: &Rect, transform: Transform, (row, col): (usize, usize)) -> (usize, usize) {
+    let (row, col) = (row - self.top, col - self.left);
+    let (row, col) = match transform {
+      Transform::RotateClockwise => (col, self.rows() - 1 - row),
+      Transform::RotateCounterClockwise => (self.cols() - 1 - col, row),
+      Transform::MirrorHorizontally => (row, self.cols() - 1 - col),
+      Transform::MirrorVertically => (self.rows() - 1 - row, col),
+    };
+    (target.top + row, target.left + col)
+  }
+
+  /// Where a cell inside this rectangle ends up after shifting, wrapping around the edges
+  pub fn shift_cell(&self, (row, col): (usize, usize), row_offset: isize, col_offset: isize) -> (usize, usize) {
+    let row = (row as isize - self.top as isize + row_offset).rem_euclid(self.rows() as isize);
+    let col = (col as isize - self.left as isize + col_offset).rem_euclid(self.cols() as isize);
+    (self.top + row as usize, self.left + col as usize)
+  }
+}
+
+/// Ways to rearrange an area of a grid, the commands inside are remapped by each ISA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+  RotateClockwise,
+  RotateCounterClockwise,
+  /// Flip left to right
+  MirrorHorizontally,
+  /// Flip top to bottom
+  MirrorVertically,
+}
+
+/// Which way the instruction pointer moves through a grid
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+  Up,
+  Down,
+  Left,
+  #[default]
+  Right,
+}
+
+impl Direction {
+  pub fn get_arrow(&self) -> char {
+    match self {
+      Self::Up => '▲',
+      Self::Down => '▼',
+      Self::Left => '◄',
+      Self::Right => '►',
+    }
+  }
+
+  pub fn is_right(&self) -> bool {
+    *self == Self::Right
+  }
+
+  /// The direction that goes the same way once the grid around it is rotated or mirrored
+  pub fn transformed(self, transform: Transform) -> Self {
+    match (transform, self) {
+      (Transform::RotateClockwise, Self::Up) => Self::Right,
+      (Transform::RotateClockwise, Self::Right) => Self::Down,
+      (Transform::RotateClockwise, Self::Down) => Self::Left,
+      (Transform::RotateClockwise, Self::Left) => Self::Up,
+
+      (Transform::RotateCounterClockwise, Self::Up) => Self::Left,
+      (Transform::RotateCounterClockwise, Self::Left) => Self::Down,
+      (Transform::RotateCounterClockwise, Self::Down) => Self::Right,
+      (Transform::RotateCounterClockwise, Self::Right) => Self::Up,
+
+      (Transform::MirrorHorizontally, Self::Left) => Self::Right,
+      (Transform::MirrorHorizontally, Self::Right) => Self::Left,
+
+      (Transform::MirrorVertically, Self::Up) => Self::Down,
+      (Transform::MirrorVertically, Self::Down) => Self::Up,
+
+      (_, direction) => direction,
+    }
+  }
+}
+
+/// Where a program's instruction pointer starts, and which way it moves first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Start {
+  #[serde(rename = "startRow")]
+  pub row: usize,
+  #[serde(rename = "startCol")]
+  pub col: usize,
+
+  // Older saves don't have one, since programs always used to start out moving right
+  #[serde(rename = "startDirection", default, skip_serializing_if = "Direction::is_right")]
+  pub direction: Direction,
+}
+
+impl Start {
+  pub fn new(row: usize, col: usize, direction: Direction) -> Self {
+    Self { row, col, direction }
+  }
 }
 
 impl<C> Grid<C>
@@ -25,7 +218,143 @@ where
     Self {
       values: vec![vec![C::default(); cols]; rows],
       breakpoints: HashSet::new(),
+      annotations: Vec::new(),
+      highlight_annotations: false,
+    }
+  }
+
+  pub fn full_rect(&self) -> Rect {
+    Rect::from_corners((0, 0), (self.rows() - 1, self.cols() - 1))
+  }
+
+  /// Copy the values inside the rectangle, row by row
+  pub fn copy_rect(&self, rect: &Rect) -> Vec<Vec<C>> {
+    (rect.top..=rect.bottom)
+      .map(|row| self.values[row][rect.left..=rect.right].to_vec())
+      .collect()
+  }
+
+  pub fn fill_rect(&mut self, rect: &Rect, value: C) {
+    for (row, col) in rect.cells() {
+      self.values[row][col] = value.clone();
+    }
+  }
+
+  ///
+  /// Write the values with their top-left corner at the given cell
+  ///   Values of None are skipped, and so is anything past the edges of the grid
+  ///   Returns the area that was written to, or None if there weren't any values
+  ///
+  pub fn paste(&mut self, top: usize, left: usize, values: &[Vec<Option<C>>]) -> Option<Rect> {
+    for (row, values) in (top..self.rows()).zip(values) {
+      for (col, value) in (left..self.cols()).zip(values) {
+        if let Some(value) = value {
+          self.values[row][col] = value.clone();
+        }
+      }
+    }
+
+    let cols = values.first()?.len();
+    let bottom = (top + values.len() - 1).min(self.rows() - 1);
+    let right = (left + cols.checked_sub(1)?).min(self.cols() - 1);
+    Some(Rect::from_corners((top, left), (bottom, right)))
+  }
+
+  ///
+  /// Move the values inside the rectangle, leaving empty cells behind
+  ///   Returns where they ended up, or None if they would go past an edge
+  ///
+  pub fn move_rect(&mut self, rect: &Rect, row_offset: isize, col_offset: isize) -> Option<Rect> {
+    let top = rect.top.checked_add_signed(row_offset)?;
+    let left = rect.left.checked_add_signed(col_offset)?;
+    let target = Rect::from_corners((top, left), (top + rect.rows() - 1, left + rect.cols() - 1));
+    if target.bottom >= self.rows() || target.right >= self.cols() {
+      return None;
+    }
+
+    let values: Vec<Vec<Option<C>>> = self
+      .copy_rect(rect)
+      .into_iter()
+      .map(|row| row.into_iter().map(Some).collect())
+      .collect();
+    self.fill_rect(rect, C::default());
+    self.paste(top, left, &values)
+  }
+
+  ///
+  /// Rotate or mirror the values inside the rectangle, passing each one through `map`
+  ///   Breakpoints and annotations inside the rectangle move with their cells
+  ///   Returns where the values ended up, or None if they don't fit in the grid,
+  ///   would land on cells outside the rectangle that aren't empty, or `map` refuses any of them
+  ///
+  pub fn transform_rect(&mut self, rect: &Rect, transform: Transform, map: impl Fn(&C) -> Option<C>) -> Option<Rect>
+  where
+    C: PartialEq,
+  {
+    let target = rect.transformed(transform, self.rows(), self.cols())?;
+    let values = self
+      .copy_rect(rect)
+      .iter()
+      .map(|row| row.iter().map(&map).collect())
+      .collect::<Option<Vec<Vec<C>>>>()?;
+    let empty = C::default();
+    let overwrites = target
+      .cells()
+      .filter(|&(row, col)| !rect.contains(row, col))
+      .any(|(row, col)| {
+        self.values[row][col] != empty || self.has_breakpoint(row, col) || self.has_annotation(row, col)
+      });
+    if overwrites {
+      return None;
+    }
+
+    self.move_cells(rect, values, |cell| rect.transform_cell(&target, transform, cell));
+    Some(target)
+  }
+
+  /// Shift the values inside the rectangle, the ones pushed past an edge come back in on the other side
+  pub fn shift_rect(&mut self, rect: &Rect, row_offset: isize, col_offset: isize) {
+    let values = self.copy_rect(rect);
+    self.move_cells(rect, values, |cell| rect.shift_cell(cell, row_offset, col_offset));
+  }
+
+  // The rectangle is cleared first, so cells can move anywhere without overwriting each other
+  fn move_cells(&mut self, rect: &Rect, values: Vec<Vec<C>>, destination: impl Fn((usize, usize)) -> (usize, usize)) {
+    let breakpoints: Vec<(usize, usize)> = self
+      .breakpoints
+      .iter()
+      .copied()
+      .filter(|&(row, col)| rect.contains(row, col))
+      .collect();
+
+    self.fill_rect(rect, C::default());
+    for point in &breakpoints {
+      self.breakpoints.remove(point);
+    }
+
+    for (row, col) in rect.cells() {
+      let (new_row, new_col) = destination((row, col));
+      self.values[new_row][new_col] = values[row - rect.top][col - rect.left].clone();
     }
+    self.breakpoints.extend(breakpoints.into_iter().map(&destination));
+
+    // Annotations can't share a cell, so the ones moving in replace any already there
+    let (moved, mut kept): (Vec<Annotation>, Vec<Annotation>) = std::mem::take(&mut self.annotations)
+      .into_iter()
+      .partition(|annotation| rect.contains(annotation.row, annotation.col));
+    let moved: Vec<Annotation> = moved
+      .into_iter()
+      .map(|annotation| {
+        let (row, col) = destination((annotation.row, annotation.col));
+        Annotation { row, col, ..annotation }
+      })
+      .collect();
+    kept.retain(|kept| !moved.iter().any(|moved| (moved.row, moved.col) == (kept.row, kept.col)));
+    self.annotations = kept;
+    self.annotations.extend(moved);
+    self
+      .annotations
+      .sort_by_key(|annotation| (annotation.row, annotation.col));
   }
 }
 
@@ -50,6 +379,15 @@ impl<C> Grid<C> {
     &self.values[row][col]
   }
 
+  /// Every value in the grid, row by row
+  pub fn values(&self) -> impl Iterator<Item = &C> + '_ {
+    self.values.iter().flatten()
+  }
+
+  pub fn breakpoints(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+    self.breakpoints.iter().copied()
+  }
+
   pub fn has_breakpoint(&self, row: usize, col: usize) -> bool {
     self.breakpoints.contains(&(row, col))
   }
@@ -65,6 +403,48 @@ impl<C> Grid<C> {
       self.breakpoints.insert(point);
     }
   }
+
+  pub fn annotation(&self, row: usize, col: usize) -> Option<&str> {
+    self
+      .annotation_index(row, col)
+      .ok()
+      .map(|index| self.annotations[index].text.as_str())
+  }
+
+  pub fn has_annotation(&self, row: usize, col: usize) -> bool {
+    self.annotation_index(row, col).is_ok()
+  }
+
+  /// Every annotation, from the top-left to the bottom-right
+  pub fn annotations(&self) -> impl Iterator<Item = &Annotation> + '_ {
+    self.annotations.iter()
+  }
+
+  /// Replace the cell's annotation, empty text removes it
+  pub fn set_annotation(&mut self, row: usize, col: usize, text: String) {
+    debug_assert!(row < self.rows());
+    debug_assert!(col < self.cols());
+
+    match (self.annotation_index(row, col), text.is_empty()) {
+      (Ok(index), true) => {
+        self.annotations.remove(index);
+      },
+      (Ok(index), false) => self.annotations[index].text = text,
+      (Err(_), true) => {},
+      (Err(index), false) => self.annotations.insert(index, Annotation { row, col, text }),
+    }
+  }
+
+  /// Whether `print` marks the annotated cells
+  pub fn set_highlight_annotations(&mut self, highlight: bool) {
+    self.highlight_annotations = highlight;
+  }
+
+  fn annotation_index(&self, row: usize, col: usize) -> Result<usize, usize> {
+    self
+      .annotations
+      .binary_search_by_key(&(row, col), |annotation| (annotation.row, annotation.col))
+  }
 }
 
 impl<C> Grid<C>
@@ -79,6 +459,89 @@ where
       .map(|row| row.iter().filter(|x| **x != default).count())
       .sum()
   }
+
+  /// Size of the smallest rectangle that holds every non-empty cell
+  pub fn used_area(&self) -> usize {
+    self.used_rect().map_or(0, |rect| rect.rows() * rect.cols())
+  }
+
+  /// Smallest rectangle that holds every non-empty cell, or None if the grid is empty
+  pub fn used_rect(&self) -> Option<Rect> {
+    let default = C::default();
+    let used: Vec<(usize, usize)> = self
+      .values
+      .iter()
+      .enumerate()
+      .flat_map(|(row, values)| {
+        values
+          .iter()
+          .enumerate()
+          .filter(|(_, x)| **x != default)
+          .map(move |(col, _)| (row, col))
+      })
+      .collect();
+
+    let rows = used.iter().map(|(row, _)| row);
+    let cols = used.iter().map(|(_, col)| col);
+    match (rows.clone().min(), rows.max(), cols.clone().min(), cols.max()) {
+      (Some(&top), Some(&bottom), Some(&left), Some(&right)) => Some(Rect {
+        top,
+        left,
+        bottom,
+        right,
+      }),
+      _ => None,
+    }
+  }
+}
+
+impl<C> Grid<C>
+where
+  C: Default + Clone + PartialEq,
+{
+  ///
+  /// Rotate or mirror the values in the area, or the whole program if there isn't one, passing each one through `map`
+  ///   The start follows its cell if it's inside, and turns so it still goes the same way
+  ///   Returns where the area ended up, or None if it can't be transformed, see transform_rect
+  ///
+  pub fn transform_program(
+    &mut self,
+    start: &mut Start,
+    area: Option<Rect>,
+    transform: Transform,
+    map: impl Fn(&C) -> Option<C>,
+  ) -> Option<Rect> {
+    let rect = area.unwrap_or_else(|| self.program_rect(start));
+    let target = self.transform_rect(&rect, transform, map)?;
+
+    if rect.contains(start.row, start.col) {
+      (start.row, start.col) = rect.transform_cell(&target, transform, (start.row, start.col));
+      start.direction = start.direction.transformed(transform);
+    }
+    Some(target)
+  }
+
+  /// Shift the values in the area, or the whole grid if there isn't one, wrapping around the edges like the instruction pointer does
+  pub fn shift_program(&mut self, start: &mut Start, area: Option<Rect>, row_offset: isize, col_offset: isize) {
+    let rect = area.unwrap_or(self.full_rect());
+    self.shift_rect(&rect, row_offset, col_offset);
+
+    if rect.contains(start.row, start.col) {
+      (start.row, start.col) = rect.shift_cell((start.row, start.col), row_offset, col_offset);
+    }
+  }
+
+  // Every value and the start, so a transform of the whole program doesn't need the whole grid to fit
+  fn program_rect(&self, start: &Start) -> Rect {
+    let cell = (start.row, start.col);
+    match self.used_rect() {
+      Some(rect) => Rect::from_corners(
+        (rect.top.min(start.row), rect.left.min(start.col)),
+        (rect.bottom.max(start.row), rect.right.max(start.col)),
+      ),
+      None => Rect::from_corners(cell, cell),
+    }
+  }
 }
 
 impl<C> Printable for Grid<C>
@@ -97,28 +560,39 @@ where
     stdout.queue(cursor::MoveLeft(cols as u16 + 2))?;
     stdout.queue(cursor::MoveDown(1))?;
 
-    let mut in_breakpoint = false;
+    // Breakpoints win over annotations when a cell has both
+    let mut highlight = None;
     for (row_index, row) in self.values.iter().enumerate() {
       write!(stdout, "│")?;
       for (col_index, command) in row.iter().enumerate() {
-        if self.has_breakpoint(row_index, col_index) {
-          if !in_breakpoint {
-            stdout
-              .queue(style::SetBackgroundColor(Color::DarkCyan))?
-              .queue(style::SetForegroundColor(Color::Black))?;
-            in_breakpoint = true;
+        let cell_highlight = if self.has_breakpoint(row_index, col_index) {
+          Some(Color::DarkCyan)
+        } else if self.highlight_annotations && self.has_annotation(row_index, col_index) {
+          Some(Color::DarkYellow)
+        } else {
+          None
+        };
+
+        if cell_highlight != highlight {
+          match cell_highlight {
+            Some(color) => {
+              stdout
+                .queue(style::SetBackgroundColor(color))?
+                .queue(style::SetForegroundColor(Color::Black))?;
+            },
+            None => {
+              stdout.queue(style::ResetColor)?;
+            },
           }
-        } else if in_breakpoint {
-          stdout.queue(style::ResetColor)?;
-          in_breakpoint = false;
+          highlight = cell_highlight;
         }
 
         command.print()?;
       }
 
-      if in_breakpoint {
+      if highlight.is_some() {
         stdout.queue(style::ResetColor)?;
-        in_breakpoint = false;
+        highlight = None;
       }
       write!(stdout, "│")?;
 
@@ -131,3 +605,135 @@ where
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // A 2 by 3 area numbered row by row, in the middle of a 5 by 5 grid
+  fn numbered_grid() -> (Grid<u8>, Rect) {
+    let mut grid = Grid::new(5, 5);
+    let rect = Rect::from_corners((1, 1), (2, 3));
+    for (value, (row, col)) in rect.cells().enumerate() {
+      grid.set_value(row, col, value as u8 + 1);
+    }
+    (grid, rect)
+  }
+
+  #[test]
+  fn transforms_of_a_non_square_rect() {
+    let expected: [(Transform, Rect, Vec<Vec<u8>>); 4] = [
+      (
+        Transform::RotateClockwise,
+        Rect::from_corners((1, 1), (3, 2)),
+        vec![vec![4, 1], vec![5, 2], vec![6, 3]],
+      ),
+      (
+        Transform::RotateCounterClockwise,
+        Rect::from_corners((1, 1), (3, 2)),
+        vec![vec![3, 6], vec![2, 5], vec![1, 4]],
+      ),
+      (
+        Transform::MirrorHorizontally,
+        Rect::from_corners((1, 1), (2, 3)),
+        vec![vec![3, 2, 1], vec![6, 5, 4]],
+      ),
+      (
+        Transform::MirrorVertically,
+        Rect::from_corners((1, 1), (2, 3)),
+        vec![vec![4, 5, 6], vec![1, 2, 3]],
+      ),
+    ];
+
+    for (transform, target, values) in expected {
+      let (mut grid, rect) = numbered_grid();
+      assert_eq!(rect.transformed(transform, 5, 5), Some(target));
+      assert_eq!(
+        grid.transform_rect(&rect, transform, |value| Some(*value)),
+        Some(target)
+      );
+      assert_eq!(grid.copy_rect(&target), values, "{transform:?}");
+      assert_eq!(grid.count_symbols(), 6, "{transform:?}");
+    }
+  }
+
+  #[test]
+  fn rotated_rect_moves_to_fit() {
+    let rect = Rect::from_corners((3, 2), (4, 4));
+    assert_eq!(
+      rect.transformed(Transform::RotateClockwise, 5, 5),
+      Some(Rect::from_corners((2, 2), (4, 3)))
+    );
+    assert_eq!(rect.transformed(Transform::RotateClockwise, 2, 5), None);
+  }
+
+  #[test]
+  fn rotation_refuses_to_cover_other_cells() {
+    let (mut grid, rect) = numbered_grid();
+    grid.set_value(3, 2, 9);
+    let before = grid.clone();
+    assert_eq!(
+      grid.transform_rect(&rect, Transform::RotateClockwise, |value| Some(*value)),
+      None
+    );
+    assert!(grid == before);
+
+    let (mut grid, rect) = numbered_grid();
+    grid.toggle_breakpoint(3, 1);
+    assert_eq!(
+      grid.transform_rect(&rect, Transform::RotateClockwise, |value| Some(*value)),
+      None
+    );
+
+    let (mut grid, rect) = numbered_grid();
+    grid.set_annotation(3, 1, "Note".into());
+    assert_eq!(
+      grid.transform_rect(&rect, Transform::RotateClockwise, |value| Some(*value)),
+      None
+    );
+  }
+
+  #[test]
+  fn start_turns_with_the_program() {
+    let (mut grid, _) = numbered_grid();
+    let mut start = Start::new(1, 1, Direction::Right);
+
+    // The whole program is the numbered area, since the start is inside it
+    grid.transform_program(&mut start, None, Transform::RotateClockwise, |value| Some(*value));
+    assert_eq!(start, Start::new(1, 2, Direction::Down));
+
+    grid.transform_program(&mut start, None, Transform::MirrorVertically, |value| Some(*value));
+    assert_eq!(start, Start::new(3, 2, Direction::Up));
+
+    grid.transform_program(&mut start, None, Transform::RotateCounterClockwise, |value| {
+      Some(*value)
+    });
+    assert_eq!(start, Start::new(1, 3, Direction::Left));
+
+    grid.transform_program(&mut start, None, Transform::MirrorHorizontally, |value| Some(*value));
+    assert_eq!(start, Start::new(1, 1, Direction::Right));
+  }
+
+  #[test]
+  fn loaded_annotations_are_sorted() {
+    let grid: Grid<u8> = serde_json::from_str(
+      r#"{
+        "values": [[0, 0], [0, 0]],
+        "annotations": [
+          { "row": 1, "col": 0, "text": "Third" },
+          { "row": 0, "col": 1, "text": "Replaced" },
+          { "row": 0, "col": 0, "text": "First" },
+          { "row": 1, "col": 1, "text": "" },
+          { "row": 0, "col": 1, "text": "Second" }
+        ]
+      }"#,
+    )
+    .unwrap();
+
+    let texts: Vec<&str> = grid.annotations().map(|annotation| annotation.text.as_str()).collect();
+    assert_eq!(texts, vec!["First", "Second", "Third"]);
+    assert_eq!(grid.annotation(1, 0), Some("Third"));
+    assert_eq!(grid.annotation(0, 1), Some("Second"));
+    assert!(!grid.has_annotation(1, 1));
+  }
+}
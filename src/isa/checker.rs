@@ -0,0 +1,83 @@
+use rlua::prelude::*;
+use std::fmt;
+
+use super::sandbox::{limit_instructions, script_error};
+
+// Optional global function in the level script
+const CHECKER_FUNCTION: &str = "checkOutputs";
+
+///
+/// Lua function from the level script that decides if the outputs are correct
+///   Used instead of comparing against the expected outputs, for puzzles with more than one right answer
+///   The Lua state is kept alive so the function can be called while the program runs
+///
+pub struct OutputChecker {
+  lua: Lua,
+  lua_file: String,
+  function: LuaRegistryKey,
+}
+
+pub enum CheckResult {
+  Correct,
+  Incomplete,
+  Wrong(String),
+}
+
+impl OutputChecker {
+  /// Returns None if the script doesn't define a checker function
+  pub fn from_script(lua: Lua, lua_file: &str) -> Result<Option<Self>, String> {
+    let function = lua
+      .context(
+        |ctx| match ctx.globals().get::<_, Option<LuaFunction>>(CHECKER_FUNCTION)? {
+          Some(function) => ctx.create_registry_value(function).map(Some),
+          None => Ok(None),
+        },
+      )
+      .map_err(|e| script_error(lua_file, &e))?;
+
+    Ok(function.map(|function| Self {
+      lua,
+      lua_file: lua_file.to_string(),
+      function,
+    }))
+  }
+
+  ///
+  /// Call the checker function with the test case inputs and current outputs
+  ///   It returns true if the outputs are correct, nil if more outputs are needed,
+  ///   or false (with an optional message) if the outputs are wrong
+  ///
+  pub fn check<A>(&self, args: A) -> CheckResult
+  where
+    A: for<'lua> ToLuaMulti<'lua>,
+  {
+    limit_instructions(&self.lua);
+    let result: LuaResult<CheckResult> = self.lua.context(|ctx| {
+      let function: LuaFunction = ctx.registry_value(&self.function)?;
+      let (value, message) = function.call::<_, (LuaValue, Option<String>)>(args)?;
+      Ok(match value {
+        LuaValue::Boolean(true) => CheckResult::Correct,
+        LuaValue::Nil => CheckResult::Incomplete,
+        LuaValue::Boolean(false) => match message {
+          Some(message) => CheckResult::Wrong(format!("Wrong outputs: {message}")),
+          None => CheckResult::Wrong("Wrong outputs".into()),
+        },
+        _ => CheckResult::Wrong(format!("{CHECKER_FUNCTION} must return true, false, or nil")),
+      })
+    });
+
+    match result {
+      Ok(result) => result,
+      Err(e) => CheckResult::Wrong(format!(
+        "Error in {CHECKER_FUNCTION}: {}",
+        script_error(&self.lua_file, &e)
+      )),
+    }
+  }
+}
+
+impl fmt::Debug for OutputChecker {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("OutputChecker")
+  }
+}
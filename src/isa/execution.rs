@@ -0,0 +1,166 @@
+use rand::Rng;
+use std::collections::VecDeque;
+
+use super::Machine;
+use crate::statistics::{Statistics, TestCaseScore};
+
+// Number of steps that can be undone
+const MAX_HISTORY: usize = 1000;
+
+/// Runs a solution against each test case in order and keeps score
+pub struct Execution<M: Machine> {
+  machines: Vec<M>,
+  test_case: usize,
+  scores: Vec<TestCaseScore>,
+
+  history: VecDeque<Snapshot<M>>,
+  max_history: usize,
+}
+
+/// Everything needed to undo a single step
+struct Snapshot<M: Machine> {
+  machine: M,
+  test_case: usize,
+  // Scores are only ever added, so the count is enough to undo them
+  num_scores: usize,
+}
+
+pub enum ExecutionStep<E> {
+  Running,
+  TestCasePassed,
+  Solved(Statistics),
+  Failed(E, usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+  None,
+  Slow,
+  Normal,
+  Fast,
+  ExtremelyFast,
+  Turbo,
+  SuperTurbo,
+}
+
+impl<M: Machine> Execution<M> {
+  pub fn new(machines: Vec<M>) -> Self {
+    debug_assert!(!machines.is_empty());
+
+    Self {
+      machines,
+      test_case: 0,
+      scores: Vec::new(),
+      history: VecDeque::new(),
+      max_history: MAX_HISTORY,
+    }
+  }
+
+  /// Skip recording history when nobody will step backwards, like when scoring headless
+  pub fn without_history(mut self) -> Self {
+    self.max_history = 0;
+    self
+  }
+
+  /// Index of the machine currently being executed
+  pub fn test_case(&self) -> usize {
+    self.test_case
+  }
+
+  pub fn current(&self) -> &M {
+    &self.machines[self.test_case]
+  }
+
+  pub fn current_mut(&mut self) -> &mut M {
+    &mut self.machines[self.test_case]
+  }
+
+  /// Includes the machines saved in the history, so changes like breakpoints survive stepping back
+  pub fn machines_mut(&mut self) -> impl Iterator<Item = &mut M> {
+    self
+      .machines
+      .iter_mut()
+      .chain(self.history.iter_mut().map(|snapshot| &mut snapshot.machine))
+  }
+
+  /// Number of steps that can currently be undone
+  pub fn history_len(&self) -> usize {
+    self.history.len()
+  }
+
+  pub fn is_at_breakpoint(&self) -> bool {
+    self.current().is_at_breakpoint()
+  }
+
+  /// Step the current machine, moving on to the next test case once it is solved
+  pub fn step(&mut self) -> ExecutionStep<M::Error> {
+    if self.max_history > 0 {
+      if self.history.len() >= self.max_history {
+        self.history.pop_front();
+      }
+      self.history.push_back(Snapshot {
+        machine: self.current().snapshot(),
+        test_case: self.test_case,
+        num_scores: self.scores.len(),
+      });
+    }
+
+    let num_machines = self.machines.len();
+    let current_machine = &mut self.machines[self.test_case];
+
+    match current_machine.step() {
+      Ok(false) => ExecutionStep::Running,
+      Ok(true) => {
+        self.scores.push(TestCaseScore {
+          cycles: current_machine.get_cycle(),
+          instructions: current_machine.instructions_executed(),
+          peak_stack_depth: current_machine.peak_stack_depth(),
+          blocked_cycles: current_machine.blocked_cycles(),
+        });
+        if self.test_case + 1 < num_machines {
+          self.test_case += 1;
+          return ExecutionStep::TestCasePassed;
+        }
+
+        ExecutionStep::Solved(Statistics::new(
+          &self.scores,
+          current_machine.count_symbols(),
+          current_machine.count_area(),
+        ))
+      },
+      Err((e, processor)) => ExecutionStep::Failed(e, processor),
+    }
+  }
+
+  /// Undo the last step, returns false if there is no history left
+  pub fn step_back(&mut self) -> bool {
+    let Some(snapshot) = self.history.pop_back() else {
+      return false;
+    };
+
+    self.test_case = snapshot.test_case;
+    self.scores.truncate(snapshot.num_scores);
+    self.machines[self.test_case] = snapshot.machine;
+    true
+  }
+
+  /// Undo steps until the machine is back at a breakpoint or the history runs out
+  pub fn step_back_to_breakpoint(&mut self) {
+    while self.step_back() && !self.is_at_breakpoint() {}
+  }
+}
+
+impl Speed {
+  pub fn num_steps(self) -> usize {
+    let mut rng = rand::thread_rng();
+    match self {
+      Self::None => 0,
+      Self::Slow => 1,
+      Self::Normal => rng.gen_range(4..=8),
+      Self::Fast => rng.gen_range(20..=30),
+      Self::ExtremelyFast => rng.gen_range(90..=110),
+      Self::Turbo => rng.gen_range(900..=1100),
+      Self::SuperTurbo => rng.gen_range(9000..=11000),
+    }
+  }
+}
@@ -0,0 +1,123 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::printable::Printable;
+
+/// Common interface for the virtual machines of every instruction set
+pub trait Machine: Sized {
+  type Error: MachineError;
+
+  ///
+  /// Execute a single cycle
+  ///   Returns Ok(true) when the puzzle is solved
+  ///   Errors are tagged with the index of the processor that raised them
+  ///
+  fn step(&mut self) -> Result<bool, (Self::Error, usize)>;
+
+  /// Number of the test case being run, counting from 1
+  fn test_case(&self) -> usize;
+  fn get_cycle(&self) -> u32;
+  fn count_symbols(&self) -> usize;
+  /// Size of the rectangle around every symbol, added up for each program
+  fn count_area(&self) -> usize;
+  /// Non-empty instructions run so far, not counting skipped or blocked ones
+  fn instructions_executed(&self) -> u32;
+  /// Most values held on any stack at once
+  fn peak_stack_depth(&self) -> usize;
+  /// Cycles spent waiting on another processor, for machines that have more than one
+  fn blocked_cycles(&self) -> Option<u32> {
+    None
+  }
+  fn is_at_breakpoint(&self) -> bool;
+
+  ///
+  /// Copy the machine state so it can be restored later
+  ///   The copy must not share anything with the original
+  ///   Loop detection starts over in the copy, since remembering every state would make snapshots expensive
+  ///
+  fn snapshot(&self) -> Self;
+}
+
+/// Any virtual machine error should implement this interface
+pub trait MachineError: Printable {
+  fn get_msg(&self) -> Cow<'static, str>;
+}
+
+// Forgotten all at once when reached, so a long program doesn't keep growing the set
+//   A loop shorter than this is still found, on its way around after the set was cleared
+const MAX_SEEN_STATES: usize = 100_000;
+
+///
+/// Detects programs that are stuck in an infinite loop
+///   A deterministic machine that revisits a state without reading or writing anything will loop forever.
+///   Only states since the last input/output are remembered, since earlier ones can never come back.
+///   Whole states are kept instead of hashes, so a hash collision can't end a working program.
+///
+#[derive(Debug, Clone)]
+pub struct LoopDetector<I, S> {
+  seen_states: HashSet<S>,
+  io_state: Option<I>,
+}
+
+impl<I, S> Default for LoopDetector<I, S> {
+  fn default() -> Self {
+    Self {
+      seen_states: HashSet::new(),
+      io_state: None,
+    }
+  }
+}
+
+impl<I: PartialEq, S: Hash + Eq> LoopDetector<I, S> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Returns true if the machine has already been in this state
+  pub fn is_repeated(&mut self, io_state: I, state: S) -> bool {
+    if self.io_state.as_ref() != Some(&io_state) {
+      self.seen_states.clear();
+      self.io_state = Some(io_state);
+    }
+    if self.seen_states.len() >= MAX_SEEN_STATES {
+      self.seen_states.clear();
+    }
+
+    !self.seen_states.insert(state)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn repeated_state_is_a_loop() {
+    let mut detector = LoopDetector::new();
+    for state in [1, 2, 3] {
+      assert!(!detector.is_repeated(0, state));
+    }
+    assert!(detector.is_repeated(0, 1));
+
+    // Reading or writing something starts over
+    assert!(!detector.is_repeated(1, 2));
+    assert!(detector.is_repeated(1, 2));
+  }
+
+  #[test]
+  fn long_program_without_a_loop() {
+    let mut detector = LoopDetector::new();
+    for state in 0..MAX_SEEN_STATES * 3 {
+      assert!(!detector.is_repeated((), state));
+      assert!(detector.seen_states.len() <= MAX_SEEN_STATES);
+    }
+
+    // Still found after the set was cleared, once the loop comes around again
+    let mut found = false;
+    for state in (0..1000).cycle().take(2000) {
+      found |= detector.is_repeated((), state);
+    }
+    assert!(found);
+  }
+}
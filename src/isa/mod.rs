@@ -1,28 +1,71 @@
-use crate::{level::LevelIndex, state::State};
+use crate::level::{Level, LevelIndex, LevelLimits, LevelType, StaticTestCase};
+use crate::solution_file::{ProgramText, SolutionFile};
+use crate::state::State;
+use crate::statistics::{RunResult, SolutionRun, Statistics};
+use rlua::FromLuaMulti;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt::Display;
+use std::path::Path;
+use std::rc::Rc;
 use uuid::Uuid;
 
+mod checker;
+mod execution;
+mod machine;
+mod palette;
 pub mod parallel;
+mod prompt;
+mod sandbox;
 pub mod standard;
+mod test_cases;
 
 // Re-export the puzzle types
 pub use parallel::Parallel;
 pub use standard::Standard;
 
+pub use checker::{CheckResult, OutputChecker};
+pub use execution::{Execution, ExecutionStep, Speed};
+pub use machine::{LoopDetector, Machine, MachineError};
+pub use palette::{parse_command, CommandPalette};
+
 pub const MAX_SOLUTION_NAME_LEN: usize = 30;
+pub const MAX_ANNOTATION_LEN: usize = 40;
+pub const MAX_NOTE_LEN: usize = 200;
+/// Older runs are dropped from a solution's history, so the save file doesn't keep growing
+pub const MAX_RUN_HISTORY: usize = 20;
 static COPY_STR: &str = " (Copy)";
 
+// Every level is scored against the same set of test cases
+pub const SEED: u32 = 0xdeadbeef;
+pub const NUM_TEST_CASES: usize = 25;
+
+/// Generated test cases, and the version that changes whenever they could change
+pub type VersionedTestCases<P> = (Vec<P>, String);
+
 /// All level types need to implement this interface
 pub trait InstructionSetArchitecture {
   type Solution: Solution;
   type Puzzle;
+  /// Values returned by the Lua "generateTestCase" function
+  type TestCase: Serialize + DeserializeOwned + for<'lua> FromLuaMulti<'lua>;
+  type Machine: Machine;
+  type Command: Copy + PartialEq + Default + Display + DeserializeOwned;
 
-  fn generate_test_cases(
-    folder: &str,
-    lua_file: &str,
-    seed: u32,
-    n: usize,
-  ) -> Result<Vec<Self::Puzzle>, Box<dyn Error>>;
+  const LEVEL_TYPE: LevelType;
+
+  /// Limits for levels that don't set their own in the pack file
+  const DEFAULT_LIMITS: LevelLimits;
+  /// Largest limits that still fit in the minimum terminal size
+  const MAX_LIMITS: LevelLimits;
+
+  /// Performs validation and returns a printable error string
+  fn new_puzzle(test_case: Self::TestCase, limits: &LevelLimits) -> Result<Self::Puzzle, String>;
+  /// Returns a printable error string if the test case is written in the form for another level type
+  fn static_test_case(test_case: &StaticTestCase) -> Result<Self::TestCase, String>;
+  fn set_checker(puzzle: &mut Self::Puzzle, checker: Rc<OutputChecker>);
 
   fn open_editor(
     level_index: LevelIndex,
@@ -30,17 +73,161 @@ pub trait InstructionSetArchitecture {
     solution: Self::Solution,
     test_cases: Vec<Self::Puzzle>,
     test_case_index: usize,
+    palette: CommandPalette<Self::Command>,
   ) -> impl State;
+
+  /// Every command placed in the solution, including empty cells
+  fn commands_used(solution: &Self::Solution) -> Vec<Self::Command>;
+
+  fn new_machine(
+    solution: &Self::Solution,
+    test_case: usize,
+    puzzle: &Self::Puzzle,
+    limits: &LevelLimits,
+  ) -> Self::Machine;
+
+  // -------- Default Implementation: --------
+
+  ///
+  /// Load and run the Lua code to generate the puzzles, after any test cases written in the pack file
+  ///   Generated test cases are saved in the cache folder, and only generated again when the scripts change
+  ///
+  fn generate_test_cases(
+    folder: &str,
+    level: &Level,
+    seed: u32,
+    n: usize,
+    cache_folder: Option<&Path>,
+  ) -> Result<Vec<Self::Puzzle>, Box<dyn Error>>
+  where
+    Self: Sized,
+  {
+    test_cases::generate_test_cases::<Self>(folder, level, seed, n, cache_folder).map(|(test_cases, _)| test_cases)
+  }
+
+  /// Same as generate_test_cases, but also returns a version that changes whenever the test cases could change
+  fn generate_versioned_test_cases(
+    folder: &str,
+    level: &Level,
+    seed: u32,
+    n: usize,
+    cache_folder: Option<&Path>,
+  ) -> Result<VersionedTestCases<Self::Puzzle>, Box<dyn Error>>
+  where
+    Self: Sized,
+  {
+    test_cases::generate_test_cases::<Self>(folder, level, seed, n, cache_folder)
+  }
+
+  /// Puzzles from the cache alone, or None if they would need to be generated, see test_cases::cached_test_cases
+  fn cached_test_cases(
+    folder: &str,
+    level: &Level,
+    seed: u32,
+    n: usize,
+    cache_folder: &Path,
+  ) -> Option<Result<Vec<Self::Puzzle>, String>>
+  where
+    Self: Sized,
+  {
+    test_cases::cached_test_cases::<Self>(folder, level, seed, n, cache_folder)
+      .map(|result| result.map(|(test_cases, _)| test_cases))
+  }
+
+  /// Version of the level's test cases from the cache alone, see test_cases::cached_test_case_version
+  fn cached_test_case_version(folder: &str, level: &Level, seed: u32, n: usize, cache_folder: &Path) -> Option<String>
+  where
+    Self: Sized,
+  {
+    test_cases::cached_test_case_version::<Self>(folder, level, seed, n, cache_folder)
+  }
+
+  /// Run the Lua code without the cache or any validation, so the values can be checked by the pack linter
+  fn generate_raw_test_cases(folder: &str, lua_file: &str, seed: u32, n: usize) -> Result<Vec<Self::TestCase>, String>
+  where
+    Self: Sized,
+  {
+    test_cases::run_generator::<Self>(folder, lua_file, seed, n).map(|(_, test_cases)| test_cases)
+  }
+
+  /// Commands the level allows, from the pack file
+  fn command_palette(level: &Level) -> Result<CommandPalette<Self::Command>, String> {
+    CommandPalette::new(level.allowed_commands(), level.forbidden_commands())
+  }
+
+  /// Run the solution against every test case without rendering anything
+  fn run_solution(
+    solution: &Self::Solution,
+    test_cases: &[Self::Puzzle],
+    limits: &LevelLimits,
+  ) -> Result<Statistics, TestCaseFailure> {
+    let machines = test_cases
+      .iter()
+      .enumerate()
+      .map(|(index, puzzle)| Self::new_machine(solution, index + 1, puzzle, limits))
+      .collect();
+
+    let mut execution = Execution::new(machines).without_history();
+    loop {
+      match execution.step() {
+        ExecutionStep::Running | ExecutionStep::TestCasePassed => {},
+        ExecutionStep::Solved(statistics) => return Ok(statistics),
+        ExecutionStep::Failed(error, _) => {
+          return Err(TestCaseFailure {
+            test_case: execution.test_case() + 1,
+            cycle: execution.current().get_cycle(),
+            message: error.get_msg().into_owned(),
+          });
+        },
+      }
+    }
+  }
+}
+
+/// Describes why a solution failed when run outside of the editor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestCaseFailure {
+  pub test_case: usize,
+  pub cycle: u32,
+  pub message: String,
 }
 
 /// Any solution type should implement this interface
 pub trait Solution: Clone {
-  fn new(name: impl Into<String>) -> Self;
+  /// Create an empty solution with a grid sized for the level
+  fn new(name: impl Into<String>, limits: &LevelLimits) -> Self;
 
   fn name(&self) -> &str;
   fn rename(&mut self, new_name: impl Into<String>);
 
+  /// Identifies the solution in the run history, even when solutions are reordered
+  fn id(&self) -> Uuid;
+  fn set_id(&mut self, id: Uuid);
+
+  /// Free-form text about the whole solution, it doesn't change how it runs
+  fn note(&self) -> &str;
+  fn set_note(&mut self, note: impl Into<String>);
+
+  /// Commands in the grids, annotations don't count
   fn symbols_used(&self) -> usize;
+
+  /// Every program grid in the solution, used for the plain-text file format
+  fn to_programs(&self) -> Vec<ProgramText>;
+  fn from_programs(name: impl Into<String>, programs: &[ProgramText], limits: &LevelLimits) -> Result<Self, String>;
+
+  // -------- Default Implementation: --------
+
+  /// Rules the editor enforces that the level's palette doesn't, checked again for solutions from files
+  fn check_rules(&self) -> Result<(), String> {
+    Ok(())
+  }
+
+  /// Changes whenever a command or start position changes, but not for breakpoints
+  fn program_hash(&self) -> String {
+    let code: String = self.to_programs().iter().map(ProgramText::code).collect();
+    format!("{:016x}", test_cases::stable_hash(code.as_bytes()))
+  }
 }
 
 /// Manages solutions for a level type
@@ -48,13 +235,34 @@ pub trait SolutionManager<ISA: InstructionSetArchitecture> {
   fn get_all_solutions(&self, level_id: Uuid) -> &Vec<ISA::Solution>;
   fn get_all_solutions_mut(&mut self, level_id: Uuid) -> &mut Vec<ISA::Solution>;
 
+  /// Runs for every solution, oldest first, keyed by the solution id
+  fn get_run_history(&self) -> &HashMap<Uuid, Vec<SolutionRun>>;
+  fn get_run_history_mut(&mut self) -> &mut HashMap<Uuid, Vec<SolutionRun>>;
+
   // -------- Default Implementation: --------
 
+  fn get_solution_runs(&self, level_id: Uuid, solution_index: usize) -> &[SolutionRun] {
+    let solution_id = self.get_all_solutions(level_id)[solution_index].id();
+    self.get_run_history().get(&solution_id).map_or(&[], Vec::as_slice)
+  }
+
+  /// Remember the result of running the solution as it is currently saved
+  fn record_run(&mut self, level_id: Uuid, solution_index: usize, result: RunResult) {
+    let solution = &self.get_all_solutions(level_id)[solution_index];
+    let (solution_id, run) = (solution.id(), SolutionRun::new(solution.program_hash(), result));
+
+    let runs = self.get_run_history_mut().entry(solution_id).or_default();
+    if runs.len() >= MAX_RUN_HISTORY {
+      runs.remove(0);
+    }
+    runs.push(run);
+  }
+
   /// Return the index of the new solution
-  fn new_solution(&mut self, level_id: Uuid) -> usize {
+  fn new_solution(&mut self, level_id: Uuid, limits: &LevelLimits) -> usize {
     let all_solutions = self.get_all_solutions_mut(level_id);
     let new_solution_name = format!("Solution {}", all_solutions.len() + 1);
-    all_solutions.push(ISA::Solution::new(new_solution_name));
+    all_solutions.push(ISA::Solution::new(new_solution_name, limits));
     all_solutions.len() - 1
   }
 
@@ -94,6 +302,14 @@ pub trait SolutionManager<ISA: InstructionSetArchitecture> {
     let new_solution_name = format!("{}{}", remove_copy_suffix(new_solution.name()), COPY_STR);
     new_solution.rename(new_solution_name);
 
+    // The copy has the same programs, so it starts with the same history
+    let old_id = new_solution.id();
+    new_solution.set_id(Uuid::new_v4());
+    if let Some(runs) = self.get_run_history().get(&old_id).cloned() {
+      self.get_run_history_mut().insert(new_solution.id(), runs);
+    }
+
+    let all_solutions = self.get_all_solutions_mut(level_id);
     all_solutions.insert(solution_index + 1, new_solution);
     solution_index + 1
   }
@@ -105,6 +321,37 @@ pub trait SolutionManager<ISA: InstructionSetArchitecture> {
 
   fn delete_solution(&mut self, level_id: Uuid, solution_index: usize) {
     let all_solutions = self.get_all_solutions_mut(level_id);
-    all_solutions.remove(solution_index);
+    let solution = all_solutions.remove(solution_index);
+    self.get_run_history_mut().remove(&solution.id());
+  }
+
+  fn export_solution(&self, level_id: Uuid, solution_index: usize) -> SolutionFile {
+    let solution = &self.get_all_solutions(level_id)[solution_index];
+    SolutionFile {
+      name: solution.name().to_string(),
+      level_id,
+      level_type: ISA::LEVEL_TYPE,
+      note: solution.note().to_string(),
+      programs: solution.to_programs(),
+    }
+  }
+
+  /// Returns the index of the new solution, or a printable error string
+  fn import_solution(
+    &mut self,
+    file: &SolutionFile,
+    limits: &LevelLimits,
+    palette: &CommandPalette<ISA::Command>,
+  ) -> Result<usize, String> {
+    if file.level_type != ISA::LEVEL_TYPE {
+      return Err("Solution is for a different type of level".into());
+    }
+
+    let mut solution = ISA::Solution::from_programs(file.name.as_str(), &file.programs, limits)?;
+    solution.set_note(file.note.as_str());
+    palette.check(ISA::commands_used(&solution))?;
+    let all_solutions = self.get_all_solutions_mut(file.level_id);
+    all_solutions.push(solution);
+    Ok(all_solutions.len() - 1)
   }
 }
@@ -0,0 +1,88 @@
+use serde::de::value::{Error as ValueError, StrDeserializer};
+use serde::de::{DeserializeOwned, IntoDeserializer};
+use std::fmt::Display;
+
+/// Commands that a level allows to be placed in the grid
+#[derive(Debug, Clone)]
+pub struct CommandPalette<C> {
+  allowed: Option<Vec<C>>,
+  forbidden: Vec<C>,
+}
+
+impl<C> Default for CommandPalette<C> {
+  fn default() -> Self {
+    Self {
+      allowed: None,
+      forbidden: Vec::new(),
+    }
+  }
+}
+
+impl<C> CommandPalette<C>
+where
+  C: Copy + PartialEq + Default + Display + DeserializeOwned,
+{
+  ///
+  /// Build the palette from the command names in the pack file
+  ///   Names can be either the symbol ("+") or the alias ("add")
+  ///   Returns a printable error string for unknown names
+  ///
+  pub fn new(allowed: Option<&[String]>, forbidden: &[String]) -> Result<Self, String> {
+    let parse_all = |names: &[String]| {
+      names
+        .iter()
+        .map(|name| parse_command(name))
+        .collect::<Result<Vec<C>, _>>()
+    };
+
+    Ok(Self {
+      allowed: allowed.map(parse_all).transpose()?,
+      forbidden: parse_all(forbidden)?,
+    })
+  }
+
+  /// Clearing a cell is always allowed
+  pub fn allows(&self, command: C) -> bool {
+    command == C::default()
+      || (self.allowed.as_ref().is_none_or(|allowed| allowed.contains(&command)) && !self.forbidden.contains(&command))
+  }
+
+  /// Each disallowed command used, in the order they are first found
+  pub fn disallowed(&self, commands: impl IntoIterator<Item = C>) -> Vec<C> {
+    let mut disallowed = Vec::new();
+    for command in commands {
+      if !self.allows(command) && !disallowed.contains(&command) {
+        disallowed.push(command);
+      }
+    }
+    disallowed
+  }
+
+  /// Returns a printable error string listing any disallowed commands
+  pub fn check(&self, commands: impl IntoIterator<Item = C>) -> Result<(), String> {
+    let disallowed = self.disallowed(commands);
+    if disallowed.is_empty() {
+      return Ok(());
+    }
+
+    let symbols: Vec<String> = disallowed.iter().map(ToString::to_string).collect();
+    Err(format!(
+      "Uses commands not allowed in this level: {}",
+      symbols.join(" ")
+    ))
+  }
+
+  /// Instruction lines are only shown if the level allows at least one of their commands
+  pub fn filter_instructions<'a>(&self, lines: &[(&'a str, &'a [C])]) -> Vec<(&'a str, &'a [C])> {
+    lines
+      .iter()
+      .filter(|(_, commands)| commands.iter().any(|command| self.allows(*command)))
+      .copied()
+      .collect()
+  }
+}
+
+pub fn parse_command<C: DeserializeOwned>(name: &str) -> Result<C, String> {
+  let deserializer: StrDeserializer<'_, ValueError> = name.into_deserializer();
+  C::deserialize(deserializer).map_err(|_| format!("Unknown command \"{name}\""))
+}
@@ -1,19 +1,26 @@
 use crossterm::{
   cursor,
-  event::{self, Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind},
+  event::{self, Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
   style::{self, Color, Stylize},
   ExecutableCommand, QueueableCommand,
 };
 use std::io::{self, Write};
 
-use super::execute_state::{ExecuteState, Speed};
+use super::execute_state::ExecuteState;
 use super::puzzle::TestCaseSet;
 use super::solution::Solution;
 use super::vm::{Command, VirtualMachine};
+use crate::clipboard::Clipboard;
+use crate::grid::{Rect, Transform};
+use crate::isa;
+use crate::isa::prompt::{self, Prompt, PromptAction, PromptTarget};
+use crate::isa::{CommandPalette, Solution as _, Speed};
+use crate::keybindings::{Action, KeyBindings};
+use crate::level::{LevelIndex, LevelLimits};
 use crate::printable::Printable;
 use crate::state::{print_string, ShowHelpState, State};
+use crate::undo::UndoHistory;
 use crate::{global_state::GlobalState, isa::SolutionManager};
-use crate::{isa, level::LevelIndex};
 
 const GRID_ROW: u16 = 3;
 const GRID_COL: u16 = 0;
@@ -28,30 +35,93 @@ macro_rules! current_program {
   };
 }
 
-static INSTRUCTIONS: &str = r#"
-│Esc    = Main Menu
-│Tab    = Step
-│Space  = Start/Stop
-│[  ]   = Test Case
-│, .    = Breakpoint
-│
-│asdw  = ←↓→↑ (Move)
-│/ \   = / \ (Bounce)
-│$     = » (Skip)
-│0-9   = 0-9
-│p     = ☼ (Pop)
-│c     = © (Copy)
-│~     = ∫ (Swap)
-│^ v   = ∩ u (Rotate)
-│+ - * = (Add/Sub/Mul)
-│< = > = (Compare to 0)
-│i     = Ї (Input)
-│o     = Θ (Output)
-│?     = (Has input?)
-│t     = τ (Transmit)
-│r     = я (Receive)
-|T R   = Ť Ř (Try T/R?)
-│b     = Set start"#;
+static INSTRUCTIONS: &[&str] = &["│Esc   = Main Menu"];
+
+// Generated from the keybindings, after the fixed instructions above
+static ACTION_INSTRUCTIONS: &[(&[Action], &str)] = &[
+  (&[Action::Step], "Step"),
+  (&[Action::Run], "Start/Stop"),
+  (&[Action::PreviousTestCase, Action::NextTestCase], "Test Case"),
+  (&[Action::Breakpoint, Action::SecondBreakpoint], "Breakpoint"),
+  (&[Action::Undo, Action::Redo], "Undo/Redo"),
+  (&[Action::Select, Action::CopyAll], "Select/Copy All"),
+  (
+    &[Action::Annotate, Action::EditNote, Action::ToggleAnnotations],
+    "Notes/Highlight",
+  ),
+];
+
+// Replace the instructions above while an area is selected
+static SELECT_INSTRUCTIONS: &[&str] = &["│Esc   = Stop Selecting"];
+
+static SELECT_ACTION_INSTRUCTIONS: &[(&[Action], &str)] = &[
+  (&[Action::Copy, Action::Cut, Action::Paste], "Copy/Cut/Paste"),
+  (
+    &[
+      Action::MoveAreaLeft,
+      Action::MoveAreaDown,
+      Action::MoveAreaUp,
+      Action::MoveAreaRight,
+    ],
+    "Move Area",
+  ),
+  (
+    &[
+      Action::ShiftLeft,
+      Action::ShiftDown,
+      Action::ShiftUp,
+      Action::ShiftRight,
+    ],
+    "Shift Around",
+  ),
+  (&[Action::RotateCounterClockwise, Action::RotateClockwise], "Rotate"),
+  (&[Action::MirrorHorizontally, Action::MirrorVertically], "Mirror"),
+];
+
+// Only shown if the level allows at least one of the commands
+static COMMAND_INSTRUCTIONS: &[(&str, &[Command])] = &[
+  (
+    "←↓→↑ (Move)",
+    &[Command::Left, Command::Down, Command::Right, Command::Up],
+  ),
+  ("/ \\ (Bounce)", &[Command::ForwardSlash, Command::BackSlash]),
+  ("» (Skip)", &[Command::Skip]),
+  (
+    "0-9",
+    &[
+      Command::Zero,
+      Command::One,
+      Command::Two,
+      Command::Three,
+      Command::Four,
+      Command::Five,
+      Command::Six,
+      Command::Seven,
+      Command::Eight,
+      Command::Nine,
+    ],
+  ),
+  ("☼ (Pop)", &[Command::Pop]),
+  ("© (Copy)", &[Command::Copy]),
+  ("∫ (Swap)", &[Command::SwapTop2]),
+  ("∩ u (Rotate)", &[Command::RotateUp, Command::RotateDown]),
+  ("(Add/Sub/Mul)", &[Command::Add, Command::Subtract, Command::Multiply]),
+  (
+    "(Compare to 0)",
+    &[Command::IfLess, Command::IfEqual, Command::IfGreater],
+  ),
+  ("Ї Θ (In/Out)", &[Command::In, Command::Out]),
+  ("(Has input?)", &[Command::HasInput]),
+  ("τ я (Send/Recv)", &[Command::Transmit, Command::Receive]),
+  ("Ť Ř (Try T/R?)", &[Command::TryTransmit, Command::TryReceive]),
+];
+
+static START_INSTRUCTIONS: &[(&[Action], &str)] = &[(&[Action::SetStart], "Set start")];
+
+// Width of the keys column in the instructions
+const KEYS_WIDTH: usize = 5;
+
+// The status line is above the test case, left of the instructions
 
 pub struct EditorState {
   level_index: LevelIndex,
@@ -62,9 +132,21 @@ pub struct EditorState {
   cursor_row: isize,
   cursor_col: isize,
   is_program_1: bool,
+  // The corner of the selected area across from the cursor, always in the same program as the cursor
+  selection_anchor: Option<(usize, usize)>,
 
   test_cases: TestCaseSet,
   test_case_index: isize,
+
+  palette: CommandPalette<Command>,
+
+  // Kept in the editor, so it survives running the solution and coming back
+  history: UndoHistory<Solution>,
+  // The solution from before a right button drag, which is one edit however many cells it clears
+  erase_start: Option<Solution>,
+
+  in_prompt: Option<Prompt>,
+  highlight_annotations: bool,
 }
 
 impl EditorState {
@@ -74,6 +156,7 @@ impl EditorState {
     solution: Solution,
     test_cases: TestCaseSet,
     test_case_index: usize,
+    palette: CommandPalette<Command>,
   ) -> Self {
     Self {
       level_index,
@@ -82,34 +165,269 @@ impl EditorState {
       cursor_row: 0,
       cursor_col: 0,
       is_program_1: false,
+      selection_anchor: None,
       test_cases,
       test_case_index: test_case_index as isize,
+      palette,
+      history: UndoHistory::new(),
+      erase_start: None,
+      in_prompt: None,
+      highlight_annotations: false,
     }
   }
 
+  // Commands the level doesn't allow are ignored, and the whole area is filled if one is selected
   fn set_cell(&mut self, command: Command) {
-    current_program!(self).set_grid_value(self.cursor_row as usize, self.cursor_col as usize, command);
+    if !self.palette.allows(command) {
+      return;
+    }
+
+    match self.selection() {
+      Some(rect) => current_program!(self).grid_mut().fill_rect(&rect, command),
+      None => current_program!(self).set_grid_value(self.cursor_row as usize, self.cursor_col as usize, command),
+    }
+  }
+
+  fn cursor(&self) -> (usize, usize) {
+    (self.cursor_row as usize, self.cursor_col as usize)
+  }
+
+  fn selection(&self) -> Option<Rect> {
+    self
+      .selection_anchor
+      .map(|anchor| Rect::from_corners(anchor, self.cursor()))
+  }
+
+  // The cursor goes in the bottom-right corner, so the area can be moved or transformed again right away
+  fn select(&mut self, rect: Rect) {
+    self.selection_anchor = Some((rect.top, rect.left));
+    self.cursor_row = rect.bottom as isize;
+    self.cursor_col = rect.right as isize;
+  }
+
+  // A selection can't cover both programs, so it's dropped when the cursor moves to the other one
+  fn set_program(&mut self, is_program_1: bool) {
+    if is_program_1 != self.is_program_1 {
+      self.selection_anchor = None;
+    }
+    self.is_program_1 = is_program_1;
+  }
+
+  // Without a selection, only the cell under the cursor is copied
+  fn copy_selection(&mut self, global_state: &mut GlobalState) -> io::Result<()> {
+    let rect = self
+      .selection()
+      .unwrap_or(Rect::from_corners(self.cursor(), self.cursor()));
+    self.copy_rect(global_state, &rect)
+  }
+
+  // Copied to the terminal's clipboard too, so it can be pasted outside the game
+  fn copy_rect(&mut self, global_state: &mut GlobalState, rect: &Rect) -> io::Result<()> {
+    let clipboard = Clipboard::copy(current_program!(self).grid(), rect, Command::get_char);
+    clipboard.copy_to_terminal()?;
+    global_state.set_clipboard(clipboard);
+    Ok(())
+  }
+
+  // The pasted cells get selected, so they can be moved right away
+  fn paste(&mut self, global_state: &GlobalState) {
+    let clipboard = global_state.clipboard();
+    if clipboard.is_empty() {
+      return;
+    }
+
+    // Only processor 1 can multiply
+    let is_program_1 = self.is_program_1;
+    // Symbols, or the keys the commands are typed with
+    let keys = global_state.keybindings().parallel_commands();
+    let values = clipboard.to_values(|c| {
+      Command::from_char(c)
+        .or_else(|| keys.typed_with(c))
+        .filter(|command| self.palette.allows(*command) && (is_program_1 || *command != Command::Multiply))
+    });
+
+    let (top, left) = self.selection().map_or(self.cursor(), |rect| (rect.top, rect.left));
+    if let Some(rect) = current_program!(self).grid_mut().paste(top, left, &values) {
+      self.select(rect);
+    }
+  }
+
+  // The selection follows the area, so it can be transformed again right away
+  fn transform(&mut self, transform: Transform) {
+    let selection = self.selection();
+    let target = current_program!(self).transform(selection, transform, &self.palette);
+    if let (Some(_), Some(target)) = (selection, target) {
+      self.select(target);
+    }
+  }
+
+  fn shift(&mut self, row_offset: isize, col_offset: isize) {
+    let selection = self.selection();
+    current_program!(self).shift(selection, row_offset, col_offset);
+  }
+
+  // The selected area moves one cell at a time, and never past the edges
+  fn move_selection(&mut self, row_offset: isize, col_offset: isize) {
+    let Some(rect) = self.selection() else {
+      return;
+    };
+    if let Some(target) = current_program!(self)
+      .grid_mut()
+      .move_rect(&rect, row_offset, col_offset)
+    {
+      self.select(target);
+    }
+  }
+
+  // Screen row of the first row of the program the cursor is in
+  fn program_row(&mut self) -> u16 {
+    GRID_ROW
+      + 1
+      + if self.is_program_1 {
+        self.solution.program_0().rows() as u16 + 1
+      } else {
+        0
+      }
+  }
+
+  fn start_prompt(&mut self, target: PromptTarget) {
+    let (row, col) = self.cursor();
+    let prompt = match target {
+      PromptTarget::Annotation => Prompt::new(target, current_program!(self).grid().annotation(row, col)),
+      PromptTarget::Note => Prompt::new(target, Some(self.solution.note())),
+    };
+    self.in_prompt = Some(prompt);
+  }
+
+  fn execute_prompt(mut self: Box<Self>, global_state: &mut GlobalState) -> io::Result<Option<Box<dyn State>>> {
+    let Some(prompt) = self.in_prompt.as_mut() else {
+      return Ok(Some(self));
+    };
+
+    loop {
+      // `read()` blocks until an `Event` is available
+      let event = match event::read() {
+        Ok(e) => e,
+        Err(_) => return Ok(None),
+      };
+
+      match prompt.handle_event(event) {
+        Some(PromptAction::Redraw) => return Ok(Some(self)),
+        Some(PromptAction::Quit) => return Ok(None),
+        Some(PromptAction::Cancel) => {
+          self.in_prompt = None;
+          return Ok(Some(self));
+        },
+        Some(PromptAction::Save(text)) => {
+          let target = prompt.target();
+          self.in_prompt = None;
+          let before = self.solution.clone();
+          match target {
+            PromptTarget::Annotation => {
+              let (row, col) = self.cursor();
+              current_program!(self).grid_mut().set_annotation(row, col, text);
+            },
+            PromptTarget::Note => self.solution.set_note(text),
+          }
+          self.history.record(before, &self.solution);
+          self.save(global_state);
+          return Ok(Some(self));
+        },
+        None => {},
+      }
+    }
+  }
+
+  fn print_status(&mut self) -> io::Result<()> {
+    let (row, col) = self.cursor();
+    let annotation = current_program!(self).grid().annotation(row, col).map(str::to_string);
+    prompt::print_status(
+      GRID_COL,
+      self.in_prompt.as_ref(),
+      annotation.as_deref(),
+      self.solution.note(),
+    )
+  }
+
+  // Called once the right button is let go, or on anything else happening in between
+  fn finish_erasing(&mut self, global_state: &mut GlobalState) {
+    if let Some(before) = self.erase_start.take() {
+      self.history.record(before, &self.solution);
+      self.save(global_state);
+    }
+  }
+
+  fn undo(&mut self) {
+    if let Some(solution) = self.history.undo(&self.solution) {
+      self.solution = solution;
+    }
+  }
+
+  fn redo(&mut self) {
+    if let Some(solution) = self.history.redo(&self.solution) {
+      self.solution = solution;
+    }
+  }
+
+  // The fixed instructions, then the ones generated from the keybindings
+  fn instructions(&self, keybindings: &KeyBindings) -> Vec<String> {
+    let mut instructions = vec![String::new()];
+    match self.selection_anchor {
+      Some(_) => {
+        instructions.extend(SELECT_INSTRUCTIONS.iter().map(ToString::to_string));
+        instructions.extend(keybindings.instructions(SELECT_ACTION_INSTRUCTIONS, KEYS_WIDTH));
+        let clear = keybindings.keys_text(&[Action::Clear], KEYS_WIDTH - 4);
+        instructions.push(format!("│Sym {clear} = Fill/Clear"));
+      },
+      None => {
+        instructions.extend(INSTRUCTIONS.iter().map(ToString::to_string));
+        instructions.extend(keybindings.instructions(ACTION_INSTRUCTIONS, KEYS_WIDTH));
+      },
+    }
+
+    let commands = self.palette.filter_instructions(COMMAND_INSTRUCTIONS);
+    instructions.extend(keybindings.parallel_commands().instructions(&commands, KEYS_WIDTH));
+    instructions.extend(keybindings.instructions(START_INSTRUCTIONS, KEYS_WIDTH));
+    instructions
+  }
+
+  fn save(&self, global_state: &mut GlobalState) {
+    let level_id = global_state.level(self.level_index).id();
+    <GlobalState as SolutionManager<isa::Parallel>>::save_solution(
+      global_state,
+      level_id,
+      self.solution_index,
+      self.solution.clone(),
+    );
   }
 
   pub(crate) fn level_index(&self) -> LevelIndex {
     self.level_index
   }
 
-  pub(crate) fn vms(&self) -> Vec<VirtualMachine> {
+  pub(crate) fn solution_index(&self) -> usize {
+    self.solution_index
+  }
+
+  pub(crate) fn vms(&self, limits: &LevelLimits) -> Vec<VirtualMachine> {
     (0..self.test_cases.len())
       .map(|i| {
         let index = (self.test_case_index as usize + i).rem_euclid(self.test_cases.len());
-        VirtualMachine::new(self.solution.clone(), index + 1, self.test_cases[index].clone())
+        VirtualMachine::new(self.solution.clone(), index + 1, self.test_cases[index].clone(), limits)
       })
       .collect()
   }
 
   pub(crate) fn toggle_processor_0_breakpoint(&mut self, row: usize, col: usize) {
+    let before = self.solution.clone();
     self.solution.program_0().toggle_breakpoint(row, col);
+    self.history.record(before, &self.solution);
   }
 
   pub(crate) fn toggle_processor_1_breakpoint(&mut self, row: usize, col: usize) {
+    let before = self.solution.clone();
     self.solution.program_1().toggle_breakpoint(row, col);
+    self.history.record(before, &self.solution);
   }
 }
 
@@ -120,7 +438,26 @@ impl State for EditorState {
     let level = global_state.level(self.level_index);
     write!(stdout, "     {} - {}", self.level_index, level.name().yellow())?;
 
+    self.solution.set_highlight_annotations(self.highlight_annotations);
     self.solution.print_at(GRID_ROW, GRID_COL)?;
+    self.print_status()?;
+
+    if let Some(rect) = self.selection() {
+      let program_row = self.program_row();
+      for (row, col) in rect.cells() {
+        stdout.queue(cursor::MoveTo(GRID_COL + 1 + col as u16, program_row + row as u16))?;
+        write!(
+          stdout,
+          "{}",
+          current_program!(self)
+            .grid()
+            .get_value(row, col)
+            .get_char()
+            .black()
+            .on_grey()
+        )?;
+      }
+    }
 
     stdout.queue(cursor::MoveTo(GRID_COL, 2))?.queue(cursor::SavePosition)?;
 
@@ -136,30 +473,28 @@ impl State for EditorState {
 
     self.test_cases[self.test_case_index as usize].print()?;
 
+    let instructions = self.instructions(global_state.keybindings());
+
     stdout
       .queue(cursor::MoveTo(55, 0))?
       .queue(style::SetForegroundColor(Color::DarkCyan))?;
-    print_string(INSTRUCTIONS)?;
+    print_string(&instructions.join("\n"))?;
 
+    let cursor_row = self.program_row() + self.cursor_row as u16;
     stdout
       .queue(style::ResetColor)?
       .queue(cursor::EnableBlinking)?
-      .execute(cursor::MoveTo(
-        GRID_COL + 1 + self.cursor_col as u16,
-        GRID_ROW
-          + 1
-          + self.cursor_row as u16
-          + if self.is_program_1 {
-            self.solution.program_1().cols() as u16 + 1
-          } else {
-            0
-          },
-      ))?;
+      .execute(cursor::MoveTo(GRID_COL + 1 + self.cursor_col as u16, cursor_row))?;
 
     Ok(())
   }
 
   fn execute(mut self: Box<Self>, global_state: &mut GlobalState) -> io::Result<Option<Box<dyn State>>> {
+    if self.in_prompt.is_some() {
+      return self.execute_prompt(global_state);
+    }
+
+    let before = self.solution.clone();
     loop {
       // `read()` blocks until an `Event` is available
       let event = match event::read() {
@@ -170,11 +505,29 @@ impl State for EditorState {
         },
       };
 
+      let erasing = matches!(
+        event,
+        Event::Mouse(MouseEvent {
+          kind: MouseEventKind::Down(MouseButton::Right) | MouseEventKind::Drag(MouseButton::Right),
+          ..
+        })
+      );
+      if !erasing {
+        self.finish_erasing(global_state);
+      }
+
       match event {
         Event::Resize(_, _) => {
           return Ok(Some(self));
         },
 
+        // Text pasted into the terminal replaces the clipboard, and is pasted the same way
+        Event::Paste(text) => {
+          global_state.set_clipboard(Clipboard::from_text(&text));
+          self.paste(global_state);
+          break;
+        },
+
         Event::Mouse(mouse) => {
           // Mouse only causes events inside the grid
           let mouse_col = (mouse.column as isize) - 1 - GRID_COL as isize;
@@ -199,17 +552,27 @@ impl State for EditorState {
           };
 
           match mouse.kind {
-            // Left button just selects the space
-            MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left) => {
+            // Left button just selects the space, or an area when dragged inside one program
+            MouseEventKind::Down(MouseButton::Left) => {
+              self.selection_anchor = None;
               self.is_program_1 = is_program_1;
               self.cursor_row = mouse_row;
               self.cursor_col = mouse_col;
               return Ok(Some(self));
             },
+            MouseEventKind::Drag(MouseButton::Left) if is_program_1 == self.is_program_1 => {
+              self.selection_anchor = self.selection_anchor.or(Some(self.cursor()));
+              self.cursor_row = mouse_row;
+              self.cursor_col = mouse_col;
+              return Ok(Some(self));
+            },
 
-            // Right button clears
+            // Right button clears just the clicked cell, even if an area is selected
+            //   Dragging clears every cell on the way, and saves once the button is let go
             MouseEventKind::Down(MouseButton::Right) | MouseEventKind::Drag(MouseButton::Right) => {
-              self.is_program_1 = is_program_1;
+              self.erase_start.get_or_insert_with(|| self.solution.clone());
+              self.selection_anchor = None;
+              self.set_program(is_program_1);
               self.cursor_row = mouse_row;
               self.cursor_col = mouse_col;
               self.set_cell(Command::Empty);
@@ -225,6 +588,10 @@ impl State for EditorState {
             return Ok(None);
           },
 
+          KeyCode::Esc if self.selection_anchor.is_some() => {
+            self.selection_anchor = None;
+            return Ok(Some(self));
+          },
           KeyCode::Esc => {
             return Ok(Some(Box::new(ShowHelpState::<isa::Parallel>::new(
               self.level_index,
@@ -233,230 +600,194 @@ impl State for EditorState {
             ))))
           },
 
-          // Start execution
-          KeyCode::Tab => return Ok(Some(Box::new(ExecuteState::new(*self, Speed::None)))),
-          KeyCode::Char(' ') => return Ok(Some(Box::new(ExecuteState::new(*self, Speed::Slow)))),
-
-          // Movement
-          KeyCode::Up | KeyCode::Char('k') => {
-            if self.cursor_row == 0 {
-              self.is_program_1 = !self.is_program_1;
-            }
-            self.cursor_row = (self.cursor_row - 1).rem_euclid(current_program!(self).rows() as isize); // TODO
-            return Ok(Some(self));
-          },
-          KeyCode::Down | KeyCode::Char('j') => {
-            if self.cursor_row + 1 == current_program!(self).rows() as isize {
-              self.is_program_1 = !self.is_program_1;
+          _ => {
+            let keybindings = global_state.keybindings();
+            let Some(action) = keybindings.editor_action(&key) else {
+              // Commands
+              match keybindings.parallel_commands().command(&key) {
+                // Only processor 1 can multiply
+                Some(Command::Multiply) if !self.is_program_1 => {},
+                Some(command) => {
+                  self.set_cell(command);
+                  break;
+                },
+                None => {},
+              }
+              continue;
+            };
+
+            match action {
+              // Undo and redo replace the whole solution, so they aren't recorded as edits themselves
+              Action::Undo => {
+                self.undo();
+                self.save(global_state);
+                return Ok(Some(self));
+              },
+              Action::Redo => {
+                self.redo();
+                self.save(global_state);
+                return Ok(Some(self));
+              },
+
+              // Start execution
+              Action::Step => return Ok(Some(Box::new(ExecuteState::new(*self, Speed::None, global_state)))),
+              Action::Run => return Ok(Some(Box::new(ExecuteState::new(*self, Speed::Slow, global_state)))),
+
+              // Selection
+              Action::Select => {
+                self.selection_anchor = match self.selection_anchor {
+                  Some(_) => None,
+                  None => Some(self.cursor()),
+                };
+                return Ok(Some(self));
+              },
+              Action::Copy => {
+                self.copy_selection(global_state)?;
+                return Ok(Some(self));
+              },
+              Action::CopyAll => {
+                let rect = current_program!(self).grid().full_rect();
+                self.copy_rect(global_state, &rect)?;
+                return Ok(Some(self));
+              },
+              Action::Cut => {
+                self.copy_selection(global_state)?;
+                self.set_cell(Command::Empty);
+                break;
+              },
+              Action::Paste => {
+                self.paste(global_state);
+                break;
+              },
+
+              // Move the selected area
+              Action::MoveAreaUp => {
+                self.move_selection(-1, 0);
+                break;
+              },
+              Action::MoveAreaDown => {
+                self.move_selection(1, 0);
+                break;
+              },
+              Action::MoveAreaLeft => {
+                self.move_selection(0, -1);
+                break;
+              },
+              Action::MoveAreaRight => {
+                self.move_selection(0, 1);
+                break;
+              },
+
+              // Rearrange the selected area, or the whole program
+              Action::RotateClockwise => {
+                self.transform(Transform::RotateClockwise);
+                break;
+              },
+              Action::RotateCounterClockwise => {
+                self.transform(Transform::RotateCounterClockwise);
+                break;
+              },
+              Action::MirrorHorizontally => {
+                self.transform(Transform::MirrorHorizontally);
+                break;
+              },
+              Action::MirrorVertically => {
+                self.transform(Transform::MirrorVertically);
+                break;
+              },
+              Action::ShiftUp => {
+                self.shift(-1, 0);
+                break;
+              },
+              Action::ShiftDown => {
+                self.shift(1, 0);
+                break;
+              },
+              Action::ShiftLeft => {
+                self.shift(0, -1);
+                break;
+              },
+              Action::ShiftRight => {
+                self.shift(0, 1);
+                break;
+              },
+
+              // Movement
+              Action::MoveUp => {
+                if self.cursor_row == 0 {
+                  self.set_program(!self.is_program_1);
+                }
+                self.cursor_row = (self.cursor_row - 1).rem_euclid(current_program!(self).rows() as isize); // TODO
+                return Ok(Some(self));
+              },
+              Action::MoveDown => {
+                if self.cursor_row + 1 == current_program!(self).rows() as isize {
+                  self.set_program(!self.is_program_1);
+                }
+                self.cursor_row = (self.cursor_row + 1).rem_euclid(current_program!(self).rows() as isize); // TODO
+                return Ok(Some(self));
+              },
+              Action::MoveLeft => {
+                self.cursor_col = (self.cursor_col - 1).rem_euclid(current_program!(self).cols() as isize);
+                return Ok(Some(self));
+              },
+              Action::MoveRight => {
+                self.cursor_col = (self.cursor_col + 1).rem_euclid(current_program!(self).cols() as isize);
+                return Ok(Some(self));
+              },
+
+              // Select test case
+              Action::NextTestCase => {
+                self.test_case_index = (self.test_case_index + 1).rem_euclid(self.test_cases.len() as isize);
+                return Ok(Some(self));
+              },
+              Action::PreviousTestCase => {
+                self.test_case_index = (self.test_case_index - 1).rem_euclid(self.test_cases.len() as isize);
+                return Ok(Some(self));
+              },
+
+              // Breakpoint
+              Action::Breakpoint | Action::SecondBreakpoint => {
+                current_program!(self).toggle_breakpoint(self.cursor_row as usize, self.cursor_col as usize);
+                break;
+              },
+
+              // Deletion
+              Action::Clear => {
+                self.set_cell(Command::Empty);
+                break;
+              },
+
+              // Starting location
+              Action::SetStart => {
+                current_program!(self).set_start(self.cursor_row as usize, self.cursor_col as usize);
+                break;
+              },
+
+              // Annotations and notes
+              Action::Annotate => {
+                self.start_prompt(PromptTarget::Annotation);
+                return Ok(Some(self));
+              },
+              Action::EditNote => {
+                self.start_prompt(PromptTarget::Note);
+                return Ok(Some(self));
+              },
+              Action::ToggleAnnotations => {
+                self.highlight_annotations = !self.highlight_annotations;
+                return Ok(Some(self));
+              },
+
+              _ => {},
             }
-            self.cursor_row = (self.cursor_row + 1).rem_euclid(current_program!(self).rows() as isize); // TODO
-            return Ok(Some(self));
-          },
-          KeyCode::Left | KeyCode::Char('h') => {
-            self.cursor_col = (self.cursor_col - 1).rem_euclid(current_program!(self).cols() as isize);
-            return Ok(Some(self));
-          },
-          KeyCode::Right | KeyCode::Char('l') => {
-            self.cursor_col = (self.cursor_col + 1).rem_euclid(current_program!(self).cols() as isize);
-            return Ok(Some(self));
-          },
-
-          // Select test case
-          KeyCode::Char(']') => {
-            self.test_case_index = (self.test_case_index + 1).rem_euclid(self.test_cases.len() as isize);
-            return Ok(Some(self));
-          },
-          KeyCode::Char('[') => {
-            self.test_case_index = (self.test_case_index - 1).rem_euclid(self.test_cases.len() as isize);
-            return Ok(Some(self));
-          },
-
-          // Breakpoint
-          KeyCode::Char(',') | KeyCode::Char('.') => {
-            current_program!(self).toggle_breakpoint(self.cursor_row as usize, self.cursor_col as usize);
-            break;
-          },
-
-          // Deletion
-          KeyCode::Backspace | KeyCode::Delete | KeyCode::Char('x') => {
-            self.set_cell(Command::Empty);
-            break;
-          },
-
-          // Arrow commands
-          KeyCode::Char('w') => {
-            self.set_cell(Command::Up);
-            break;
-          },
-          KeyCode::Char('s') => {
-            self.set_cell(Command::Down);
-            break;
-          },
-          KeyCode::Char('a') => {
-            self.set_cell(Command::Left);
-            break;
-          },
-          KeyCode::Char('d') => {
-            self.set_cell(Command::Right);
-            break;
-          },
-          KeyCode::Char('/') => {
-            self.set_cell(Command::ForwardSlash);
-            break;
-          },
-          KeyCode::Char('\\') => {
-            self.set_cell(Command::BackSlash);
-            break;
-          },
-          KeyCode::Char('$') => {
-            self.set_cell(Command::Skip);
-            break;
-          },
-
-          // Numbers
-          KeyCode::Char('0') => {
-            self.set_cell(Command::Zero);
-            break;
           },
-          KeyCode::Char('1') => {
-            self.set_cell(Command::One);
-            break;
-          },
-          KeyCode::Char('2') => {
-            self.set_cell(Command::Two);
-            break;
-          },
-          KeyCode::Char('3') => {
-            self.set_cell(Command::Three);
-            break;
-          },
-          KeyCode::Char('4') => {
-            self.set_cell(Command::Four);
-            break;
-          },
-          KeyCode::Char('5') => {
-            self.set_cell(Command::Five);
-            break;
-          },
-          KeyCode::Char('6') => {
-            self.set_cell(Command::Six);
-            break;
-          },
-          KeyCode::Char('7') => {
-            self.set_cell(Command::Seven);
-            break;
-          },
-          KeyCode::Char('8') => {
-            self.set_cell(Command::Eight);
-            break;
-          },
-          KeyCode::Char('9') => {
-            self.set_cell(Command::Nine);
-            break;
-          },
-
-          // Stack operations
-          KeyCode::Char('p') => {
-            self.set_cell(Command::Pop);
-            break;
-          },
-          KeyCode::Char('c') => {
-            self.set_cell(Command::Copy);
-            break;
-          },
-          KeyCode::Char('~') => {
-            self.set_cell(Command::SwapTop2);
-            break;
-          },
-          KeyCode::Char('v') => {
-            self.set_cell(Command::RotateDown);
-            break;
-          },
-          KeyCode::Char('^') => {
-            self.set_cell(Command::RotateUp);
-            break;
-          },
-
-          // Math
-          KeyCode::Char('+') => {
-            self.set_cell(Command::Add);
-            break;
-          },
-          KeyCode::Char('-') => {
-            self.set_cell(Command::Subtract);
-            break;
-          },
-          KeyCode::Char('*') if self.is_program_1 => {
-            // Only processor 1 can multiply
-            self.set_cell(Command::Multiply);
-            break;
-          },
-
-          // Comparisons
-          KeyCode::Char('<') => {
-            self.set_cell(Command::IfLess);
-            break;
-          },
-          KeyCode::Char('=') => {
-            self.set_cell(Command::IfEqual);
-            break;
-          },
-          KeyCode::Char('>') => {
-            self.set_cell(Command::IfGreater);
-            break;
-          },
-
-          // Input and output
-          KeyCode::Char('i') => {
-            self.set_cell(Command::In);
-            break;
-          },
-          KeyCode::Char('?') => {
-            self.set_cell(Command::HasInput);
-            break;
-          },
-          KeyCode::Char('o') => {
-            self.set_cell(Command::Out);
-            break;
-          },
-
-          // Transmit / Receive
-          KeyCode::Char('t') => {
-            self.set_cell(Command::Transmit);
-            break;
-          },
-          KeyCode::Char('r') => {
-            self.set_cell(Command::Receive);
-            break;
-          },
-          KeyCode::Char('T') => {
-            self.set_cell(Command::TryTransmit);
-            break;
-          },
-          KeyCode::Char('R') => {
-            self.set_cell(Command::TryReceive);
-            break;
-          },
-
-          // Starting location
-          KeyCode::Char('b') => {
-            current_program!(self).set_start(self.cursor_row as usize, self.cursor_col as usize);
-            break;
-          },
-
-          _ => {},
         },
         _ => {},
       }
     }
 
-    let level_id = global_state.level(self.level_index).id();
-    <GlobalState as SolutionManager<isa::Parallel>>::save_solution(
-      global_state,
-      level_id,
-      self.solution_index,
-      self.solution.clone(),
-    );
+    self.history.record(before, &self.solution);
+    self.save(global_state);
 
     Ok(Some(self))
   }
@@ -2,9 +2,8 @@ use crossterm::{
   cursor,
   event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
   style::{self, Color, Stylize},
-  QueueableCommand,
+  terminal, QueueableCommand,
 };
-use rand::Rng;
 use std::{
   cell::RefMut,
   io::{self, Write},
@@ -15,58 +14,53 @@ use std::{
 use super::editor_state::EditorState;
 use super::vm::{Processor, VMError, VirtualMachine};
 use crate::global_state::GlobalState;
+use crate::isa::{self, Execution, ExecutionStep, Machine, MachineError, SolutionManager, Speed, TestCaseFailure};
+use crate::keybindings::Action;
 use crate::level::LevelIndex;
 use crate::printable::Printable;
-use crate::state::{print_string, State, SuccessState};
-use crate::statistics::Statistics;
-
-static INSTRUCTIONS: &str = r#"
-│Esc    = Editor
-│Ctrl-C = Close Program
-│
-│Tab    = Step
-│Space  = Start/Stop
-│1-6    = Set Speed
-│
-│,      = P0 Breakpoint
-│.      = P1 Breakpoint
-│
-│
-│
-│
-│
-│
-│
-│
-│
-│
-│
-│
-│
-│"#;
+use crate::state::{print_string, wrap_string, State, SuccessState};
+use crate::statistics::RunResult;
+
+static INSTRUCTIONS: &[&str] = &["│Esc    = Editor", "│Ctrl-C = Close Program", "│"];
+
+// Generated from the keybindings, after the fixed instructions above
+static ACTION_INSTRUCTIONS: &[(&[Action], &str)] = &[
+  (&[Action::Step], "Step"),
+  (&[Action::StepBack], "Step Back"),
+  (&[Action::Run], "Start/Stop"),
+  (
+    &[
+      Action::Speed1,
+      Action::Speed2,
+      Action::Speed3,
+      Action::Speed4,
+      Action::Speed5,
+      Action::Speed6,
+    ],
+    "Set Speed",
+  ),
+  (&[], ""),
+  (&[Action::Breakpoint], "P0 Breakpoint"),
+  (&[Action::SecondBreakpoint], "P1 Breakpoint"),
+  (&[Action::StepBackToBreakpoint], "Last Breakpoint"),
+  (&[Action::ScrubBack, Action::ScrubForward], "Scrub Timeline"),
+];
+
+// The border is drawn down to the bottom of the screen
+const INSTRUCTIONS_LINES: usize = 25;
+const KEYS_WIDTH: usize = 6;
+
+// Number of steps to move when scrubbing the timeline
+const SCRUB_STEPS: usize = 10;
 
 pub struct ExecuteState {
   editor: EditorState,
   level_index: LevelIndex,
 
-  vms: Vec<VirtualMachine>,
-  test_case: usize,
+  execution: Execution<VirtualMachine>,
   speed: Speed,
 
   last_error: Option<(VMError, usize)>,
-
-  total_cycles: f64,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Speed {
-  None,
-  Slow,
-  Normal,
-  Fast,
-  ExtremelyFast,
-  Turbo,
-  SuperTurbo,
 }
 
 enum StepResult {
@@ -75,69 +69,97 @@ enum StepResult {
 }
 
 impl ExecuteState {
-  pub fn new(editor: EditorState, speed: Speed) -> Self {
+  pub fn new(editor: EditorState, speed: Speed, global_state: &GlobalState) -> Self {
     let level_index = editor.level_index();
-    let vms = editor.vms();
+    let limits = global_state.level(level_index).limits();
+    let execution = Execution::new(editor.vms(&limits));
 
     Self {
       editor,
       level_index,
-      vms,
-      test_case: 0,
+      execution,
       speed,
       last_error: None,
-      total_cycles: 0.0,
     }
   }
 
   fn step_vm(mut self: Box<Self>, global_state: &mut GlobalState) -> StepResult {
-    let current_vm = &mut self.vms[self.test_case];
-    let step = current_vm.step();
-    if current_vm.is_at_breakpoint() {
+    let step = self.execution.step();
+    if self.execution.is_at_breakpoint() {
       self.speed = Speed::None;
     }
 
     match step {
-      Ok(false) => StepResult::Continue(self),
-      Ok(true) => {
-        self.total_cycles += current_vm.get_cycle() as f64;
-        let num_symbols = current_vm.count_symbols();
-        let num_vms = self.vms.len() as f64;
-
-        self.test_case += 1;
-        if self.test_case == self.vms.len() {
-          let level_id = global_state.level(self.level_index).id();
-          let statistics = Statistics::new(self.total_cycles / num_vms, num_symbols);
-
-          let best = global_state.complete_level(level_id, statistics.clone());
-          return StepResult::OtherState(Box::new(SuccessState::new(
-            self.level_index,
-            statistics,
-            best,
-            Box::new(self.editor),
-          )));
-        }
-
-        StepResult::Continue(self)
+      ExecutionStep::Running | ExecutionStep::TestCasePassed => StepResult::Continue(self),
+      ExecutionStep::Solved(mut statistics) => {
+        let level_id = global_state.level(self.level_index).id();
+        statistics.set_test_case_version(global_state.test_case_version(level_id).map(str::to_string));
+        statistics.set_par(&global_state.level(self.level_index).par());
+        self.record_run(global_state, RunResult::Solved(statistics.clone()));
+        let best = global_state.complete_level(level_id, statistics.clone());
+        StepResult::OtherState(Box::new(SuccessState::new(
+          self.level_index,
+          statistics,
+          best,
+          Box::new(self.editor),
+        )))
       },
-      Err(e) => {
-        self.last_error = Some(e);
+      ExecutionStep::Failed(e, processor) => {
+        let current = self.execution.current();
+        let failure = TestCaseFailure {
+          test_case: current.test_case(),
+          cycle: current.get_cycle(),
+          message: e.get_msg().into_owned(),
+        };
+        self.record_run(global_state, RunResult::Failed(failure));
+        self.last_error = Some((e, processor));
         StepResult::OtherState(Box::new(*self))
       },
     }
   }
 
+  fn record_run(&self, global_state: &mut GlobalState, result: RunResult) {
+    let level_id = global_state.level(self.level_index).id();
+    <GlobalState as SolutionManager<isa::Parallel>>::record_run(
+      global_state,
+      level_id,
+      self.editor.solution_index(),
+      result,
+    );
+  }
+
+  // Move backwards through the execution history, which also clears any error
+  fn rewind(&mut self, action: Action) {
+    match action {
+      Action::StepBack => {
+        self.execution.step_back();
+      },
+      Action::StepBackToBreakpoint => self.execution.step_back_to_breakpoint(),
+      Action::ScrubBack => {
+        for _ in 0..SCRUB_STEPS {
+          if !self.execution.step_back() {
+            break;
+          }
+        }
+      },
+      _ => return,
+    }
+
+    self.speed = Speed::None;
+    self.last_error = None;
+  }
+
   fn toggle_breakpoint(
     &mut self,
     func: impl Fn(&mut VirtualMachine) -> RefMut<Processor>,
     toggle_editor: impl Fn(&mut EditorState, usize, usize),
   ) {
-    let current_vm = func(&mut self.vms[self.test_case]);
+    let current_vm = func(self.execution.current_mut());
     let row = current_vm.row();
     let col = current_vm.col();
     drop(current_vm);
 
-    for mut vm in self.vms.iter_mut().map(func) {
+    for mut vm in self.execution.machines_mut().map(func) {
       vm.toggle_breakpoint(row, col);
     }
 
@@ -153,27 +175,44 @@ impl State for ExecuteState {
     stdout.queue(cursor::Hide)?;
     write!(stdout, "     {} - {}", self.level_index, level.name().yellow())?;
 
-    self.vms[self.test_case].print_at(2, 0)?;
+    self.execution.current().print_at(2, 0)?;
+
+    // Show how far back the timeline goes
+    stdout.queue(cursor::MoveTo(0, 1))?;
+    write!(stdout, "{} {}", "Rewind:".dark_cyan(), self.execution.history_len())?;
 
     if let Some((ref last_error, error_index)) = self.last_error {
+      let current_vm = self.execution.current_mut();
       match error_index {
-        0 => self.vms[self.test_case].processor_0().print_error_symbol_at(3, 0),
+        0 => current_vm.processor_0().print_error_symbol_at(3, 0),
         1 => {
-          let rows = self.vms[self.test_case].processor_0().rows() as u16;
-          self.vms[self.test_case]
-            .processor_1()
-            .print_error_symbol_at(3 + rows + 1, 0)
+          let rows = current_vm.processor_0().rows() as u16;
+          current_vm.processor_1().print_error_symbol_at(3 + rows + 1, 0)
         },
         _ => Ok(()),
       }?;
 
-      last_error.print_at(self.vms[0].height() + 4, 0)?;
+      // Level checkers can give long messages, so keep them to the left of the IO boxes
+      let cols = current_vm.processor_0().cols();
+      let row = current_vm.height() + 4;
+      let max_lines = terminal::size()?.1.saturating_sub(row).max(1) as usize;
+      for (line, i) in wrap_string(&last_error.get_msg(), cols + 10, max_lines)
+        .lines()
+        .zip(0..)
+      {
+        stdout.queue(cursor::MoveTo(0, row + i))?;
+        write!(stdout, "{}", line.red())?;
+      }
     }
 
     stdout
       .queue(cursor::MoveTo(55, 0))?
       .queue(style::SetForegroundColor(Color::DarkCyan))?;
-    print_string(INSTRUCTIONS)?;
+    let mut instructions = vec![String::new()];
+    instructions.extend(INSTRUCTIONS.iter().map(ToString::to_string));
+    instructions.extend(global_state.keybindings().instructions(ACTION_INSTRUCTIONS, KEYS_WIDTH));
+    instructions.resize(INSTRUCTIONS_LINES.max(instructions.len()), "│".to_string());
+    print_string(&instructions.join("\n"))?;
     stdout.queue(style::ResetColor)?;
 
     stdout.flush()?;
@@ -202,7 +241,15 @@ impl State for ExecuteState {
 
             KeyCode::Esc => return Ok(Some(Box::new(self.editor))),
 
-            _ => {},
+            // Step back to before the error
+            _ => {
+              if let Some(action @ (Action::StepBack | Action::StepBackToBreakpoint | Action::ScrubBack)) =
+                global_state.keybindings().execute_action(&key)
+              {
+                self.rewind(action);
+                return Ok(Some(self));
+              }
+            },
           },
 
           _ => {},
@@ -242,57 +289,81 @@ impl State for ExecuteState {
             return Ok(None);
           },
 
-          // Single step
-          KeyCode::Tab => {
-            self.speed = Speed::None;
-            return Ok(self.step_vm(global_state).into_box());
+          // Go back
+          KeyCode::Esc => {
+            return Ok(Some(Box::new(self.editor)));
           },
 
-          // Start/Stop
-          KeyCode::Char(' ') => {
-            if self.speed != Speed::None {
+          _ => match global_state.keybindings().execute_action(&key) {
+            // Single step
+            Some(Action::Step) => {
               self.speed = Speed::None;
-            } else {
-              self.speed = Speed::Slow;
-            }
-          },
+              return Ok(self.step_vm(global_state).into_box());
+            },
 
-          // Set Speed
-          KeyCode::Char('1') => {
-            self.speed = Speed::Slow;
-          },
-          KeyCode::Char('2') => {
-            self.speed = Speed::Normal;
-          },
-          KeyCode::Char('3') => {
-            self.speed = Speed::Fast;
-          },
-          KeyCode::Char('4') => {
-            self.speed = Speed::ExtremelyFast;
-          },
-          KeyCode::Char('5') => {
-            self.speed = Speed::Turbo;
-          },
-          KeyCode::Char('6') => {
-            self.speed = Speed::SuperTurbo;
-          },
+            // Step backwards
+            Some(action @ (Action::StepBack | Action::StepBackToBreakpoint | Action::ScrubBack)) => {
+              self.rewind(action);
+              return Ok(Some(self));
+            },
 
-          // Breakpoint
-          KeyCode::Char(',') if self.speed == Speed::None => {
-            self.toggle_breakpoint(VirtualMachine::processor_0, EditorState::toggle_processor_0_breakpoint);
-            return Ok(Some(self));
-          },
-          KeyCode::Char('.') if self.speed == Speed::None => {
-            self.toggle_breakpoint(VirtualMachine::processor_1, EditorState::toggle_processor_1_breakpoint);
-            return Ok(Some(self));
-          },
+            // Scrub forwards, stopping early at a breakpoint
+            Some(Action::ScrubForward) => {
+              self.speed = Speed::None;
+              for _ in 0..SCRUB_STEPS {
+                self = match self.step_vm(global_state) {
+                  StepResult::Continue(s) => s,
+                  result @ StepResult::OtherState(_) => return Ok(result.into_box()),
+                };
+
+                if self.execution.is_at_breakpoint() {
+                  break;
+                }
+              }
+              return Ok(Some(self));
+            },
 
-          // Go back
-          KeyCode::Esc => {
-            return Ok(Some(Box::new(self.editor)));
-          },
+            // Start/Stop
+            Some(Action::Run) => {
+              if self.speed != Speed::None {
+                self.speed = Speed::None;
+              } else {
+                self.speed = Speed::Slow;
+              }
+            },
 
-          _ => {},
+            // Set Speed
+            Some(Action::Speed1) => {
+              self.speed = Speed::Slow;
+            },
+            Some(Action::Speed2) => {
+              self.speed = Speed::Normal;
+            },
+            Some(Action::Speed3) => {
+              self.speed = Speed::Fast;
+            },
+            Some(Action::Speed4) => {
+              self.speed = Speed::ExtremelyFast;
+            },
+            Some(Action::Speed5) => {
+              self.speed = Speed::Turbo;
+            },
+            Some(Action::Speed6) => {
+              self.speed = Speed::SuperTurbo;
+            },
+
+            // Breakpoint
+            Some(Action::Breakpoint) if self.speed == Speed::None => {
+              self.toggle_breakpoint(VirtualMachine::processor_0, EditorState::toggle_processor_0_breakpoint);
+              return Ok(Some(self));
+            },
+            Some(Action::SecondBreakpoint) if self.speed == Speed::None => {
+              self.toggle_breakpoint(VirtualMachine::processor_1, EditorState::toggle_processor_1_breakpoint);
+              return Ok(Some(self));
+            },
+
+            _ => {},
+          },
         },
 
         _ => {},
@@ -310,21 +381,6 @@ impl State for ExecuteState {
   }
 }
 
-impl Speed {
-  pub fn num_steps(self) -> usize {
-    let mut rng = rand::thread_rng();
-    match self {
-      Self::None => 0,
-      Self::Slow => 1,
-      Self::Normal => rng.gen_range(4..=8),
-      Self::Fast => rng.gen_range(20..=30),
-      Self::ExtremelyFast => rng.gen_range(90..=110),
-      Self::Turbo => rng.gen_range(900..=1100),
-      Self::SuperTurbo => rng.gen_range(9000..=11000),
-    }
-  }
-}
-
 impl StepResult {
   pub fn into_box(self) -> Option<Box<dyn State>> {
     match self {
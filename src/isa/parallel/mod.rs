@@ -1,11 +1,10 @@
-use crate::isa::InstructionSetArchitecture;
-use crate::level::LevelIndex;
+use crate::isa::{CommandPalette, InstructionSetArchitecture, OutputChecker};
+use crate::level::{LevelIndex, LevelLimits, LevelType, StaticTestCase, DEFAULT_MAX_CYCLES};
 use crate::state::State;
 use editor_state::EditorState;
 use puzzle::ProcessorIO;
-use rlua::prelude::*;
-use std::error::Error;
-use std::fs;
+use std::rc::Rc;
+use vm::VirtualMachine;
 
 mod editor_state;
 mod execute_state;
@@ -14,6 +13,7 @@ mod solution;
 mod vm;
 
 pub use solution::Solution;
+pub use vm::Command;
 
 /// Parallel level type
 pub struct Parallel;
@@ -21,55 +21,63 @@ pub struct Parallel;
 impl InstructionSetArchitecture for Parallel {
   type Solution = Solution;
   type Puzzle = puzzle::Puzzle;
+  // Inputs and expected outputs for each processor
+  type TestCase = (Vec<i16>, Vec<i16>, Vec<i16>, Vec<i16>);
+  type Machine = VirtualMachine;
+  type Command = vm::Command;
 
-  ///
-  /// Load and run the Lua code to generate the puzzles
-  ///
-  fn generate_test_cases(
-    folder: &str,
-    lua_file: &str,
-    seed: u32,
-    n: usize,
-  ) -> Result<Vec<Self::Puzzle>, Box<dyn Error>> {
-    // Try to load the Lua code file into memory
-    let lua_code = fs::read_to_string(format!("{folder}/{lua_file}"))?;
+  const LEVEL_TYPE: LevelType = LevelType::Parallel;
 
-    // Generate and run the code within the Lua context
-    let test_cases = Lua::new().context::<_, LuaResult<Vec<Self::Puzzle>>>(|ctx| {
-      let globals = ctx.globals();
+  const DEFAULT_LIMITS: LevelLimits = LevelLimits {
+    grid_rows: 8,
+    grid_cols: 8,
+    stack_size: 8,
+    max_inputs: 8,
+    max_outputs: 8,
+    max_cycles: DEFAULT_MAX_CYCLES,
+  };
 
-      // Add the levels folder to the path
-      ctx
-        .load(&format!(r#"package.path = "./{folder}/?.lua;" .. package.path"#))
-        .exec()?;
+  const MAX_LIMITS: LevelLimits = LevelLimits {
+    grid_rows: 8,
+    grid_cols: 13,
+    stack_size: 9,
+    max_inputs: 9,
+    max_outputs: 9,
+    max_cycles: u32::MAX,
+  };
 
-      // Seed the random number generator
-      globals
-        .get::<_, LuaTable>("math")?
-        .get::<_, LuaFunction>("randomseed")?
-        .call::<_, ()>(seed)?;
-
-      // Load the script code
-      //  This should define a global function named "generateTestCase"
-      ctx.load(&lua_code).exec()?;
-
-      // Generate the test cases one-by-one
-      let generate_test_case: LuaFunction = globals.get("generateTestCase")?;
-      let test_cases = (0..n)
-        .map(|_| {
-          let (p0_inputs, p0_outputs, p1_inputs, p1_outputs): (Vec<i16>, Vec<i16>, Vec<i16>, Vec<i16>) =
-            generate_test_case.call(())?;
-
-          let p0 = ProcessorIO::new(p0_inputs, p0_outputs).map_err(LuaError::RuntimeError)?;
-          let p1 = ProcessorIO::new(p1_inputs, p1_outputs).map_err(LuaError::RuntimeError)?;
-          Ok(Self::Puzzle::new(p0, p1))
-        })
-        .collect::<Result<_, _>>()?;
+  fn new_puzzle(
+    (p0_inputs, p0_outputs, p1_inputs, p1_outputs): Self::TestCase,
+    limits: &LevelLimits,
+  ) -> Result<Self::Puzzle, String> {
+    let p0 = ProcessorIO::new(p0_inputs, p0_outputs, limits)?;
+    let p1 = ProcessorIO::new(p1_inputs, p1_outputs, limits)?;
+    Ok(Self::Puzzle::new(p0, p1))
+  }
 
-      Ok(test_cases)
-    })?;
+  fn static_test_case(test_case: &StaticTestCase) -> Result<Self::TestCase, String> {
+    match test_case {
+      StaticTestCase {
+        inputs: None,
+        outputs: None,
+        p0_inputs: Some(p0_inputs),
+        p0_outputs: Some(p0_outputs),
+        p1_inputs: Some(p1_inputs),
+        p1_outputs: Some(p1_outputs),
+      } => Ok((
+        p0_inputs.clone(),
+        p0_outputs.clone(),
+        p1_inputs.clone(),
+        p1_outputs.clone(),
+      )),
+      _ => Err(
+        "Parallel levels need \"p0Inputs\", \"p0Outputs\", \"p1Inputs\", and \"p1Outputs\" for each test case".into(),
+      ),
+    }
+  }
 
-    Ok(test_cases)
+  fn set_checker(puzzle: &mut Self::Puzzle, checker: Rc<OutputChecker>) {
+    puzzle.set_checker(checker);
   }
 
   fn open_editor(
@@ -78,7 +86,28 @@ impl InstructionSetArchitecture for Parallel {
     solution: Self::Solution,
     test_cases: Vec<Self::Puzzle>,
     test_case_index: usize,
+    palette: CommandPalette<Self::Command>,
   ) -> impl State {
-    EditorState::new(level_index, solution_index, solution, test_cases, test_case_index)
+    EditorState::new(
+      level_index,
+      solution_index,
+      solution,
+      test_cases,
+      test_case_index,
+      palette,
+    )
+  }
+
+  fn commands_used(solution: &Self::Solution) -> Vec<Self::Command> {
+    solution.commands().collect()
+  }
+
+  fn new_machine(
+    solution: &Self::Solution,
+    test_case: usize,
+    puzzle: &Self::Puzzle,
+    limits: &LevelLimits,
+  ) -> Self::Machine {
+    VirtualMachine::new(solution.clone(), test_case, puzzle.clone(), limits)
   }
 }
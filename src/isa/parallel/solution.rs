@@ -2,29 +2,33 @@ use crossterm::style::Stylize;
 use crossterm::{cursor, QueueableCommand};
 use serde::{Deserialize, Serialize};
 use std::io::{self, Write};
+use uuid::Uuid;
 
 use super::vm::Command;
-use crate::grid::Grid;
-use crate::isa;
+use crate::grid::{Direction, Grid, Rect, Start, Transform};
+use crate::isa::{self, CommandPalette};
+use crate::level::LevelLimits;
 use crate::printable::Printable;
+use crate::solution_file::ProgramText;
 
-// Parallel levels use an 8x8 grid
-const GRID_SIZE: usize = 8;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Solution {
   #[serde(default = "default_name")]
   name: String,
+  #[serde(default = "Uuid::new_v4")]
+  id: Uuid,
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  note: String,
   programs: [Program; 2],
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Program {
   grid: Grid<Command>,
-  start_row: usize,
-  start_col: usize,
+  #[serde(flatten)]
+  start: Start,
 }
 
 fn default_name() -> String {
@@ -32,10 +36,12 @@ fn default_name() -> String {
 }
 
 impl isa::Solution for Solution {
-  fn new(name: impl Into<String>) -> Self {
+  fn new(name: impl Into<String>, limits: &LevelLimits) -> Self {
     Self {
       name: name.into(),
-      ..Default::default()
+      id: Uuid::new_v4(),
+      note: String::new(),
+      programs: [Program::new(limits), Program::new(limits)],
     }
   }
 
@@ -47,9 +53,64 @@ impl isa::Solution for Solution {
     self.name = new_name.into();
   }
 
+  fn id(&self) -> Uuid {
+    self.id
+  }
+
+  fn set_id(&mut self, id: Uuid) {
+    self.id = id;
+  }
+
+  fn note(&self) -> &str {
+    &self.note
+  }
+
+  fn set_note(&mut self, note: impl Into<String>) {
+    self.note = note.into();
+  }
+
   fn symbols_used(&self) -> usize {
     self.programs.iter().map(Program::symbols_used).sum()
   }
+
+  fn to_programs(&self) -> Vec<ProgramText> {
+    self
+      .programs
+      .iter()
+      .map(|program| ProgramText::from_grid(&program.grid, program.start, Command::get_char))
+      .collect()
+  }
+
+  fn from_programs(name: impl Into<String>, programs: &[ProgramText], limits: &LevelLimits) -> Result<Self, String> {
+    let [p0, p1] = programs else {
+      return Err("Parallel solutions must have exactly two programs".into());
+    };
+
+    let to_program = |program: &ProgramText| {
+      let (grid, start) = program.to_grid(limits.grid_rows, limits.grid_cols, Command::from_char)?;
+      Ok::<_, String>(Program { grid, start })
+    };
+
+    let solution = Self {
+      name: name.into(),
+      id: Uuid::new_v4(),
+      note: String::new(),
+      programs: [to_program(p0)?, to_program(p1)?],
+    };
+    solution.check_rules()?;
+    Ok(solution)
+  }
+
+  fn check_rules(&self) -> Result<(), String> {
+    if self.programs[0]
+      .grid
+      .values()
+      .any(|command| *command == Command::Multiply)
+    {
+      return Err("Only the second processor can multiply".into());
+    }
+    Ok(())
+  }
 }
 
 #[allow(unused)]
@@ -62,6 +123,16 @@ impl Solution {
     &mut self.programs[1]
   }
 
+  pub fn commands(&self) -> impl Iterator<Item = Command> + '_ {
+    self.programs.iter().flat_map(|program| program.grid.values().copied())
+  }
+
+  pub fn set_highlight_annotations(&mut self, highlight: bool) {
+    for program in &mut self.programs {
+      program.grid.set_highlight_annotations(highlight);
+    }
+  }
+
   pub fn into_programs(self) -> (Program, Program) {
     let [p0, p1] = self.programs;
     (p0, p1)
@@ -81,20 +152,26 @@ impl Printable for Solution {
   }
 }
 
-impl Default for Solution {
-  fn default() -> Self {
+impl Program {
+  fn new(limits: &LevelLimits) -> Self {
     Self {
-      name: "New Solution".into(),
-      programs: [Program::default(), Program::default()],
+      grid: Grid::new(limits.grid_rows, limits.grid_cols),
+      start: Start::default(),
     }
   }
-}
 
-impl Program {
   pub fn into_grid(self) -> Grid<Command> {
     self.grid
   }
 
+  pub fn grid(&self) -> &Grid<Command> {
+    &self.grid
+  }
+
+  pub fn grid_mut(&mut self) -> &mut Grid<Command> {
+    &mut self.grid
+  }
+
   pub fn rows(&self) -> usize {
     self.grid.rows()
   }
@@ -108,19 +185,24 @@ impl Program {
   }
 
   pub fn start_row(&self) -> usize {
-    self.start_row
+    self.start.row
   }
 
   pub fn start_col(&self) -> usize {
-    self.start_col
+    self.start.col
   }
 
+  pub fn start_direction(&self) -> Direction {
+    self.start.direction
+  }
+
+  /// Move the start to another cell, it keeps going the same way
   pub fn set_start(&mut self, start_row: usize, start_col: usize) {
-    debug_assert!(self.start_row < self.grid.rows());
-    debug_assert!(self.start_col < self.grid.cols());
+    debug_assert!(start_row < self.grid.rows());
+    debug_assert!(start_col < self.grid.cols());
 
-    self.start_row = start_row;
-    self.start_col = start_col;
+    self.start.row = start_row;
+    self.start.col = start_col;
   }
 
   pub fn symbols_used(&self) -> usize {
@@ -130,15 +212,25 @@ impl Program {
   pub fn toggle_breakpoint(&mut self, row: usize, col: usize) {
     self.grid.toggle_breakpoint(row, col);
   }
-}
 
-impl Default for Program {
-  fn default() -> Self {
-    Self {
-      grid: Grid::new(GRID_SIZE, GRID_SIZE),
-      start_row: 0,
-      start_col: 0,
-    }
+  /// Rotate or mirror the commands in the area, or the whole program if there isn't one, see Grid::transform_program
+  ///   Arrows and mirrors turn into other commands, so it is refused unless the palette allows those too
+  pub fn transform(
+    &mut self,
+    area: Option<Rect>,
+    transform: Transform,
+    palette: &CommandPalette<Command>,
+  ) -> Option<Rect> {
+    self
+      .grid
+      .transform_program(&mut self.start, area, transform, |command| {
+        Some(command.transformed(transform)).filter(|command| palette.allows(*command))
+      })
+  }
+
+  /// Shift the commands in the area, or the whole grid if there isn't one, see Grid::shift_program
+  pub fn shift(&mut self, area: Option<Rect>, row_offset: isize, col_offset: isize) {
+    self.grid.shift_program(&mut self.start, area, row_offset, col_offset);
   }
 }
 
@@ -150,19 +242,16 @@ impl Printable for Program {
 
     stdout
       .queue(cursor::RestorePosition)?
-      .queue(cursor::MoveRight(self.start_col as u16 + 1))?
-      .queue(cursor::MoveDown(self.start_row as u16 + 1))?;
-
-    write!(
-      stdout,
-      "{}",
-      self
-        .grid
-        .get_value(self.start_row, self.start_col)
-        .get_char()
-        .green()
-        .reverse()
-    )?;
+      .queue(cursor::MoveRight(self.start.col as u16 + 1))?
+      .queue(cursor::MoveDown(self.start.row as u16 + 1))?;
+
+    // An empty start shows which way it goes, unless it's the usual way
+    let command = self.grid.get_value(self.start.row, self.start.col);
+    let symbol = match command {
+      Command::Empty if !self.start.direction.is_right() => self.start.direction.get_arrow(),
+      _ => command.get_char(),
+    };
+    write!(stdout, "{}", symbol.green().reverse())?;
 
     stdout.queue(cursor::RestorePosition)?;
 
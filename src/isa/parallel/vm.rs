@@ -1,29 +1,44 @@
 use crossterm::style::{self, Color, Stylize};
 use crossterm::{cursor, QueueableCommand};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::cell::{RefCell, RefMut};
 use std::collections::VecDeque;
+use std::fmt;
 use std::io::{self, Write};
 use std::rc::{Rc, Weak};
 
 // use crate::global_state::Solution;
 use super::puzzle::{ProcessorIO, Puzzle, PuzzleIO};
 use super::solution::{Program, Solution};
-use crate::grid::Grid;
+use crate::grid::{Direction, Grid, Transform};
+use crate::isa::{CheckResult, LoopDetector, Machine, MachineError, OutputChecker};
+use crate::level::LevelLimits;
 use crate::printable::Printable;
 
 pub const VAL_MIN: i16 = -999;
 pub const VAL_MAX: i16 = 999;
 pub const VAL_CHAR_WIDTH: usize = 4; // 3 numbers and negative sign
-const MAX_STACK_ENTRIES: usize = 8;
 
-#[derive(Debug, Clone)]
+// Cloning the processors would share them between machines, so use snapshot() instead
+#[derive(Debug)]
 pub struct VirtualMachine {
   processors: [Rc<RefCell<Processor>>; 2],
   cycle: u32,
   test_case: usize,
+
+  // Judges the outputs of both processors instead of the expected outputs if the level has one
+  checker: Option<Rc<OutputChecker>>,
+  test_case_inputs: [PuzzleIO; 2],
+  checked_outputs: Option<(usize, usize)>,
+
+  max_cycles: u32,
+  loop_detector: LoopDetector<[(usize, usize); 2], [ProcessorState; 2]>,
 }
 
+// Everything that decides what a processor does next, other than its grid
+type ProcessorState = (i16, i16, Direction, bool, bool, Stack);
+
 #[derive(Debug, Clone)]
 pub struct Processor {
   grid: Grid<Command>,
@@ -41,29 +56,16 @@ pub struct Processor {
 
   other_processor: Option<Weak<RefCell<Processor>>>,
   sending_status: SendStatus,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Direction {
-  Up,
-  Down,
-  Left,
-  Right,
-}
 
-impl Direction {
-  pub fn get_arrow(&self) -> char {
-    match self {
-      Self::Up => '▲',
-      Self::Down => '▼',
-      Self::Left => '◄',
-      Self::Right => '►',
-    }
-  }
+  // Scoring details, which aren't part of the state for loop detection
+  instructions_executed: u32,
+  peak_stack_depth: usize,
+  blocked_cycles: u32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Command {
+  #[default]
   #[serde(rename = " ", alias = "empty")]
   Empty,
   #[serde(rename = "↑", alias = "up")]
@@ -179,11 +181,116 @@ impl Command {
       Self::TryReceive => 'Ř',
     }
   }
-}
 
-impl Default for Command {
-  fn default() -> Self {
-    Self::Empty
+  pub fn from_char(c: char) -> Option<Self> {
+    match c {
+      ' ' => Some(Self::Empty),
+      '↑' => Some(Self::Up),
+      '↓' => Some(Self::Down),
+      '←' => Some(Self::Left),
+      '→' => Some(Self::Right),
+      '/' => Some(Self::ForwardSlash),
+      '\\' => Some(Self::BackSlash),
+      '0' => Some(Self::Zero),
+      '1' => Some(Self::One),
+      '2' => Some(Self::Two),
+      '3' => Some(Self::Three),
+      '4' => Some(Self::Four),
+      '5' => Some(Self::Five),
+      '6' => Some(Self::Six),
+      '7' => Some(Self::Seven),
+      '8' => Some(Self::Eight),
+      '9' => Some(Self::Nine),
+      '☼' => Some(Self::Pop),
+      '©' => Some(Self::Copy),
+      '∫' => Some(Self::SwapTop2),
+      'u' => Some(Self::RotateDown),
+      '∩' => Some(Self::RotateUp),
+      '+' => Some(Self::Add),
+      '-' => Some(Self::Subtract),
+      'х' => Some(Self::Multiply),
+      '<' => Some(Self::IfLess),
+      '=' => Some(Self::IfEqual),
+      '>' => Some(Self::IfGreater),
+      '»' => Some(Self::Skip),
+      'Ї' => Some(Self::In),
+      '?' => Some(Self::HasInput),
+      'Θ' => Some(Self::Out),
+      'τ' => Some(Self::Transmit),
+      'я' => Some(Self::Receive),
+      'Ť' => Some(Self::TryTransmit),
+      'Ř' => Some(Self::TryReceive),
+      _ => None,
+    }
+  }
+
+  /// The command typed with this key by default, the keybindings file can change it
+  pub fn from_key(c: char) -> Option<Self> {
+    match c {
+      'w' => Some(Self::Up),
+      's' => Some(Self::Down),
+      'a' => Some(Self::Left),
+      'd' => Some(Self::Right),
+      '/' => Some(Self::ForwardSlash),
+      '\\' => Some(Self::BackSlash),
+      '$' => Some(Self::Skip),
+      '0' => Some(Self::Zero),
+      '1' => Some(Self::One),
+      '2' => Some(Self::Two),
+      '3' => Some(Self::Three),
+      '4' => Some(Self::Four),
+      '5' => Some(Self::Five),
+      '6' => Some(Self::Six),
+      '7' => Some(Self::Seven),
+      '8' => Some(Self::Eight),
+      '9' => Some(Self::Nine),
+      'p' => Some(Self::Pop),
+      'c' => Some(Self::Copy),
+      '~' => Some(Self::SwapTop2),
+      'v' => Some(Self::RotateDown),
+      '^' => Some(Self::RotateUp),
+      '+' => Some(Self::Add),
+      '-' => Some(Self::Subtract),
+      '*' => Some(Self::Multiply),
+      '<' => Some(Self::IfLess),
+      '=' => Some(Self::IfEqual),
+      '>' => Some(Self::IfGreater),
+      'i' => Some(Self::In),
+      '?' => Some(Self::HasInput),
+      'o' => Some(Self::Out),
+      't' => Some(Self::Transmit),
+      'r' => Some(Self::Receive),
+      'T' => Some(Self::TryTransmit),
+      'R' => Some(Self::TryReceive),
+      _ => None,
+    }
+  }
+
+  /// The command that does the same thing once the grid around it is rotated or mirrored
+  pub fn transformed(self, transform: Transform) -> Self {
+    match (transform, self) {
+      // Every transform flips which way the mirrors face
+      (_, Self::ForwardSlash) => Self::BackSlash,
+      (_, Self::BackSlash) => Self::ForwardSlash,
+
+      (Transform::RotateClockwise, Self::Up) => Self::Right,
+      (Transform::RotateClockwise, Self::Right) => Self::Down,
+      (Transform::RotateClockwise, Self::Down) => Self::Left,
+      (Transform::RotateClockwise, Self::Left) => Self::Up,
+
+      (Transform::RotateCounterClockwise, Self::Up) => Self::Left,
+      (Transform::RotateCounterClockwise, Self::Left) => Self::Down,
+      (Transform::RotateCounterClockwise, Self::Down) => Self::Right,
+      (Transform::RotateCounterClockwise, Self::Right) => Self::Up,
+
+      (Transform::MirrorHorizontally, Self::Left) => Self::Right,
+      (Transform::MirrorHorizontally, Self::Right) => Self::Left,
+
+      (Transform::MirrorVertically, Self::Up) => Self::Down,
+      (Transform::MirrorVertically, Self::Down) => Self::Up,
+
+      (_, command) => command,
+    }
   }
 }
 
@@ -210,62 +317,122 @@ pub enum VMError {
   NoInputs,
   TooManyOutputs,
   Deadlock,
+  InfiniteLoop,
+  CycleLimitExceeded,
+  WrongOutputs(String),
 }
 
 #[allow(unused)]
 impl VirtualMachine {
-  pub fn new(solution: Solution, test_case: usize, io: Puzzle) -> Self {
+  pub fn new(solution: Solution, test_case: usize, io: Puzzle, limits: &LevelLimits) -> Self {
     let (p0, p1) = solution.into_programs();
+    let checker = io.get_checker().cloned();
     let (p0_io, p1_io) = io.into_processor_ios();
-
-    let mut processor_0 = Rc::new(RefCell::new(Processor::new(p0, p0_io)));
-    let mut processor_1 = Rc::new(RefCell::new(Processor::new(p1, p1_io)));
-
-    (*processor_0).borrow_mut().other_processor = Some(Rc::downgrade(&processor_1));
-    (*processor_1).borrow_mut().other_processor = Some(Rc::downgrade(&processor_0));
+    let test_case_inputs = [p0_io.get_inputs().clone(), p1_io.get_inputs().clone()];
 
     Self {
-      processors: [processor_0, processor_1],
+      processors: link_processors(Processor::new(p0, p0_io, limits), Processor::new(p1, p1_io, limits)),
       cycle: 0,
       test_case,
+      checker,
+      test_case_inputs,
+      checked_outputs: None,
+      max_cycles: limits.max_cycles,
+      loop_detector: LoopDetector::new(),
     }
   }
 
-  pub fn get_cycle(&self) -> u32 {
-    self.cycle
+  pub fn processor_0(&mut self) -> RefMut<'_, Processor> {
+    (*self.processors[0]).borrow_mut()
   }
 
-  pub fn count_symbols(&self) -> usize {
-    self
-      .processors
-      .iter()
-      .map(|processor| (**processor).borrow().count_symbols())
-      .sum()
+  pub fn processor_1(&mut self) -> RefMut<'_, Processor> {
+    (*self.processors[1]).borrow_mut()
   }
 
-  pub fn is_at_breakpoint(&self) -> bool {
-    self
-      .processors
-      .iter()
-      .any(|processor| (**processor).borrow().is_at_breakpoint())
+  fn print_processor_program(&self, processor: &Processor, line: u16) -> io::Result<()> {
+    let mut stdout = io::stdout();
+
+    if line > 0 {
+      stdout.queue(cursor::MoveDown(line))?;
+    }
+
+    processor.grid.print()?;
+
+    stdout
+      .queue(cursor::RestorePosition)?
+      .queue(cursor::MoveDown(processor.row as u16 + 2 + line))?
+      .queue(cursor::MoveRight(processor.col as u16 + 1))?;
+
+    if processor.skip_next_instruction {
+      write!(stdout, "{}", processor.direction.get_arrow().red())?;
+    } else if self.is_at_breakpoint() && processor.is_at_breakpoint() {
+      stdout.queue(style::SetBackgroundColor(Color::DarkCyan))?;
+      write!(stdout, "{}", processor.direction.get_arrow().black())?;
+      stdout.queue(style::ResetColor)?;
+    } else {
+      write!(stdout, "{}", processor.direction.get_arrow().green())?;
+    }
+
+    Ok(())
   }
 
-  pub fn processor_0(&mut self) -> RefMut<Processor> {
-    (*self.processors[0]).borrow_mut()
+  // Returns Ok(true) once both processors have read every input and the outputs are correct
+  fn is_solved(&mut self) -> Result<bool, VMError> {
+    let [p0, p1] = &self.processors;
+    let (p0, p1) = ((**p0).borrow(), (**p1).borrow());
+    if p0.inputs.len() > 0 || p1.inputs.len() > 0 {
+      return Ok(false);
+    }
+
+    let Some(ref checker) = self.checker else {
+      return Ok(p0.outputs == p0.expected_outputs && p1.outputs == p1.expected_outputs);
+    };
+
+    // Only run the checker when there are new outputs
+    let output_lens = (p0.outputs.len(), p1.outputs.len());
+    if self.checked_outputs == Some(output_lens) {
+      return Ok(false);
+    }
+    self.checked_outputs = Some(output_lens);
+
+    let [p0_inputs, p1_inputs] = &self.test_case_inputs;
+    match checker.check((
+      p0_inputs.to_vec(),
+      p0.outputs.to_vec(),
+      p1_inputs.to_vec(),
+      p1.outputs.to_vec(),
+    )) {
+      CheckResult::Correct => Ok(true),
+      CheckResult::Incomplete => Ok(false),
+      CheckResult::Wrong(message) => Err(VMError::WrongOutputs(message)),
+    }
   }
 
-  pub fn processor_1(&mut self) -> RefMut<Processor> {
-    (*self.processors[1]).borrow_mut()
+  pub fn height(&self) -> u16 {
+    self
+      .processors
+      .iter()
+      .map(|processor| (**processor).borrow().rows() + 1)
+      .sum::<usize>() as u16
+      + 1
   }
+}
 
-  // Returns Ok(true) when the puzzle is solved
-  pub fn step(&mut self) -> Result<bool, (VMError, usize)> {
+impl Machine for VirtualMachine {
+  type Error = VMError;
+
+  fn step(&mut self) -> Result<bool, (VMError, usize)> {
     // Have we solved the puzzle?
-    if self.processors.iter().all(|processor| {
-      let processor = (**processor).borrow();
-      processor.inputs.len() == 0 && processor.outputs == processor.expected_outputs
-    }) {
-      return Ok(true);
+    match self.is_solved() {
+      Ok(true) => return Ok(true),
+      Ok(false) => {},
+      Err(e) => return Err((e, self.processors.len())),
+    }
+
+    // Neither of these errors belong to a single processor
+    if self.cycle >= self.max_cycles {
+      return Err((VMError::CycleLimitExceeded, self.processors.len()));
     }
 
     for processor in self.processors.iter_mut() {
@@ -286,68 +453,128 @@ impl VirtualMachine {
     self.cycle = self.cycle.wrapping_add(1);
 
     if let Some(e) = result {
-      Err(e)
-    } else {
-      Ok(false)
+      return Err(e);
     }
+
+    let [p0, p1] = &self.processors;
+    let (p0, p1) = ((**p0).borrow(), (**p1).borrow());
+    if self
+      .loop_detector
+      .is_repeated([p0.io_state(), p1.io_state()], [p0.state(), p1.state()])
+    {
+      return Err((VMError::InfiniteLoop, self.processors.len()));
+    }
+
+    Ok(false)
   }
 
-  fn print_processor_program(&self, processor: &Processor, line: u16) -> io::Result<()> {
-    let mut stdout = io::stdout();
+  fn test_case(&self) -> usize {
+    self.test_case
+  }
 
-    if line > 0 {
-      stdout.queue(cursor::MoveDown(line))?;
-    }
+  fn get_cycle(&self) -> u32 {
+    self.cycle
+  }
 
-    processor.grid.print()?;
+  fn count_symbols(&self) -> usize {
+    self
+      .processors
+      .iter()
+      .map(|processor| (**processor).borrow().count_symbols())
+      .sum()
+  }
 
-    stdout
-      .queue(cursor::RestorePosition)?
-      .queue(cursor::MoveDown(processor.row as u16 + 2 + line))?
-      .queue(cursor::MoveRight(processor.col as u16 + 1))?;
+  fn count_area(&self) -> usize {
+    self
+      .processors
+      .iter()
+      .map(|processor| (**processor).borrow().grid.used_area())
+      .sum()
+  }
 
-    if processor.skip_next_instruction {
-      write!(stdout, "{}", processor.direction.get_arrow().red())?;
-    } else if self.is_at_breakpoint() && processor.is_at_breakpoint() {
-      stdout.queue(style::SetBackgroundColor(Color::DarkCyan))?;
-      write!(stdout, "{}", processor.direction.get_arrow().black())?;
-      stdout.queue(style::ResetColor)?;
-    } else {
-      write!(stdout, "{}", processor.direction.get_arrow().green())?;
-    }
+  fn instructions_executed(&self) -> u32 {
+    self
+      .processors
+      .iter()
+      .map(|processor| (**processor).borrow().instructions_executed)
+      .sum()
+  }
 
-    Ok(())
+  fn peak_stack_depth(&self) -> usize {
+    self
+      .processors
+      .iter()
+      .map(|processor| (**processor).borrow().peak_stack_depth)
+      .max()
+      .unwrap_or(0)
   }
 
-  pub fn height(&self) -> u16 {
+  fn blocked_cycles(&self) -> Option<u32> {
+    Some(
+      self
+        .processors
+        .iter()
+        .map(|processor| (**processor).borrow().blocked_cycles)
+        .sum(),
+    )
+  }
+
+  fn is_at_breakpoint(&self) -> bool {
     self
       .processors
       .iter()
-      .map(|processor| (**processor).borrow().rows() + 1)
-      .sum::<usize>() as u16
-      + 1
+      .any(|processor| (**processor).borrow().is_at_breakpoint())
+  }
+
+  fn snapshot(&self) -> Self {
+    let [p0, p1] = &self.processors;
+    Self {
+      processors: link_processors((**p0).borrow().clone(), (**p1).borrow().clone()),
+      cycle: self.cycle,
+      test_case: self.test_case,
+      checker: self.checker.clone(),
+      test_case_inputs: self.test_case_inputs.clone(),
+      checked_outputs: self.checked_outputs,
+      max_cycles: self.max_cycles,
+      loop_detector: LoopDetector::new(),
+    }
   }
 }
 
+// Each processor needs a reference to the other one for transmit/receive
+fn link_processors(processor_0: Processor, processor_1: Processor) -> [Rc<RefCell<Processor>>; 2] {
+  let processor_0 = Rc::new(RefCell::new(processor_0));
+  let processor_1 = Rc::new(RefCell::new(processor_1));
+
+  (*processor_0).borrow_mut().other_processor = Some(Rc::downgrade(&processor_1));
+  (*processor_1).borrow_mut().other_processor = Some(Rc::downgrade(&processor_0));
+
+  [processor_0, processor_1]
+}
+
 #[allow(unused)]
 impl Processor {
-  pub fn new(program: Program, io: ProcessorIO) -> Self {
+  pub fn new(program: Program, io: ProcessorIO, limits: &LevelLimits) -> Self {
     let row = program.start_row() as i16;
     let col = program.start_col() as i16;
+    let direction = program.start_direction();
 
     Self {
       grid: program.into_grid(),
       row,
       col,
-      direction: Direction::Right, // Always starts facing right
+      direction,
       skip_next_instruction: false,
       last_was_number: false,
-      stack: Stack::new(),
+      stack: Stack::new(limits.stack_size),
       inputs: io.get_inputs().clone(),
-      outputs: PuzzleIO::new(),
+      outputs: PuzzleIO::new(limits.max_outputs),
       expected_outputs: io.get_outputs().clone(),
       other_processor: None,
       sending_status: SendStatus::None,
+      instructions_executed: 0,
+      peak_stack_depth: 0,
+      blocked_cycles: 0,
     }
   }
 
@@ -363,6 +590,22 @@ impl Processor {
     self.grid.count_symbols()
   }
 
+  // The sending status is left out since it gets recomputed at the start of every cycle
+  fn state(&self) -> ProcessorState {
+    (
+      self.row,
+      self.col,
+      self.direction,
+      self.skip_next_instruction,
+      self.last_was_number,
+      self.stack.clone(),
+    )
+  }
+
+  fn io_state(&self) -> (usize, usize) {
+    (self.inputs.len(), self.outputs.len())
+  }
+
   pub fn compute_send_status(&mut self) {
     self.sending_status = match self.grid.get_value(self.row as usize, self.col as usize) {
       Command::Transmit => SendStatus::Transmitting,
@@ -376,8 +619,12 @@ impl Processor {
   // Returns Ok(true) when the puzzle is solved
   pub fn step(&mut self) -> Result<(), VMError> {
     let mut is_number = false;
+    let mut is_executed = false;
     if !self.skip_next_instruction {
-      match self.grid.get_value(self.row as usize, self.col as usize) {
+      let command = *self.grid.get_value(self.row as usize, self.col as usize);
+      is_executed = command != Command::Empty;
+
+      match command {
         Command::Empty => {},
         Command::Up => {
           self.direction = Direction::Up;
@@ -477,15 +724,15 @@ impl Processor {
         },
         Command::IfLess => {
           let val = self.peek()?;
-          self.skip_next_instruction = !(val < 0);
+          self.skip_next_instruction = val >= 0;
         },
         Command::IfEqual => {
           let val = self.peek()?;
-          self.skip_next_instruction = !(val == 0);
+          self.skip_next_instruction = val != 0;
         },
         Command::IfGreater => {
           let val = self.peek()?;
-          self.skip_next_instruction = !(val > 0);
+          self.skip_next_instruction = val <= 0;
         },
         Command::Skip => {
           self.skip_next_instruction = true;
@@ -519,6 +766,14 @@ impl Processor {
     } else {
       self.skip_next_instruction = false;
     }
+    self.peak_stack_depth = self.peak_stack_depth.max(self.stack.len());
+
+    // Waiting on the other processor doesn't count as running the instruction
+    if self.sending_status.is_blocking() {
+      self.blocked_cycles += 1;
+    } else if is_executed {
+      self.instructions_executed += 1;
+    }
 
     // Now perform movement (if not waiting for the send status)
     if !self.sending_status.is_blocking() {
@@ -708,32 +963,57 @@ impl Printable for VirtualMachine {
       .queue(cursor::MoveRight(p0.cols() as u16 + 11))?
       .queue(cursor::SavePosition)?;
 
-    write!(stdout, "Stack    Input  Output Expected")?;
+    // A checker accepts other answers, so only show the expected outputs as an example
+    if self.checker.is_some() {
+      write!(stdout, "Stack    Input  Output Example")?;
+    } else {
+      write!(stdout, "Stack    Input  Output Expected")?;
+    }
+
+    // Every box uses the same number of rows so the two processors line up
+    let rows = [&p0, &p1]
+      .iter()
+      .map(|p| {
+        p.stack
+          .capacity()
+          .max(p.inputs.capacity())
+          .max(p.expected_outputs.capacity())
+      })
+      .max()
+      .unwrap_or(0);
 
     stdout
       .queue(cursor::RestorePosition)?
       .queue(cursor::MoveDown(1))?
       .queue(cursor::SavePosition)?;
-    p0.stack.print()?;
+    p0.stack.print_padded(rows)?;
     stdout.queue(cursor::RestorePosition)?.queue(cursor::MoveRight(9))?;
-    p0.inputs.print()?;
+    p0.inputs.print_padded(rows)?;
     stdout.queue(cursor::RestorePosition)?.queue(cursor::MoveRight(16))?;
-    p0.outputs.print_with_expected_outputs(&p0.expected_outputs)?;
+    if self.checker.is_some() {
+      p0.outputs.print_padded(rows)?;
+    } else {
+      p0.outputs.print_with_expected_outputs(&p0.expected_outputs, rows)?;
+    }
     stdout.queue(cursor::RestorePosition)?.queue(cursor::MoveRight(23))?;
-    p0.expected_outputs.print()?;
+    p0.expected_outputs.print_padded(rows)?;
 
     stdout
       .queue(cursor::RestorePosition)?
-      .queue(cursor::MoveDown(MAX_STACK_ENTRIES as u16 + 1))?
+      .queue(cursor::MoveDown(rows as u16 + 1))?
       .queue(cursor::SavePosition)?;
 
-    p1.stack.print()?;
+    p1.stack.print_padded(rows)?;
     stdout.queue(cursor::RestorePosition)?.queue(cursor::MoveRight(9))?;
-    p1.inputs.print()?;
+    p1.inputs.print_padded(rows)?;
     stdout.queue(cursor::RestorePosition)?.queue(cursor::MoveRight(16))?;
-    p1.outputs.print_with_expected_outputs(&p1.expected_outputs)?;
+    if self.checker.is_some() {
+      p1.outputs.print_padded(rows)?;
+    } else {
+      p1.outputs.print_with_expected_outputs(&p1.expected_outputs, rows)?;
+    }
     stdout.queue(cursor::RestorePosition)?.queue(cursor::MoveRight(23))?;
-    p1.expected_outputs.print()?;
+    p1.expected_outputs.print_padded(rows)?;
 
     stdout.queue(cursor::RestorePosition)?;
     write!(stdout, "├{0}┤   ├{0}┤ ├{0}┤ ├{0}┤", "─".repeat(VAL_CHAR_WIDTH))?;
@@ -749,16 +1029,24 @@ impl Printable for Command {
   }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl fmt::Display for Command {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.get_char())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Stack {
   values: VecDeque<i16>,
+  capacity: usize,
 }
 
 #[allow(unused)]
 impl Stack {
-  pub fn new() -> Self {
+  pub fn new(capacity: usize) -> Self {
     Self {
       values: VecDeque::new(),
+      capacity,
     }
   }
 
@@ -766,13 +1054,17 @@ impl Stack {
     self.values.len()
   }
 
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
   pub fn is_empty(&self) -> bool {
     self.values.len() == 0
   }
 
   // Returns false if the stack overflows
   pub fn push(&mut self, val: i16) -> bool {
-    if self.values.len() < MAX_STACK_ENTRIES {
+    if self.values.len() < self.capacity {
       self.values.push_back(val.clamp(VAL_MIN, VAL_MAX));
       true
     } else {
@@ -806,8 +1098,9 @@ impl Stack {
   }
 }
 
-impl Printable for Stack {
-  fn print(&self) -> io::Result<()> {
+impl Stack {
+  // Empty rows are added above the values to fill the box
+  fn print_padded(&self, rows: usize) -> io::Result<()> {
     let mut stdout = io::stdout();
     // ┌─┐
     // │ │
@@ -818,7 +1111,7 @@ impl Printable for Stack {
       .queue(cursor::MoveLeft(VAL_CHAR_WIDTH as u16 + 2))?
       .queue(cursor::MoveDown(1))?;
 
-    for _ in self.values.len()..MAX_STACK_ENTRIES {
+    for _ in self.values.len()..rows {
       write!(stdout, "│    │")?;
       stdout
         .queue(cursor::MoveLeft(VAL_CHAR_WIDTH as u16 + 2))?
@@ -838,15 +1131,18 @@ impl Printable for Stack {
   }
 }
 
-impl VMError {
-  pub fn get_msg(&self) -> &'static str {
+impl MachineError for VMError {
+  fn get_msg(&self) -> Cow<'static, str> {
     match self {
-      Self::NumericOverflow => "Numeric overflow",
-      Self::StackOverflow => "Stack overflow",
-      Self::StackUnderflow => "Stack underflow",
-      Self::NoInputs => "No inputs left",
-      Self::TooManyOutputs => "Too many outputs",
-      Self::Deadlock => "Deadlock",
+      Self::NumericOverflow => "Numeric overflow".into(),
+      Self::StackOverflow => "Stack overflow".into(),
+      Self::StackUnderflow => "Stack underflow".into(),
+      Self::NoInputs => "No inputs left".into(),
+      Self::TooManyOutputs => "Too many outputs".into(),
+      Self::Deadlock => "Deadlock".into(),
+      Self::InfiniteLoop => "Infinite loop detected".into(),
+      Self::CycleLimitExceeded => "Cycle limit exceeded".into(),
+      Self::WrongOutputs(message) => message.clone().into(),
     }
   }
 }
@@ -0,0 +1,135 @@
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::Stylize;
+use crossterm::{cursor, QueueableCommand};
+use std::io::{self, Write};
+
+use crate::isa::{MAX_ANNOTATION_LEN, MAX_NOTE_LEN};
+use crate::state::truncate_string;
+
+const STATUS_ROW: u16 = 1;
+const STATUS_WIDTH: usize = 54;
+
+/// What the text typed into an editor's status line is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptTarget {
+  Annotation,
+  Note,
+}
+
+/// Text being typed into an editor's status line
+#[derive(Debug, Clone)]
+pub struct Prompt {
+  target: PromptTarget,
+  text: String,
+}
+
+/// What the editor should do after an event while typing
+pub enum PromptAction {
+  Redraw,
+  Cancel,
+  // The trimmed text, empty text removes the annotation or note
+  Save(String),
+  Quit,
+}
+
+impl PromptTarget {
+  fn label(self) -> &'static str {
+    match self {
+      Self::Annotation => "Annotation:",
+      Self::Note => "Note:",
+    }
+  }
+
+  fn max_len(self) -> usize {
+    match self {
+      Self::Annotation => MAX_ANNOTATION_LEN,
+      Self::Note => MAX_NOTE_LEN,
+    }
+  }
+}
+
+impl Prompt {
+  // Starts with the current text, so it can be edited instead of typed out again
+  pub fn new(target: PromptTarget, text: Option<&str>) -> Self {
+    Self {
+      target,
+      text: text.unwrap_or_default().to_string(),
+    }
+  }
+
+  pub fn target(&self) -> PromptTarget {
+    self.target
+  }
+
+  ///
+  /// Apply one terminal event to the text, which is only saved with Enter
+  ///   Returns None for events that don't change anything
+  ///
+  pub fn handle_event(&mut self, event: Event) -> Option<PromptAction> {
+    let max_len = self.target.max_len();
+    match event {
+      Event::Resize(_, _) => Some(PromptAction::Redraw),
+
+      // Only the first line of pasted text is used
+      Event::Paste(text) => {
+        let room = max_len.saturating_sub(self.text.chars().count());
+        let line = text.lines().next().unwrap_or_default();
+        self.text.extend(line.chars().filter(|c| !c.is_control()).take(room));
+        Some(PromptAction::Redraw)
+      },
+
+      Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(PromptAction::Quit),
+        KeyCode::Esc => Some(PromptAction::Cancel),
+        KeyCode::Enter => Some(PromptAction::Save(self.text.trim().to_string())),
+        KeyCode::Backspace => {
+          self.text.pop();
+          Some(PromptAction::Redraw)
+        },
+        KeyCode::Char(c)
+          if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+            && self.text.chars().count() < max_len =>
+        {
+          self.text.push(c);
+          Some(PromptAction::Redraw)
+        },
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+}
+
+///
+/// Print an editor's status line, above the grid starting at the given column
+///   Shows the prompt while typing, otherwise the annotation under the cursor or the solution's note
+///
+pub fn print_status(col: u16, prompt: Option<&Prompt>, annotation: Option<&str>, note: &str) -> io::Result<()> {
+  let mut stdout = io::stdout();
+  stdout.queue(cursor::MoveTo(col, STATUS_ROW))?;
+
+  if let Some(prompt) = prompt {
+    let label = prompt.target.label();
+    // Keep the end of the text in view, with room for the "_"
+    let width = STATUS_WIDTH - label.len() - 2;
+    let skip = prompt.text.chars().count().saturating_sub(width);
+    let shown: String = prompt.text.chars().skip(skip).collect();
+    return write!(stdout, "{} {}_", label.dark_cyan(), shown);
+  }
+
+  match annotation {
+    Some(text) => write!(
+      stdout,
+      "{} {}",
+      "Annotation:".dark_cyan(),
+      truncate_string(text, STATUS_WIDTH - 12)
+    ),
+    None if !note.is_empty() => write!(
+      stdout,
+      "{} {}",
+      "Note:".dark_cyan(),
+      truncate_string(note, STATUS_WIDTH - 6).dark_grey()
+    ),
+    None => Ok(()),
+  }
+}
@@ -0,0 +1,178 @@
+use rlua::prelude::*;
+use rlua::{HookTriggers, StdLib};
+use std::fs;
+
+// Level scripts only need to generate numbers, so these are generous
+const INSTRUCTION_LIMIT: u64 = 50_000_000;
+const INSTRUCTIONS_PER_HOOK: u32 = 10_000;
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+// No "io", "os", or "debug" libraries, since level packs can come from anyone
+const STANDARD_LIBRARIES: StdLib = StdLib::BASE
+  .union(StdLib::COROUTINE)
+  .union(StdLib::TABLE)
+  .union(StdLib::STRING)
+  .union(StdLib::UTF8)
+  .union(StdLib::MATH)
+  .union(StdLib::PACKAGE);
+
+// Base library functions that can read files outside of the level pack
+const REMOVED_GLOBALS: &[&str] = &["dofile", "loadfile"];
+
+// Set once the script goes over the instruction limit, so it can't catch the error and keep running
+const LIMIT_ERROR_KEY: &str = "instructionLimitError";
+
+// Every Lua file loaded by the script, so the test case cache knows when they change
+const LOADED_FILES_KEY: &str = "loadedFiles";
+
+///
+/// Create a sandboxed Lua state and run the level script inside it
+///   The script can only require() other Lua files from the level pack folder
+///   Errors are converted into a printable string with the script name and line
+///
+pub fn load_level_script(folder: &str, lua_file: &str, seed: u32) -> Result<Lua, String> {
+  let lua_code = fs::read_to_string(format!("{folder}/{lua_file}")).map_err(|e| format!("{lua_file}: {e}"))?;
+
+  let lua = Lua::new_with(STANDARD_LIBRARIES);
+  lua.set_memory_limit(Some(MEMORY_LIMIT));
+  limit_instructions(&lua);
+
+  lua
+    .context(|ctx| {
+      let globals = ctx.globals();
+      for name in REMOVED_GLOBALS {
+        globals.set(*name, LuaNil)?;
+      }
+
+      ctx.set_named_registry_value(LOADED_FILES_KEY, ctx.create_sequence_from([lua_file])?)?;
+
+      rethrow_limit_error(ctx, &globals, "pcall")?;
+      rethrow_limit_error(ctx, &globals, "xpcall")?;
+      rethrow_limit_error(ctx, &globals.get("coroutine")?, "resume")?;
+
+      // Only search the levels folder, and never load C libraries
+      //  The searcher is written in Rust so the script can't change the path
+      let package: LuaTable = globals.get("package")?;
+      let searchers: LuaTable = package.get("searchers")?;
+      let preload_searcher: LuaFunction = searchers.get(1)?;
+      package.set(
+        "searchers",
+        ctx.create_sequence_from([preload_searcher, create_level_searcher(ctx, folder)?])?,
+      )?;
+
+      // Seed the random number generator
+      globals
+        .get::<_, LuaTable>("math")?
+        .get::<_, LuaFunction>("randomseed")?
+        .call::<_, ()>(seed)?;
+
+      // Load the script code
+      //  The "@" tells Lua to use the file name in error messages
+      ctx.load(&lua_code).set_name(&format!("@{lua_file}"))?.exec()
+    })
+    .map_err(|e| script_error(lua_file, &e))?;
+
+  Ok(lua)
+}
+
+///
+/// Reset the number of instructions the script can run before it gets stopped
+///   Call before each batch of work, so a runaway script can't hang the game
+///
+pub fn limit_instructions(lua: &Lua) {
+  let mut instructions = 0;
+  let triggers = HookTriggers {
+    every_nth_instruction: Some(INSTRUCTIONS_PER_HOOK),
+    ..Default::default()
+  };
+
+  lua.set_hook(triggers, move |ctx, debug| {
+    instructions += INSTRUCTIONS_PER_HOOK as u64;
+    if instructions <= INSTRUCTION_LIMIT {
+      return Ok(());
+    }
+
+    let source = debug.source();
+    let file = String::from_utf8_lossy(source.short_src.unwrap_or_default());
+    let message = format!(
+      "{file}:{}: script took too long to run (over {INSTRUCTION_LIMIT} instructions)",
+      debug.curr_line()
+    );
+    ctx.set_named_registry_value(LIMIT_ERROR_KEY, message.as_str())?;
+    Err(LuaError::RuntimeError(message))
+  });
+
+  // Clear any error from the last batch of work
+  lua.context(|ctx| ctx.unset_named_registry_value(LIMIT_ERROR_KEY)).ok();
+}
+
+// Wrap a function that catches errors, so it passes on the instruction limit error instead
+fn rethrow_limit_error<'lua>(ctx: LuaContext<'lua>, table: &LuaTable<'lua>, name: &str) -> LuaResult<()> {
+  let original = ctx.create_registry_value(table.get::<_, LuaFunction>(name)?)?;
+  let wrapper = ctx.create_function(move |ctx, args: LuaMultiValue| {
+    let results: LuaMultiValue = ctx.registry_value::<LuaFunction>(&original)?.call(args)?;
+    match ctx.named_registry_value::<_, Option<String>>(LIMIT_ERROR_KEY)? {
+      Some(message) => Err(LuaError::RuntimeError(message)),
+      None => Ok(results),
+    }
+  })?;
+  table.set(name, wrapper)
+}
+
+///
+/// Searcher for require() that only loads Lua files from the level pack folder
+///   Module names use dots for subfolders like normal, but can't contain any path characters
+///   Returns a string explaining why the module wasn't found, which Lua adds to the error message
+///
+fn create_level_searcher<'lua>(ctx: LuaContext<'lua>, folder: &str) -> LuaResult<LuaFunction<'lua>> {
+  let folder = folder.to_string();
+  ctx.create_function(move |ctx, name: String| {
+    let is_valid_part =
+      |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !name.split('.').all(is_valid_part) {
+      return Ok(LuaValue::String(
+        ctx.create_string(&format!("\n\tinvalid module name '{name}'"))?,
+      ));
+    }
+
+    let lua_file = format!("{}.lua", name.replace('.', "/"));
+    match fs::read_to_string(format!("{folder}/{lua_file}")) {
+      Ok(code) => {
+        let loaded_files: LuaTable = ctx.named_registry_value(LOADED_FILES_KEY)?;
+        loaded_files.set(loaded_files.raw_len() + 1, lua_file.as_str())?;
+        Ok(LuaValue::Function(
+          ctx.load(&code).set_name(&format!("@{lua_file}"))?.into_function()?,
+        ))
+      },
+      Err(_) => Ok(LuaValue::String(
+        ctx.create_string(&format!("\n\tno file '{lua_file}' in the level pack"))?,
+      )),
+    }
+  })
+}
+
+/// Level script and every module it required, relative to the level pack folder
+pub fn loaded_files(lua: &Lua) -> LuaResult<Vec<String>> {
+  lua.context(|ctx| {
+    ctx
+      .named_registry_value::<_, LuaTable>(LOADED_FILES_KEY)?
+      .sequence_values()
+      .collect()
+  })
+}
+
+/// Convert a Lua error into a single line, without the traceback
+pub fn script_error(lua_file: &str, error: &LuaError) -> String {
+  match error {
+    LuaError::CallbackError { cause, .. } => script_error(lua_file, cause),
+    LuaError::RuntimeError(message) | LuaError::SyntaxError { message, .. } => message
+      .lines()
+      .take_while(|line| !line.starts_with("stack traceback:"))
+      .map(str::trim)
+      .filter(|line| !line.is_empty())
+      .collect::<Vec<_>>()
+      .join(" "),
+    LuaError::MemoryError(_) => format!("{lua_file}: script used too much memory (over {MEMORY_LIMIT} bytes)"),
+    e => format!("{lua_file}: {}", e.to_string().lines().next().unwrap_or_default()),
+  }
+}
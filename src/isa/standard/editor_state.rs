@@ -1,45 +1,121 @@
 use crossterm::{
   cursor,
-  event::{self, Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind},
+  event::{self, Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
   style::{self, Color, Stylize},
   ExecutableCommand, QueueableCommand,
 };
 use std::io::{self, Write};
 
-use super::execute_state::{ExecuteState, Speed};
+use super::execute_state::ExecuteState;
 use super::puzzle::TestCaseSet;
 use super::solution::Solution;
 use super::vm::{Command, VirtualMachine};
-use crate::{global_state::GlobalState, isa, level::LevelIndex, printable::Printable, state::State};
+use crate::clipboard::Clipboard;
+use crate::grid::{Rect, Transform};
+use crate::isa::prompt::{self, Prompt, PromptAction, PromptTarget};
+use crate::isa::{CommandPalette, Solution as _, Speed};
+use crate::keybindings::{Action, KeyBindings};
+use crate::level::{LevelIndex, LevelLimits};
+use crate::undo::UndoHistory;
+use crate::{global_state::GlobalState, isa, printable::Printable, state::State};
 use crate::{
   isa::SolutionManager,
   state::{print_string, ShowHelpState},
 };
 
-static INSTRUCTIONS: &str = r#"
-│Esc    = Main Menu
-│Ctrl-C = Close Program
-│
-│Tab    = Step
-│Space  = Start/Stop
-│[  ]   = Test Case
-│,      = Breakpoint
-│Arrow  = Move Cursor
-│Delete = Clear
-│asdw   = ←↓→↑ (Move)
-│/ \    = / \ (Bounce)
-│*      = » (Skip)
-│0-9    = 0-9
-│p      = ☼ (Pop)
-│c      = © (Copy)
-│~      = ∫ (Swap)
-│^ v    = ∩ u (Rotate)
-│+ -    = (Add, Sub)
-│< = >  = (Compare to 0)
-│i      = Ї (Input)
-│o      = Θ (Output)
-│?      = (Has input?)
-│b      = Set start"#;
+static INSTRUCTIONS: &[&str] = &["│Esc    = Main Menu", "│Ctrl-C = Close Program"];
+
+// Generated from the keybindings, after the fixed instructions above
+static ACTION_INSTRUCTIONS: &[(&[Action], &str)] = &[
+  (&[Action::Step], "Step"),
+  (&[Action::Run], "Start/Stop"),
+  (&[Action::PreviousTestCase, Action::NextTestCase], "Test Case"),
+  (&[Action::Breakpoint], "Breakpoint"),
+  (&[Action::Undo, Action::Redo], "Undo/Redo"),
+  (&[Action::Select, Action::CopyAll], "Select/Copy All"),
+  (&[Action::Clear], "Clear"),
+  (
+    &[Action::Annotate, Action::EditNote, Action::ToggleAnnotations],
+    "Notes/Highlight",
+  ),
+];
+
+// Replace the instructions above while an area is selected
+static SELECT_INSTRUCTIONS: &[&str] = &["│Esc    = Stop Selecting"];
+
+static SELECT_ACTION_INSTRUCTIONS: &[(&[Action], &str)] = &[
+  (&[Action::Copy, Action::Cut, Action::Paste], "Copy/Cut/Paste"),
+  (
+    &[
+      Action::MoveAreaLeft,
+      Action::MoveAreaDown,
+      Action::MoveAreaUp,
+      Action::MoveAreaRight,
+    ],
+    "Move Area",
+  ),
+  (
+    &[
+      Action::ShiftLeft,
+      Action::ShiftDown,
+      Action::ShiftUp,
+      Action::ShiftRight,
+    ],
+    "Shift Around",
+  ),
+  (&[Action::RotateCounterClockwise, Action::RotateClockwise], "Rotate"),
+  (&[Action::MirrorHorizontally, Action::MirrorVertically], "Mirror"),
+];
+
+static FILL_INSTRUCTION: &str = "│Symbol = Fill Area";
+
+static SELECT_END_INSTRUCTIONS: &[(&[Action], &str)] = &[
+  (&[Action::Clear], "Clear Area"),
+  (&[Action::Undo, Action::Redo], "Undo/Redo"),
+];
+
+// Only shown if the level allows at least one of the commands
+static COMMAND_INSTRUCTIONS: &[(&str, &[Command])] = &[
+  (
+    "←↓→↑ (Move)",
+    &[Command::Left, Command::Down, Command::Right, Command::Up],
+  ),
+  ("/ \\ (Bounce)", &[Command::ForwardSlash, Command::BackSlash]),
+  ("» (Skip)", &[Command::Skip]),
+  (
+    "0-9",
+    &[
+      Command::Zero,
+      Command::One,
+      Command::Two,
+      Command::Three,
+      Command::Four,
+      Command::Five,
+      Command::Six,
+      Command::Seven,
+      Command::Eight,
+      Command::Nine,
+    ],
+  ),
+  ("☼ (Pop)", &[Command::Pop]),
+  ("© (Copy)", &[Command::Copy]),
+  ("∫ (Swap)", &[Command::SwapTop2]),
+  ("∩ u (Rotate)", &[Command::RotateUp, Command::RotateDown]),
+  ("(Add, Sub)", &[Command::Add, Command::Subtract]),
+  (
+    "(Compare to 0)",
+    &[Command::IfLess, Command::IfEqual, Command::IfGreater],
+  ),
+  ("Ї Θ (In/Out)", &[Command::In, Command::Out]),
+  ("(Has input?)", &[Command::HasInput]),
+];
+
+static START_INSTRUCTIONS: &[(&[Action], &str)] = &[(&[Action::SetStart], "Set start")];
+
+// Width of the keys column in the instructions
+const KEYS_WIDTH: usize = 6;
+
+// The status line is above the grid, left of the instructions
 
 pub struct EditorState {
   level_index: LevelIndex,
@@ -49,9 +125,21 @@ pub struct EditorState {
 
   cursor_row: isize,
   cursor_col: isize,
+  // The corner of the selected area across from the cursor
+  selection_anchor: Option<(usize, usize)>,
 
   test_cases: TestCaseSet,
   test_case_index: isize,
+
+  palette: CommandPalette<Command>,
+
+  // Kept in the editor, so it survives running the solution and coming back
+  history: UndoHistory<Solution>,
+  // The solution from before a right button drag, which is one edit however many cells it clears
+  erase_start: Option<Solution>,
+
+  in_prompt: Option<Prompt>,
+  highlight_annotations: bool,
 }
 
 impl EditorState {
@@ -61,6 +149,7 @@ impl EditorState {
     solution: Solution,
     test_cases: TestCaseSet,
     test_case_index: usize,
+    palette: CommandPalette<Command>,
   ) -> Self {
     Self {
       level_index,
@@ -68,32 +157,235 @@ impl EditorState {
       solution,
       cursor_row: 0,
       cursor_col: 0,
+      selection_anchor: None,
       test_cases,
       test_case_index: test_case_index as isize,
+      palette,
+      history: UndoHistory::new(),
+      erase_start: None,
+      in_prompt: None,
+      highlight_annotations: false,
     }
   }
 
+  // Commands the level doesn't allow are ignored, and the whole area is filled if one is selected
   fn set_cell(&mut self, command: Command) {
+    if !self.palette.allows(command) {
+      return;
+    }
+
+    match self.selection() {
+      Some(rect) => self.solution.grid_mut().fill_rect(&rect, command),
+      None => self
+        .solution
+        .set_grid_value(self.cursor_row as usize, self.cursor_col as usize, command),
+    }
+  }
+
+  fn cursor(&self) -> (usize, usize) {
+    (self.cursor_row as usize, self.cursor_col as usize)
+  }
+
+  fn selection(&self) -> Option<Rect> {
     self
-      .solution
-      .set_grid_value(self.cursor_row as usize, self.cursor_col as usize, command);
+      .selection_anchor
+      .map(|anchor| Rect::from_corners(anchor, self.cursor()))
+  }
+
+  // The cursor goes in the bottom-right corner, so the area can be moved or transformed again right away
+  fn select(&mut self, rect: Rect) {
+    self.selection_anchor = Some((rect.top, rect.left));
+    self.cursor_row = rect.bottom as isize;
+    self.cursor_col = rect.right as isize;
+  }
+
+  // Without a selection, only the cell under the cursor is copied
+  fn copy_selection(&self, global_state: &mut GlobalState) -> io::Result<()> {
+    let rect = self
+      .selection()
+      .unwrap_or(Rect::from_corners(self.cursor(), self.cursor()));
+    self.copy_rect(global_state, &rect)
+  }
+
+  // Copied to the terminal's clipboard too, so it can be pasted outside the game
+  fn copy_rect(&self, global_state: &mut GlobalState, rect: &Rect) -> io::Result<()> {
+    let clipboard = Clipboard::copy(self.solution.grid(), rect, Command::get_char);
+    clipboard.copy_to_terminal()?;
+    global_state.set_clipboard(clipboard);
+    Ok(())
+  }
+
+  // The pasted cells get selected, so they can be moved right away
+  fn paste(&mut self, global_state: &GlobalState) {
+    let clipboard = global_state.clipboard();
+    if clipboard.is_empty() {
+      return;
+    }
+
+    let (top, left) = self.selection().map_or(self.cursor(), |rect| (rect.top, rect.left));
+    // Symbols, or the keys the commands are typed with
+    let keys = global_state.keybindings().standard_commands();
+    let values = clipboard.to_values(|c| {
+      Command::from_char(c)
+        .or_else(|| keys.typed_with(c))
+        .filter(|command| self.palette.allows(*command))
+    });
+    if let Some(rect) = self.solution.grid_mut().paste(top, left, &values) {
+      self.select(rect);
+    }
+  }
+
+  // The selection follows the area, so it can be transformed again right away
+  fn transform(&mut self, transform: Transform) {
+    let selection = self.selection();
+    let target = self.solution.transform(selection, transform, &self.palette);
+    if let (Some(_), Some(target)) = (selection, target) {
+      self.select(target);
+    }
+  }
+
+  fn shift(&mut self, row_offset: isize, col_offset: isize) {
+    let selection = self.selection();
+    self.solution.shift(selection, row_offset, col_offset);
+  }
+
+  // The selected area moves one cell at a time, and never past the edges
+  fn move_selection(&mut self, row_offset: isize, col_offset: isize) {
+    let Some(rect) = self.selection() else {
+      return;
+    };
+    if let Some(target) = self.solution.grid_mut().move_rect(&rect, row_offset, col_offset) {
+      self.select(target);
+    }
+  }
+
+  fn start_prompt(&mut self, target: PromptTarget) {
+    let (row, col) = self.cursor();
+    let prompt = match target {
+      PromptTarget::Annotation => Prompt::new(target, self.solution.grid().annotation(row, col)),
+      PromptTarget::Note => Prompt::new(target, Some(self.solution.note())),
+    };
+    self.in_prompt = Some(prompt);
+  }
+
+  fn execute_prompt(mut self: Box<Self>, global_state: &mut GlobalState) -> io::Result<Option<Box<dyn State>>> {
+    let Some(prompt) = self.in_prompt.as_mut() else {
+      return Ok(Some(self));
+    };
+
+    loop {
+      // `read()` blocks until an `Event` is available
+      let event = match event::read() {
+        Ok(e) => e,
+        Err(_) => return Ok(None),
+      };
+
+      match prompt.handle_event(event) {
+        Some(PromptAction::Redraw) => return Ok(Some(self)),
+        Some(PromptAction::Quit) => return Ok(None),
+        Some(PromptAction::Cancel) => {
+          self.in_prompt = None;
+          return Ok(Some(self));
+        },
+        Some(PromptAction::Save(text)) => {
+          let target = prompt.target();
+          self.in_prompt = None;
+          let before = self.solution.clone();
+          match target {
+            PromptTarget::Annotation => {
+              let (row, col) = self.cursor();
+              self.solution.grid_mut().set_annotation(row, col, text);
+            },
+            PromptTarget::Note => self.solution.set_note(text),
+          }
+          self.history.record(before, &self.solution);
+          self.save(global_state);
+          return Ok(Some(self));
+        },
+        None => {},
+      }
+    }
+  }
+
+  fn print_status(&self) -> io::Result<()> {
+    let (row, col) = self.cursor();
+    let annotation = self.solution.grid().annotation(row, col);
+    prompt::print_status(0, self.in_prompt.as_ref(), annotation, self.solution.note())
+  }
+
+  // Called once the right button is let go, or on anything else happening in between
+  fn finish_erasing(&mut self, global_state: &mut GlobalState) {
+    if let Some(before) = self.erase_start.take() {
+      self.history.record(before, &self.solution);
+      self.save(global_state);
+    }
+  }
+
+  fn undo(&mut self) {
+    if let Some(solution) = self.history.undo(&self.solution) {
+      self.solution = solution;
+    }
+  }
+
+  fn redo(&mut self) {
+    if let Some(solution) = self.history.redo(&self.solution) {
+      self.solution = solution;
+    }
+  }
+
+  // The fixed instructions, then the ones generated from the keybindings
+  fn instructions(&self, keybindings: &KeyBindings) -> Vec<String> {
+    let mut instructions = vec![String::new()];
+    match self.selection_anchor {
+      Some(_) => {
+        instructions.extend(SELECT_INSTRUCTIONS.iter().map(ToString::to_string));
+        instructions.extend(keybindings.instructions(SELECT_ACTION_INSTRUCTIONS, KEYS_WIDTH));
+        instructions.push(FILL_INSTRUCTION.to_string());
+        instructions.extend(keybindings.instructions(SELECT_END_INSTRUCTIONS, KEYS_WIDTH));
+      },
+      None => {
+        instructions.extend(INSTRUCTIONS.iter().map(ToString::to_string));
+        instructions.extend(keybindings.instructions(ACTION_INSTRUCTIONS, KEYS_WIDTH));
+      },
+    }
+
+    let commands = self.palette.filter_instructions(COMMAND_INSTRUCTIONS);
+    instructions.extend(keybindings.standard_commands().instructions(&commands, KEYS_WIDTH));
+    instructions.extend(keybindings.instructions(START_INSTRUCTIONS, KEYS_WIDTH));
+    instructions
+  }
+
+  fn save(&self, global_state: &mut GlobalState) {
+    let level_id = global_state.level(self.level_index).id();
+    <GlobalState as SolutionManager<isa::Standard>>::save_solution(
+      global_state,
+      level_id,
+      self.solution_index,
+      self.solution.clone(),
+    );
   }
 
   pub(crate) fn level_index(&self) -> LevelIndex {
     self.level_index
   }
 
-  pub(crate) fn vms(&self) -> Vec<VirtualMachine> {
+  pub(crate) fn solution_index(&self) -> usize {
+    self.solution_index
+  }
+
+  pub(crate) fn vms(&self, limits: &LevelLimits) -> Vec<VirtualMachine> {
     (0..self.test_cases.len())
       .map(|i| {
         let index = (self.test_case_index as usize + i).rem_euclid(self.test_cases.len());
-        VirtualMachine::new(self.solution.clone(), index + 1, &self.test_cases[index])
+        VirtualMachine::new(self.solution.clone(), index + 1, &self.test_cases[index], limits)
       })
       .collect()
   }
 
   pub(crate) fn toggle_breakpoint(&mut self, row: usize, col: usize) {
+    let before = self.solution.clone();
     self.solution.toggle_breakpoint(row, col);
+    self.history.record(before, &self.solution);
   }
 }
 
@@ -104,7 +396,20 @@ impl State for EditorState {
     let level = global_state.level(self.level_index);
     write!(stdout, "     {}", level.get_title(self.level_index).yellow())?;
 
+    self.solution.set_highlight_annotations(self.highlight_annotations);
     self.solution.print_at(2, 0)?;
+    self.print_status()?;
+
+    if let Some(rect) = self.selection() {
+      for (row, col) in rect.cells() {
+        stdout.queue(cursor::MoveTo(col as u16 + 1, row as u16 + 1 + 2))?;
+        write!(
+          stdout,
+          "{}",
+          self.solution.grid().get_value(row, col).get_char().black().on_grey()
+        )?;
+      }
+    }
 
     stdout
       .queue(cursor::MoveTo(self.solution.cols() as u16 + 2 + 8, 2))?
@@ -119,10 +424,12 @@ impl State for EditorState {
 
     self.test_cases[self.test_case_index as usize].print()?;
 
+    let instructions = self.instructions(global_state.keybindings());
+
     stdout
       .queue(cursor::MoveTo(55, 0))?
       .queue(style::SetForegroundColor(Color::DarkCyan))?;
-    print_string(INSTRUCTIONS)?;
+    print_string(&instructions.join("\n"))?;
 
     stdout
       .queue(style::ResetColor)?
@@ -136,6 +443,11 @@ impl State for EditorState {
   }
 
   fn execute(mut self: Box<Self>, global_state: &mut GlobalState) -> io::Result<Option<Box<dyn State>>> {
+    if self.in_prompt.is_some() {
+      return self.execute_prompt(global_state);
+    }
+
+    let before = self.solution.clone();
     loop {
       // `read()` blocks until an `Event` is available
       let event = match event::read() {
@@ -146,11 +458,29 @@ impl State for EditorState {
         },
       };
 
+      let erasing = matches!(
+        event,
+        Event::Mouse(MouseEvent {
+          kind: MouseEventKind::Down(MouseButton::Right) | MouseEventKind::Drag(MouseButton::Right),
+          ..
+        })
+      );
+      if !erasing {
+        self.finish_erasing(global_state);
+      }
+
       match event {
         Event::Resize(_, _) => {
           return Ok(Some(self));
         },
 
+        // Text pasted into the terminal replaces the clipboard, and is pasted the same way
+        Event::Paste(text) => {
+          global_state.set_clipboard(Clipboard::from_text(&text));
+          self.paste(global_state);
+          break;
+        },
+
         Event::Mouse(mouse) => {
           // Mouse only causes events inside the grid
           let mouse_row = (mouse.row as isize) - 1 - 2;
@@ -164,15 +494,25 @@ impl State for EditorState {
           }
 
           match mouse.kind {
-            // Left button just selects the space
-            MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left) => {
+            // Left button just selects the space, or an area when dragged
+            MouseEventKind::Down(MouseButton::Left) => {
+              self.selection_anchor = None;
+              self.cursor_row = mouse_row;
+              self.cursor_col = mouse_col;
+              return Ok(Some(self));
+            },
+            MouseEventKind::Drag(MouseButton::Left) => {
+              self.selection_anchor = self.selection_anchor.or(Some(self.cursor()));
               self.cursor_row = mouse_row;
               self.cursor_col = mouse_col;
               return Ok(Some(self));
             },
 
-            // Right button clears
+            // Right button clears just the clicked cell, even if an area is selected
+            //   Dragging clears every cell on the way, and saves once the button is let go
             MouseEventKind::Down(MouseButton::Right) | MouseEventKind::Drag(MouseButton::Right) => {
+              self.erase_start.get_or_insert_with(|| self.solution.clone());
+              self.selection_anchor = None;
               self.cursor_row = mouse_row;
               self.cursor_col = mouse_col;
               self.set_cell(Command::Empty);
@@ -188,6 +528,10 @@ impl State for EditorState {
             return Ok(None);
           },
 
+          KeyCode::Esc if self.selection_anchor.is_some() => {
+            self.selection_anchor = None;
+            return Ok(Some(self));
+          },
           KeyCode::Esc => {
             return Ok(Some(Box::new(ShowHelpState::<isa::Standard>::new(
               self.level_index,
@@ -196,205 +540,188 @@ impl State for EditorState {
             ))))
           },
 
-          // Start execution
-          KeyCode::Tab => return Ok(Some(Box::new(ExecuteState::new(*self, Speed::None)))),
-          KeyCode::Char(' ') => return Ok(Some(Box::new(ExecuteState::new(*self, Speed::Slow)))),
-
-          // Movement
-          KeyCode::Up | KeyCode::Char('k') => {
-            self.cursor_row = (self.cursor_row - 1).rem_euclid(self.solution.rows() as isize);
-            return Ok(Some(self));
-          },
-          KeyCode::Down | KeyCode::Char('j') => {
-            self.cursor_row = (self.cursor_row + 1).rem_euclid(self.solution.rows() as isize);
-            return Ok(Some(self));
-          },
-          KeyCode::Left | KeyCode::Char('h') => {
-            self.cursor_col = (self.cursor_col - 1).rem_euclid(self.solution.cols() as isize);
-            return Ok(Some(self));
-          },
-          KeyCode::Right | KeyCode::Char('l') => {
-            self.cursor_col = (self.cursor_col + 1).rem_euclid(self.solution.cols() as isize);
-            return Ok(Some(self));
-          },
-
-          // Select test case
-          KeyCode::Char(']') => {
-            self.test_case_index = (self.test_case_index + 1).rem_euclid(self.test_cases.len() as isize);
-            return Ok(Some(self));
-          },
-          KeyCode::Char('[') => {
-            self.test_case_index = (self.test_case_index - 1).rem_euclid(self.test_cases.len() as isize);
-            return Ok(Some(self));
-          },
-
-          // Breakpoint
-          KeyCode::Char(',') => {
-            self
-              .solution
-              .toggle_breakpoint(self.cursor_row as usize, self.cursor_col as usize);
-            break;
-          },
-
-          // Deletion
-          KeyCode::Backspace | KeyCode::Delete | KeyCode::Char('x') => {
-            self.set_cell(Command::Empty);
-            break;
-          },
-
-          // Arrow commands
-          KeyCode::Char('w') => {
-            self.set_cell(Command::Up);
-            break;
-          },
-          KeyCode::Char('s') => {
-            self.set_cell(Command::Down);
-            break;
-          },
-          KeyCode::Char('a') => {
-            self.set_cell(Command::Left);
-            break;
-          },
-          KeyCode::Char('d') => {
-            self.set_cell(Command::Right);
-            break;
-          },
-          KeyCode::Char('/') => {
-            self.set_cell(Command::ForwardSlash);
-            break;
-          },
-          KeyCode::Char('\\') => {
-            self.set_cell(Command::BackSlash);
-            break;
-          },
-          KeyCode::Char('*') => {
-            self.set_cell(Command::Skip);
-            break;
-          },
-
-          // Numbers
-          KeyCode::Char('0') => {
-            self.set_cell(Command::Zero);
-            break;
-          },
-          KeyCode::Char('1') => {
-            self.set_cell(Command::One);
-            break;
-          },
-          KeyCode::Char('2') => {
-            self.set_cell(Command::Two);
-            break;
-          },
-          KeyCode::Char('3') => {
-            self.set_cell(Command::Three);
-            break;
-          },
-          KeyCode::Char('4') => {
-            self.set_cell(Command::Four);
-            break;
-          },
-          KeyCode::Char('5') => {
-            self.set_cell(Command::Five);
-            break;
-          },
-          KeyCode::Char('6') => {
-            self.set_cell(Command::Six);
-            break;
-          },
-          KeyCode::Char('7') => {
-            self.set_cell(Command::Seven);
-            break;
-          },
-          KeyCode::Char('8') => {
-            self.set_cell(Command::Eight);
-            break;
-          },
-          KeyCode::Char('9') => {
-            self.set_cell(Command::Nine);
-            break;
-          },
-
-          // Stack operations
-          KeyCode::Char('p') => {
-            self.set_cell(Command::Pop);
-            break;
-          },
-          KeyCode::Char('c') => {
-            self.set_cell(Command::Copy);
-            break;
-          },
-          KeyCode::Char('~') => {
-            self.set_cell(Command::SwapTop2);
-            break;
-          },
-          KeyCode::Char('v') => {
-            self.set_cell(Command::RotateDown);
-            break;
-          },
-          KeyCode::Char('^') => {
-            self.set_cell(Command::RotateUp);
-            break;
-          },
-
-          // Math
-          KeyCode::Char('+') => {
-            self.set_cell(Command::Add);
-            break;
+          _ => {
+            let keybindings = global_state.keybindings();
+            let Some(action) = keybindings.editor_action(&key) else {
+              // Commands
+              if let Some(command) = keybindings.standard_commands().command(&key) {
+                self.set_cell(command);
+                break;
+              }
+              continue;
+            };
+
+            match action {
+              // Undo and redo replace the whole solution, so they aren't recorded as edits themselves
+              Action::Undo => {
+                self.undo();
+                self.save(global_state);
+                return Ok(Some(self));
+              },
+              Action::Redo => {
+                self.redo();
+                self.save(global_state);
+                return Ok(Some(self));
+              },
+
+              // Start execution
+              Action::Step => return Ok(Some(Box::new(ExecuteState::new(*self, Speed::None, global_state)))),
+              Action::Run => return Ok(Some(Box::new(ExecuteState::new(*self, Speed::Slow, global_state)))),
+
+              // Selection
+              Action::Select => {
+                self.selection_anchor = match self.selection_anchor {
+                  Some(_) => None,
+                  None => Some(self.cursor()),
+                };
+                return Ok(Some(self));
+              },
+              Action::Copy => {
+                self.copy_selection(global_state)?;
+                return Ok(Some(self));
+              },
+              Action::CopyAll => {
+                let rect = self.solution.grid().full_rect();
+                self.copy_rect(global_state, &rect)?;
+                return Ok(Some(self));
+              },
+              Action::Cut => {
+                self.copy_selection(global_state)?;
+                self.set_cell(Command::Empty);
+                break;
+              },
+              Action::Paste => {
+                self.paste(global_state);
+                break;
+              },
+
+              // Move the selected area
+              Action::MoveAreaUp => {
+                self.move_selection(-1, 0);
+                break;
+              },
+              Action::MoveAreaDown => {
+                self.move_selection(1, 0);
+                break;
+              },
+              Action::MoveAreaLeft => {
+                self.move_selection(0, -1);
+                break;
+              },
+              Action::MoveAreaRight => {
+                self.move_selection(0, 1);
+                break;
+              },
+
+              // Rearrange the selected area, or the whole program
+              Action::RotateClockwise => {
+                self.transform(Transform::RotateClockwise);
+                break;
+              },
+              Action::RotateCounterClockwise => {
+                self.transform(Transform::RotateCounterClockwise);
+                break;
+              },
+              Action::MirrorHorizontally => {
+                self.transform(Transform::MirrorHorizontally);
+                break;
+              },
+              Action::MirrorVertically => {
+                self.transform(Transform::MirrorVertically);
+                break;
+              },
+              Action::ShiftUp => {
+                self.shift(-1, 0);
+                break;
+              },
+              Action::ShiftDown => {
+                self.shift(1, 0);
+                break;
+              },
+              Action::ShiftLeft => {
+                self.shift(0, -1);
+                break;
+              },
+              Action::ShiftRight => {
+                self.shift(0, 1);
+                break;
+              },
+
+              // Movement
+              Action::MoveUp => {
+                self.cursor_row = (self.cursor_row - 1).rem_euclid(self.solution.rows() as isize);
+                return Ok(Some(self));
+              },
+              Action::MoveDown => {
+                self.cursor_row = (self.cursor_row + 1).rem_euclid(self.solution.rows() as isize);
+                return Ok(Some(self));
+              },
+              Action::MoveLeft => {
+                self.cursor_col = (self.cursor_col - 1).rem_euclid(self.solution.cols() as isize);
+                return Ok(Some(self));
+              },
+              Action::MoveRight => {
+                self.cursor_col = (self.cursor_col + 1).rem_euclid(self.solution.cols() as isize);
+                return Ok(Some(self));
+              },
+
+              // Select test case
+              Action::NextTestCase => {
+                self.test_case_index = (self.test_case_index + 1).rem_euclid(self.test_cases.len() as isize);
+                return Ok(Some(self));
+              },
+              Action::PreviousTestCase => {
+                self.test_case_index = (self.test_case_index - 1).rem_euclid(self.test_cases.len() as isize);
+                return Ok(Some(self));
+              },
+
+              // Breakpoint
+              Action::Breakpoint => {
+                self
+                  .solution
+                  .toggle_breakpoint(self.cursor_row as usize, self.cursor_col as usize);
+                break;
+              },
+
+              // Deletion
+              Action::Clear => {
+                self.set_cell(Command::Empty);
+                break;
+              },
+
+              // Starting location
+              Action::SetStart => {
+                self
+                  .solution
+                  .set_start(self.cursor_row as usize, self.cursor_col as usize);
+                break;
+              },
+
+              // Annotations and notes
+              Action::Annotate => {
+                self.start_prompt(PromptTarget::Annotation);
+                return Ok(Some(self));
+              },
+              Action::EditNote => {
+                self.start_prompt(PromptTarget::Note);
+                return Ok(Some(self));
+              },
+              Action::ToggleAnnotations => {
+                self.highlight_annotations = !self.highlight_annotations;
+                return Ok(Some(self));
+              },
+
+              // Only the parallel levels have a second processor
+              _ => {},
+            }
           },
-          KeyCode::Char('-') => {
-            self.set_cell(Command::Subtract);
-            break;
-          },
-
-          // Comparisons
-          KeyCode::Char('<') => {
-            self.set_cell(Command::IfLess);
-            break;
-          },
-          KeyCode::Char('=') => {
-            self.set_cell(Command::IfEqual);
-            break;
-          },
-          KeyCode::Char('>') => {
-            self.set_cell(Command::IfGreater);
-            break;
-          },
-
-          // Input and output
-          KeyCode::Char('i') => {
-            self.set_cell(Command::In);
-            break;
-          },
-          KeyCode::Char('?') => {
-            self.set_cell(Command::HasInput);
-            break;
-          },
-          KeyCode::Char('o') => {
-            self.set_cell(Command::Out);
-            break;
-          },
-
-          // Starting location
-          KeyCode::Char('b') => {
-            self
-              .solution
-              .set_start(self.cursor_row as usize, self.cursor_col as usize);
-            break;
-          },
-
-          _ => {},
         },
         _ => {},
       }
     }
 
-    let level_id = global_state.level(self.level_index).id();
-    <GlobalState as SolutionManager<isa::Standard>>::save_solution(
-      global_state,
-      level_id,
-      self.solution_index,
-      self.solution.clone(),
-    );
+    self.history.record(before, &self.solution);
+    self.save(global_state);
 
     Ok(Some(self))
   }
@@ -1,8 +1,8 @@
-use crate::isa::InstructionSetArchitecture;
+use crate::isa::{CommandPalette, InstructionSetArchitecture, OutputChecker};
+use crate::level::{LevelLimits, LevelType, StaticTestCase, DEFAULT_MAX_CYCLES};
 use editor_state::EditorState;
-use rlua::prelude::*;
-use std::error::Error;
-use std::fs;
+use std::rc::Rc;
+use vm::VirtualMachine;
 
 mod editor_state;
 mod execute_state;
@@ -11,6 +11,7 @@ mod solution;
 mod vm;
 
 pub use solution::Solution;
+pub use vm::Command;
 
 /// Standard level type
 pub struct Standard;
@@ -18,51 +19,51 @@ pub struct Standard;
 impl InstructionSetArchitecture for Standard {
   type Solution = Solution;
   type Puzzle = puzzle::Puzzle;
+  // Inputs and expected outputs
+  type TestCase = (Vec<i16>, Vec<i16>);
+  type Machine = VirtualMachine;
+  type Command = vm::Command;
 
-  ///
-  /// Load and run the Lua code to generate the puzzles
-  ///
-  fn generate_test_cases(
-    folder: &str,
-    lua_file: &str,
-    seed: u32,
-    n: usize,
-  ) -> Result<Vec<Self::Puzzle>, Box<dyn Error>> {
-    // Try to load the Lua code file into memory
-    let lua_code = fs::read_to_string(format!("{folder}/{lua_file}"))?;
+  const LEVEL_TYPE: LevelType = LevelType::Standard;
 
-    // Generate and run the code within the Lua context
-    let test_cases = Lua::new().context::<_, LuaResult<Vec<Self::Puzzle>>>(|ctx| {
-      let globals = ctx.globals();
+  const DEFAULT_LIMITS: LevelLimits = LevelLimits {
+    grid_rows: 10,
+    grid_cols: 10,
+    stack_size: 15,
+    max_inputs: 15,
+    max_outputs: 15,
+    max_cycles: DEFAULT_MAX_CYCLES,
+  };
 
-      // Add the levels folder to the path
-      ctx
-        .load(&format!(r#"package.path = "./{folder}/?.lua;" .. package.path"#))
-        .exec()?;
+  const MAX_LIMITS: LevelLimits = LevelLimits {
+    grid_rows: 15,
+    grid_cols: 14,
+    stack_size: 17,
+    max_inputs: 17,
+    max_outputs: 17,
+    max_cycles: u32::MAX,
+  };
 
-      // Seed the random number generator
-      globals
-        .get::<_, LuaTable>("math")?
-        .get::<_, LuaFunction>("randomseed")?
-        .call::<_, ()>(seed)?;
-
-      // Load the script code
-      //  This should define a global function named "generateTestCase"
-      ctx.load(&lua_code).exec()?;
-
-      // Generate the test cases one-by-one
-      let generate_test_case: LuaFunction = globals.get("generateTestCase")?;
-      let test_cases = (0..n)
-        .map(|_| {
-          let (inputs, outputs): (Vec<i16>, Vec<i16>) = generate_test_case.call(())?;
-          Self::Puzzle::new(inputs, outputs).map_err(LuaError::RuntimeError)
-        })
-        .collect::<Result<_, _>>()?;
+  fn new_puzzle((inputs, outputs): Self::TestCase, limits: &LevelLimits) -> Result<Self::Puzzle, String> {
+    Self::Puzzle::new(inputs, outputs, limits)
+  }
 
-      Ok(test_cases)
-    })?;
+  fn static_test_case(test_case: &StaticTestCase) -> Result<Self::TestCase, String> {
+    match test_case {
+      StaticTestCase {
+        inputs: Some(inputs),
+        outputs: Some(outputs),
+        p0_inputs: None,
+        p0_outputs: None,
+        p1_inputs: None,
+        p1_outputs: None,
+      } => Ok((inputs.clone(), outputs.clone())),
+      _ => Err("Standard levels need \"inputs\" and \"outputs\" for each test case".into()),
+    }
+  }
 
-    Ok(test_cases)
+  fn set_checker(puzzle: &mut Self::Puzzle, checker: Rc<OutputChecker>) {
+    puzzle.set_checker(checker);
   }
 
   fn open_editor(
@@ -71,7 +72,28 @@ impl InstructionSetArchitecture for Standard {
     solution: Self::Solution,
     test_cases: Vec<Self::Puzzle>,
     test_case_index: usize,
+    palette: CommandPalette<Self::Command>,
   ) -> impl crate::state::State {
-    EditorState::new(level_index, solution_index, solution, test_cases, test_case_index)
+    EditorState::new(
+      level_index,
+      solution_index,
+      solution,
+      test_cases,
+      test_case_index,
+      palette,
+    )
+  }
+
+  fn commands_used(solution: &Self::Solution) -> Vec<Self::Command> {
+    solution.commands().collect()
+  }
+
+  fn new_machine(
+    solution: &Self::Solution,
+    test_case: usize,
+    puzzle: &Self::Puzzle,
+    limits: &LevelLimits,
+  ) -> Self::Machine {
+    VirtualMachine::new(solution.clone(), test_case, puzzle, limits)
   }
 }
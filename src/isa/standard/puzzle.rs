@@ -3,32 +3,36 @@ use crossterm::{cursor, QueueableCommand};
 use rand::Rng;
 use std::collections::VecDeque;
 use std::io::{self, Write};
+use std::rc::Rc;
 
 use super::vm::{VAL_CHAR_WIDTH, VAL_MAX, VAL_MIN};
+use crate::isa::OutputChecker;
+use crate::level::LevelLimits;
 use crate::printable::Printable;
 
-pub const MAX_PUZZLE_VALUES: usize = 15;
-
 pub type TestCaseSet = Vec<Puzzle>;
 
 #[derive(Debug, Clone)]
 pub struct Puzzle {
   inputs: PuzzleIO,
   outputs: PuzzleIO,
+  checker: Option<Rc<OutputChecker>>,
 }
 
 impl Puzzle {
   // Performs validation and returns a printable error string
-  pub fn new(inputs: Vec<i16>, outputs: Vec<i16>) -> Result<Self, String> {
-    if inputs.len() > MAX_PUZZLE_VALUES {
+  pub fn new(inputs: Vec<i16>, outputs: Vec<i16>, limits: &LevelLimits) -> Result<Self, String> {
+    if inputs.len() > limits.max_inputs {
       return Err(format!(
-        "Too many input values, maximum of {MAX_PUZZLE_VALUES} allowed, {} given",
+        "Too many input values, maximum of {} allowed, {} given",
+        limits.max_inputs,
         inputs.len()
       ));
     }
-    if outputs.len() > MAX_PUZZLE_VALUES {
+    if outputs.len() > limits.max_outputs {
       return Err(format!(
-        "Too many output values, maximum of {MAX_PUZZLE_VALUES} allowed, {} given",
+        "Too many output values, maximum of {} allowed, {} given",
+        limits.max_outputs,
         outputs.len()
       ));
     }
@@ -45,11 +49,26 @@ impl Puzzle {
     }
 
     Ok(Self {
-      inputs: PuzzleIO(inputs.into()),
-      outputs: PuzzleIO(outputs.into()),
+      inputs: PuzzleIO {
+        values: inputs.into(),
+        capacity: limits.max_inputs,
+      },
+      outputs: PuzzleIO {
+        values: outputs.into(),
+        capacity: limits.max_outputs,
+      },
+      checker: None,
     })
   }
 
+  pub fn set_checker(&mut self, checker: Rc<OutputChecker>) {
+    self.checker = Some(checker);
+  }
+
+  pub fn get_checker(&self) -> Option<&Rc<OutputChecker>> {
+    self.checker.as_ref()
+  }
+
   pub fn get_inputs(&self) -> &PuzzleIO {
     &self.inputs
   }
@@ -79,35 +98,54 @@ impl Printable for Puzzle {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct PuzzleIO(VecDeque<i16>);
+pub struct PuzzleIO {
+  values: VecDeque<i16>,
+  capacity: usize,
+}
 
 #[allow(unused)]
 impl PuzzleIO {
-  pub fn new() -> Self {
-    Self(VecDeque::new())
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      values: VecDeque::new(),
+      capacity,
+    }
   }
 
-  pub fn new_random() -> Self {
+  pub fn new_random(capacity: usize) -> Self {
     let mut rng = rand::thread_rng();
-    Self((0..rng.gen_range(0..=10)).map(|_| rng.gen_range(-999..=999)).collect())
+    Self {
+      values: (0..rng.gen_range(0..=capacity))
+        .map(|_| rng.gen_range(-999..=999))
+        .collect(),
+      capacity,
+    }
   }
 
   pub fn len(&self) -> usize {
-    self.0.len()
+    self.values.len()
+  }
+
+  pub fn to_vec(&self) -> Vec<i16> {
+    self.values.iter().copied().collect()
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.capacity
   }
 
   pub fn can_read(&self) -> bool {
-    !self.0.is_empty()
+    !self.values.is_empty()
   }
 
   pub fn read(&mut self) -> Option<i16> {
-    self.0.pop_front()
+    self.values.pop_front()
   }
 
   // Returns false if the stack overflows
   pub fn write(&mut self, val: i16) -> bool {
-    if self.0.len() < MAX_PUZZLE_VALUES {
-      self.0.push_back(val);
+    if self.values.len() < self.capacity {
+      self.values.push_back(val);
       true
     } else {
       false
@@ -125,9 +163,9 @@ impl PuzzleIO {
       .queue(cursor::MoveLeft(VAL_CHAR_WIDTH as u16 + 2))?
       .queue(cursor::MoveDown(1))?;
 
-    for (i, value) in self.0.iter().enumerate() {
+    for (i, value) in self.values.iter().enumerate() {
       let text = format!("{:-4}", value);
-      match expected_outputs.0.get(i) {
+      match expected_outputs.values.get(i) {
         Some(x) if x == value => write!(stdout, "│{}│", text),
         Some(_) | None => write!(stdout, "│{}│", text.red()),
       }?;
@@ -137,7 +175,7 @@ impl PuzzleIO {
         .queue(cursor::MoveDown(1))?;
     }
 
-    for _ in self.0.len()..MAX_PUZZLE_VALUES {
+    for _ in self.values.len()..self.capacity {
       write!(stdout, "│    │")?;
       stdout
         .queue(cursor::MoveLeft(VAL_CHAR_WIDTH as u16 + 2))?
@@ -2,23 +2,27 @@ use crossterm::style::Stylize;
 use crossterm::{cursor, QueueableCommand};
 use serde::{Deserialize, Serialize};
 use std::io::{self, Write};
+use uuid::Uuid;
 
 use super::vm::Command;
-use crate::grid::Grid;
-use crate::isa;
+use crate::grid::{Direction, Grid, Rect, Start, Transform};
+use crate::isa::{self, CommandPalette};
+use crate::level::LevelLimits;
 use crate::printable::Printable;
+use crate::solution_file::ProgramText;
 
-// Standard levels use a 10x10 grid
-const GRID_SIZE: usize = 10;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Solution {
   #[serde(default = "default_name")]
   name: String,
+  #[serde(default = "Uuid::new_v4")]
+  id: Uuid,
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  note: String,
   grid: Grid<Command>,
-  start_row: usize,
-  start_col: usize,
+  #[serde(flatten)]
+  start: Start,
 }
 
 fn default_name() -> String {
@@ -26,10 +30,13 @@ fn default_name() -> String {
 }
 
 impl isa::Solution for Solution {
-  fn new(name: impl Into<String>) -> Self {
+  fn new(name: impl Into<String>, limits: &LevelLimits) -> Self {
     Self {
       name: name.into(),
-      ..Default::default()
+      id: Uuid::new_v4(),
+      note: String::new(),
+      grid: Grid::new(limits.grid_rows, limits.grid_cols),
+      start: Start::default(),
     }
   }
 
@@ -41,9 +48,44 @@ impl isa::Solution for Solution {
     self.name = new_name.into();
   }
 
+  fn id(&self) -> Uuid {
+    self.id
+  }
+
+  fn set_id(&mut self, id: Uuid) {
+    self.id = id;
+  }
+
+  fn note(&self) -> &str {
+    &self.note
+  }
+
+  fn set_note(&mut self, note: impl Into<String>) {
+    self.note = note.into();
+  }
+
   fn symbols_used(&self) -> usize {
     self.grid.count_symbols()
   }
+
+  fn to_programs(&self) -> Vec<ProgramText> {
+    vec![ProgramText::from_grid(&self.grid, self.start, Command::get_char)]
+  }
+
+  fn from_programs(name: impl Into<String>, programs: &[ProgramText], limits: &LevelLimits) -> Result<Self, String> {
+    let [program] = programs else {
+      return Err("Standard solutions must have exactly one program".into());
+    };
+
+    let (grid, start) = program.to_grid(limits.grid_rows, limits.grid_cols, Command::from_char)?;
+    Ok(Self {
+      name: name.into(),
+      id: Uuid::new_v4(),
+      note: String::new(),
+      grid,
+      start,
+    })
+  }
 }
 
 #[allow(unused)]
@@ -52,6 +94,14 @@ impl Solution {
     self.grid
   }
 
+  pub fn grid(&self) -> &Grid<Command> {
+    &self.grid
+  }
+
+  pub fn grid_mut(&mut self) -> &mut Grid<Command> {
+    &mut self.grid
+  }
+
   pub fn rows(&self) -> usize {
     self.grid.rows()
   }
@@ -60,39 +110,61 @@ impl Solution {
     self.grid.cols()
   }
 
+  pub fn commands(&self) -> impl Iterator<Item = Command> + '_ {
+    self.grid.values().copied()
+  }
+
   pub fn set_grid_value(&mut self, row: usize, col: usize, value: Command) {
     self.grid.set_value(row, col, value);
   }
 
   pub fn start_row(&self) -> usize {
-    self.start_row
+    self.start.row
   }
 
   pub fn start_col(&self) -> usize {
-    self.start_col
+    self.start.col
+  }
+
+  pub fn start_direction(&self) -> Direction {
+    self.start.direction
   }
 
+  /// Move the start to another cell, it keeps going the same way
   pub fn set_start(&mut self, start_row: usize, start_col: usize) {
-    debug_assert!(self.start_row < self.grid.rows());
-    debug_assert!(self.start_col < self.grid.cols());
+    debug_assert!(start_row < self.grid.rows());
+    debug_assert!(start_col < self.grid.cols());
 
-    self.start_row = start_row;
-    self.start_col = start_col;
+    self.start.row = start_row;
+    self.start.col = start_col;
   }
 
   pub fn toggle_breakpoint(&mut self, row: usize, col: usize) {
     self.grid.toggle_breakpoint(row, col);
   }
-}
 
-impl Default for Solution {
-  fn default() -> Self {
-    Self {
-      name: "New Solution".into(),
-      grid: Grid::new(GRID_SIZE, GRID_SIZE),
-      start_row: 0,
-      start_col: 0,
-    }
+  pub fn set_highlight_annotations(&mut self, highlight: bool) {
+    self.grid.set_highlight_annotations(highlight);
+  }
+
+  /// Rotate or mirror the commands in the area, or the whole program if there isn't one, see Grid::transform_program
+  ///   Arrows and mirrors turn into other commands, so it is refused unless the palette allows those too
+  pub fn transform(
+    &mut self,
+    area: Option<Rect>,
+    transform: Transform,
+    palette: &CommandPalette<Command>,
+  ) -> Option<Rect> {
+    self
+      .grid
+      .transform_program(&mut self.start, area, transform, |command| {
+        Some(command.transformed(transform)).filter(|command| palette.allows(*command))
+      })
+  }
+
+  /// Shift the commands in the area, or the whole grid if there isn't one, see Grid::shift_program
+  pub fn shift(&mut self, area: Option<Rect>, row_offset: isize, col_offset: isize) {
+    self.grid.shift_program(&mut self.start, area, row_offset, col_offset);
   }
 }
 
@@ -104,20 +176,66 @@ impl Printable for Solution {
 
     stdout
       .queue(cursor::RestorePosition)?
-      .queue(cursor::MoveRight(self.start_col as u16 + 1))?
-      .queue(cursor::MoveDown(self.start_row as u16 + 1))?;
-
-    write!(
-      stdout,
-      "{}",
-      self
-        .grid
-        .get_value(self.start_row, self.start_col)
-        .get_char()
-        .green()
-        .reverse()
-    )?;
+      .queue(cursor::MoveRight(self.start.col as u16 + 1))?
+      .queue(cursor::MoveDown(self.start.row as u16 + 1))?;
+
+    // An empty start shows which way it goes, unless it's the usual way
+    let command = self.grid.get_value(self.start.row, self.start.col);
+    let symbol = match command {
+      Command::Empty if !self.start.direction.is_right() => self.start.direction.get_arrow(),
+      _ => command.get_char(),
+    };
+    write!(stdout, "{}", symbol.green().reverse())?;
 
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::isa::{InstructionSetArchitecture, Solution as _};
+
+  #[test]
+  fn commands_turn_with_a_rotated_area() {
+    let mut solution = Solution::new("Rotate", &isa::Standard::DEFAULT_LIMITS);
+    solution.set_grid_value(0, 0, Command::In);
+    solution.set_grid_value(0, 1, Command::Up);
+    solution.set_grid_value(0, 2, Command::ForwardSlash);
+    solution.set_grid_value(1, 1, Command::Right);
+
+    // A 2 by 3 area holding the start turns into a 3 by 2 one
+    let target = solution.transform(None, Transform::RotateClockwise, &CommandPalette::default());
+    assert_eq!(target, Some(Rect::from_corners((0, 0), (2, 1))));
+    assert_eq!(
+      solution.grid().copy_rect(&target.unwrap()),
+      vec![
+        vec![Command::Empty, Command::In],
+        vec![Command::Down, Command::Right],
+        vec![Command::Empty, Command::BackSlash],
+      ]
+    );
+    assert_eq!((solution.start_row(), solution.start_col()), (0, 1));
+    assert_eq!(solution.start_direction(), Direction::Down);
+
+    solution.transform(None, Transform::MirrorHorizontally, &CommandPalette::default());
+    assert_eq!(solution.grid().get_value(1, 0), &Command::Left);
+    assert_eq!(solution.grid().get_value(2, 0), &Command::ForwardSlash);
+    assert_eq!(solution.start_direction(), Direction::Down);
+  }
+
+  #[test]
+  fn transform_needs_the_turned_commands_in_the_palette() {
+    let mut solution = Solution::new("Rotate", &isa::Standard::DEFAULT_LIMITS);
+    solution.set_grid_value(0, 0, Command::In);
+    solution.set_grid_value(0, 1, Command::Right);
+    let unchanged = solution.clone();
+
+    let palette = CommandPalette::new(None, &["down".to_string()]).unwrap();
+    assert_eq!(solution.transform(None, Transform::RotateClockwise, &palette), None);
+    assert_eq!(solution, unchanged);
+    assert!(solution
+      .transform(None, Transform::MirrorHorizontally, &palette)
+      .is_some());
+  }
+}
@@ -1,18 +1,25 @@
 use crossterm::style::{self, Color, Stylize};
 use crossterm::{cursor, QueueableCommand};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::VecDeque;
+use std::fmt;
 use std::io::{self, Write};
+use std::rc::Rc;
 
 use super::puzzle::{Puzzle, PuzzleIO};
 use super::solution::Solution;
-use crate::grid::Grid;
+use crate::grid::{Direction, Grid, Transform};
+use crate::isa::{CheckResult, LoopDetector, Machine, MachineError, OutputChecker};
+use crate::level::LevelLimits;
 use crate::printable::Printable;
 
 pub const VAL_MIN: i16 = -999;
 pub const VAL_MAX: i16 = 999;
 pub const VAL_CHAR_WIDTH: usize = 4; // 3 numbers and negative sign
-const MAX_STACK_ENTRIES: usize = 15;
+
+// Everything that decides what the machine does next, other than the grid
+type LoopState = (i16, i16, Direction, bool, bool, Stack);
 
 #[derive(Debug, Clone)]
 pub struct VirtualMachine {
@@ -30,29 +37,23 @@ pub struct VirtualMachine {
   outputs: PuzzleIO,
   test_case: usize,
   expected_outputs: PuzzleIO,
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Direction {
-  Up,
-  Down,
-  Left,
-  Right,
-}
+  // Judges the outputs instead of the expected outputs if the level has one
+  checker: Option<Rc<OutputChecker>>,
+  test_case_inputs: PuzzleIO,
+  checked_outputs: Option<usize>,
 
-impl Direction {
-  pub fn get_arrow(&self) -> char {
-    match self {
-      Self::Up => '▲',
-      Self::Down => '▼',
-      Self::Left => '◄',
-      Self::Right => '►',
-    }
-  }
+  max_cycles: u32,
+  loop_detector: LoopDetector<(usize, usize), LoopState>,
+
+  // Scoring details, which aren't part of the state for loop detection
+  instructions_executed: u32,
+  peak_stack_depth: usize,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Command {
+  #[default]
   #[serde(rename = " ", alias = "empty")]
   Empty,
   #[serde(rename = "↑", alias = "up")]
@@ -153,11 +154,106 @@ impl Command {
       Self::Out => 'Θ',
     }
   }
-}
 
-impl Default for Command {
-  fn default() -> Self {
-    Self::Empty
+  pub fn from_char(c: char) -> Option<Self> {
+    match c {
+      ' ' => Some(Self::Empty),
+      '↑' => Some(Self::Up),
+      '↓' => Some(Self::Down),
+      '←' => Some(Self::Left),
+      '→' => Some(Self::Right),
+      '/' => Some(Self::ForwardSlash),
+      '\\' => Some(Self::BackSlash),
+      '0' => Some(Self::Zero),
+      '1' => Some(Self::One),
+      '2' => Some(Self::Two),
+      '3' => Some(Self::Three),
+      '4' => Some(Self::Four),
+      '5' => Some(Self::Five),
+      '6' => Some(Self::Six),
+      '7' => Some(Self::Seven),
+      '8' => Some(Self::Eight),
+      '9' => Some(Self::Nine),
+      '☼' => Some(Self::Pop),
+      '©' => Some(Self::Copy),
+      '∫' => Some(Self::SwapTop2),
+      'u' => Some(Self::RotateDown),
+      '∩' => Some(Self::RotateUp),
+      '+' => Some(Self::Add),
+      '-' => Some(Self::Subtract),
+      '<' => Some(Self::IfLess),
+      '=' => Some(Self::IfEqual),
+      '>' => Some(Self::IfGreater),
+      '»' => Some(Self::Skip),
+      'Ї' => Some(Self::In),
+      '?' => Some(Self::HasInput),
+      'Θ' => Some(Self::Out),
+      _ => None,
+    }
+  }
+
+  /// The command typed with this key by default, the keybindings file can change it
+  pub fn from_key(c: char) -> Option<Self> {
+    match c {
+      'w' => Some(Self::Up),
+      's' => Some(Self::Down),
+      'a' => Some(Self::Left),
+      'd' => Some(Self::Right),
+      '/' => Some(Self::ForwardSlash),
+      '\\' => Some(Self::BackSlash),
+      '*' => Some(Self::Skip),
+      '0' => Some(Self::Zero),
+      '1' => Some(Self::One),
+      '2' => Some(Self::Two),
+      '3' => Some(Self::Three),
+      '4' => Some(Self::Four),
+      '5' => Some(Self::Five),
+      '6' => Some(Self::Six),
+      '7' => Some(Self::Seven),
+      '8' => Some(Self::Eight),
+      '9' => Some(Self::Nine),
+      'p' => Some(Self::Pop),
+      'c' => Some(Self::Copy),
+      '~' => Some(Self::SwapTop2),
+      'v' => Some(Self::RotateDown),
+      '^' => Some(Self::RotateUp),
+      '+' => Some(Self::Add),
+      '-' => Some(Self::Subtract),
+      '<' => Some(Self::IfLess),
+      '=' => Some(Self::IfEqual),
+      '>' => Some(Self::IfGreater),
+      'i' => Some(Self::In),
+      '?' => Some(Self::HasInput),
+      'o' => Some(Self::Out),
+      _ => None,
+    }
+  }
+
+  /// The command that does the same thing once the grid around it is rotated or mirrored
+  pub fn transformed(self, transform: Transform) -> Self {
+    match (transform, self) {
+      // Every transform flips which way the mirrors face
+      (_, Self::ForwardSlash) => Self::BackSlash,
+      (_, Self::BackSlash) => Self::ForwardSlash,
+
+      (Transform::RotateClockwise, Self::Up) => Self::Right,
+      (Transform::RotateClockwise, Self::Right) => Self::Down,
+      (Transform::RotateClockwise, Self::Down) => Self::Left,
+      (Transform::RotateClockwise, Self::Left) => Self::Up,
+
+      (Transform::RotateCounterClockwise, Self::Up) => Self::Left,
+      (Transform::RotateCounterClockwise, Self::Left) => Self::Down,
+      (Transform::RotateCounterClockwise, Self::Down) => Self::Right,
+      (Transform::RotateCounterClockwise, Self::Right) => Self::Up,
+
+      (Transform::MirrorHorizontally, Self::Left) => Self::Right,
+      (Transform::MirrorHorizontally, Self::Right) => Self::Left,
+
+      (Transform::MirrorVertically, Self::Up) => Self::Down,
+      (Transform::MirrorVertically, Self::Down) => Self::Up,
+
+      (_, command) => command,
+    }
   }
 }
 
@@ -167,27 +263,38 @@ pub enum VMError {
   StackUnderflow,
   NoInputs,
   TooManyOutputs,
+  InfiniteLoop,
+  CycleLimitExceeded,
+  WrongOutputs(String),
 }
 
 #[allow(unused)]
 impl VirtualMachine {
-  pub fn new(solution: Solution, test_case: usize, puzzle: &Puzzle) -> Self {
+  pub fn new(solution: Solution, test_case: usize, puzzle: &Puzzle, limits: &LevelLimits) -> Self {
     let row = solution.start_row() as i16;
     let col = solution.start_col() as i16;
+    let direction = solution.start_direction();
 
     Self {
       grid: solution.into_grid(),
       cycle: 0,
       row,
       col,
-      direction: Direction::Right, // Always starts facing right
+      direction,
       skip_next_instruction: false,
       last_was_number: false,
-      stack: Stack::new(),
+      stack: Stack::new(limits.stack_size),
       inputs: puzzle.get_inputs().clone(),
-      outputs: PuzzleIO::new(),
+      outputs: PuzzleIO::new(limits.max_outputs),
       test_case,
       expected_outputs: puzzle.get_outputs().clone(),
+      checker: puzzle.get_checker().cloned(),
+      test_case_inputs: puzzle.get_inputs().clone(),
+      checked_outputs: None,
+      max_cycles: limits.max_cycles,
+      loop_detector: LoopDetector::new(),
+      instructions_executed: 0,
+      peak_stack_depth: 0,
     }
   }
 
@@ -199,25 +306,49 @@ impl VirtualMachine {
     self.grid.cols()
   }
 
-  pub fn count_symbols(&self) -> usize {
-    self.grid.count_symbols()
-  }
+  // Returns Ok(true) once every input is read and the outputs are correct
+  fn is_solved(&mut self) -> Result<bool, VMError> {
+    if self.inputs.len() > 0 {
+      return Ok(false);
+    }
 
-  pub fn get_cycle(&self) -> u32 {
-    self.cycle
+    let Some(ref checker) = self.checker else {
+      return Ok(self.outputs == self.expected_outputs);
+    };
+
+    // Only run the checker when there are new outputs
+    if self.checked_outputs == Some(self.outputs.len()) {
+      return Ok(false);
+    }
+    self.checked_outputs = Some(self.outputs.len());
+
+    match checker.check((self.test_case_inputs.to_vec(), self.outputs.to_vec())) {
+      CheckResult::Correct => Ok(true),
+      CheckResult::Incomplete => Ok(false),
+      CheckResult::Wrong(message) => Err(VMError::WrongOutputs(message)),
+    }
   }
 
   // Returns Ok(true) when the puzzle is solved
-  pub fn step(&mut self) -> Result<bool, VMError> {
-    if self.inputs.len() == 0 && self.outputs == self.expected_outputs {
+  fn run_cycle(&mut self) -> Result<bool, VMError> {
+    if self.is_solved()? {
       return Ok(true);
     }
 
+    if self.cycle >= self.max_cycles {
+      return Err(VMError::CycleLimitExceeded);
+    }
+
     self.cycle = self.cycle.wrapping_add(1);
 
     let mut is_number = false;
     if !self.skip_next_instruction {
-      match self.grid.get_value(self.row as usize, self.col as usize) {
+      let command = *self.grid.get_value(self.row as usize, self.col as usize);
+      if command != Command::Empty {
+        self.instructions_executed += 1;
+      }
+
+      match command {
         Command::Empty => {},
         Command::Up => {
           self.direction = Direction::Up;
@@ -312,15 +443,15 @@ impl VirtualMachine {
         },
         Command::IfLess => {
           let val = self.peek()?;
-          self.skip_next_instruction = !(val < 0);
+          self.skip_next_instruction = val >= 0;
         },
         Command::IfEqual => {
           let val = self.peek()?;
-          self.skip_next_instruction = !(val == 0);
+          self.skip_next_instruction = val != 0;
         },
         Command::IfGreater => {
           let val = self.peek()?;
-          self.skip_next_instruction = !(val > 0);
+          self.skip_next_instruction = val <= 0;
         },
         Command::Skip => {
           self.skip_next_instruction = true;
@@ -342,6 +473,7 @@ impl VirtualMachine {
     } else {
       self.skip_next_instruction = false;
     }
+    self.peak_stack_depth = self.peak_stack_depth.max(self.stack.len());
 
     // Now perform movement
     match self.direction {
@@ -361,6 +493,19 @@ impl VirtualMachine {
 
     self.last_was_number = is_number;
 
+    let io_state = (self.inputs.len(), self.outputs.len());
+    let state = (
+      self.row,
+      self.col,
+      self.direction,
+      self.skip_next_instruction,
+      self.last_was_number,
+      self.stack.clone(),
+    );
+    if self.loop_detector.is_repeated(io_state, state) {
+      return Err(VMError::InfiniteLoop);
+    }
+
     Ok(false)
   }
 
@@ -417,10 +562,67 @@ impl VirtualMachine {
   pub fn toggle_breakpoint(&mut self, row: usize, col: usize) {
     self.grid.toggle_breakpoint(row, col)
   }
+}
+
+impl Machine for VirtualMachine {
+  type Error = VMError;
+
+  fn step(&mut self) -> Result<bool, (VMError, usize)> {
+    // Standard levels only have a single processor
+    self.run_cycle().map_err(|e| (e, 0))
+  }
+
+  fn test_case(&self) -> usize {
+    self.test_case
+  }
+
+  fn get_cycle(&self) -> u32 {
+    self.cycle
+  }
+
+  fn count_symbols(&self) -> usize {
+    self.grid.count_symbols()
+  }
+
+  fn count_area(&self) -> usize {
+    self.grid.used_area()
+  }
+
+  fn instructions_executed(&self) -> u32 {
+    self.instructions_executed
+  }
 
-  pub fn is_at_breakpoint(&self) -> bool {
+  fn peak_stack_depth(&self) -> usize {
+    self.peak_stack_depth
+  }
+
+  fn is_at_breakpoint(&self) -> bool {
     self.grid.has_breakpoint(self.row as usize, self.col as usize) && !self.skip_next_instruction
   }
+
+  fn snapshot(&self) -> Self {
+    Self {
+      grid: self.grid.clone(),
+      cycle: self.cycle,
+      row: self.row,
+      col: self.col,
+      direction: self.direction,
+      skip_next_instruction: self.skip_next_instruction,
+      last_was_number: self.last_was_number,
+      stack: self.stack.clone(),
+      inputs: self.inputs.clone(),
+      outputs: self.outputs.clone(),
+      test_case: self.test_case,
+      expected_outputs: self.expected_outputs.clone(),
+      checker: self.checker.clone(),
+      test_case_inputs: self.test_case_inputs.clone(),
+      checked_outputs: self.checked_outputs,
+      max_cycles: self.max_cycles,
+      loop_detector: LoopDetector::new(),
+      instructions_executed: self.instructions_executed,
+      peak_stack_depth: self.peak_stack_depth,
+    }
+  }
 }
 
 impl Printable for VirtualMachine {
@@ -458,7 +660,12 @@ impl Printable for VirtualMachine {
       .queue(cursor::RestorePosition)?
       .queue(cursor::MoveDown(2))?
       .queue(cursor::SavePosition)?;
-    write!(stdout, "Stack    Input  Output Expected")?;
+    // A checker accepts other answers, so only show the expected outputs as an example
+    if self.checker.is_some() {
+      write!(stdout, "Stack    Input  Output Example")?;
+    } else {
+      write!(stdout, "Stack    Input  Output Expected")?;
+    }
 
     stdout
       .queue(cursor::RestorePosition)?
@@ -468,7 +675,11 @@ impl Printable for VirtualMachine {
     stdout.queue(cursor::RestorePosition)?.queue(cursor::MoveRight(9))?;
     self.inputs.print()?;
     stdout.queue(cursor::RestorePosition)?.queue(cursor::MoveRight(16))?;
-    self.outputs.print_with_expected_outputs(&self.expected_outputs)?;
+    if self.checker.is_some() {
+      self.outputs.print()?;
+    } else {
+      self.outputs.print_with_expected_outputs(&self.expected_outputs)?;
+    }
     stdout.queue(cursor::RestorePosition)?.queue(cursor::MoveRight(23))?;
     self.expected_outputs.print()?;
 
@@ -483,16 +694,24 @@ impl Printable for Command {
   }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl fmt::Display for Command {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.get_char())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Stack {
   values: VecDeque<i16>,
+  capacity: usize,
 }
 
 #[allow(unused)]
 impl Stack {
-  pub fn new() -> Self {
+  pub fn new(capacity: usize) -> Self {
     Self {
       values: VecDeque::new(),
+      capacity,
     }
   }
 
@@ -500,13 +719,17 @@ impl Stack {
     self.values.len()
   }
 
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
   pub fn is_empty(&self) -> bool {
     self.values.len() == 0
   }
 
   // Returns false if the stack overflows
   pub fn push(&mut self, val: i16) -> bool {
-    if self.values.len() < MAX_STACK_ENTRIES {
+    if self.values.len() < self.capacity {
       self.values.push_back(val.clamp(VAL_MIN, VAL_MAX));
       true
     } else {
@@ -552,7 +775,7 @@ impl Printable for Stack {
       .queue(cursor::MoveLeft(VAL_CHAR_WIDTH as u16 + 2))?
       .queue(cursor::MoveDown(1))?;
 
-    for _ in self.values.len()..MAX_STACK_ENTRIES {
+    for _ in self.values.len()..self.capacity {
       write!(stdout, "│    │")?;
       stdout
         .queue(cursor::MoveLeft(VAL_CHAR_WIDTH as u16 + 2))?
@@ -572,14 +795,17 @@ impl Printable for Stack {
   }
 }
 
-impl VMError {
-  pub fn get_msg(&self) -> &'static str {
+impl MachineError for VMError {
+  fn get_msg(&self) -> Cow<'static, str> {
     match self {
-      Self::NumericOverflow => "Numeric overflow",
-      Self::StackOverflow => "Stack overflow",
-      Self::StackUnderflow => "Stack underflow",
-      Self::NoInputs => "No inputs left",
-      Self::TooManyOutputs => "Too many outputs",
+      Self::NumericOverflow => "Numeric overflow".into(),
+      Self::StackOverflow => "Stack overflow".into(),
+      Self::StackUnderflow => "Stack underflow".into(),
+      Self::NoInputs => "No inputs left".into(),
+      Self::TooManyOutputs => "Too many outputs".into(),
+      Self::InfiniteLoop => "Infinite loop detected".into(),
+      Self::CycleLimitExceeded => "Cycle limit exceeded".into(),
+      Self::WrongOutputs(message) => message.clone().into(),
     }
   }
 }
@@ -589,3 +815,41 @@ impl Printable for VMError {
     write!(io::stdout(), "{}", self.get_msg().red())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::isa::{self, InstructionSetArchitecture, Solution as _};
+
+  // Runs until the machine stops, returning the error it stopped with
+  //   An output is expected, so a program that never writes one can't be solved right away
+  fn run(solution: Solution) -> Option<VMError> {
+    let limits = isa::Standard::DEFAULT_LIMITS;
+    let puzzle = Puzzle::new(Vec::new(), vec![1], &limits).unwrap();
+    let mut vm = VirtualMachine::new(solution, 1, &puzzle, &limits);
+    loop {
+      match vm.step() {
+        Ok(true) => return None,
+        Ok(false) => {},
+        Err((e, _)) => return Some(e),
+      }
+    }
+  }
+
+  #[test]
+  fn empty_program_loops() {
+    let solution = Solution::new("Loop", &isa::Standard::DEFAULT_LIMITS);
+    assert!(matches!(run(solution), Some(VMError::InfiniteLoop)));
+  }
+
+  #[test]
+  fn counting_program_doesnt_loop() {
+    // Adds 1 on every trip down the second column, until the value is too big
+    let mut solution = Solution::new("Count", &isa::Standard::DEFAULT_LIMITS);
+    solution.set_grid_value(0, 0, Command::One);
+    solution.set_grid_value(0, 1, Command::Down);
+    solution.set_grid_value(1, 1, Command::One);
+    solution.set_grid_value(2, 1, Command::Add);
+    assert!(matches!(run(solution), Some(VMError::NumericOverflow)));
+  }
+}
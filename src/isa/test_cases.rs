@@ -0,0 +1,418 @@
+use rlua::prelude::*;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::sandbox::{load_level_script, loaded_files, script_error};
+use super::{InstructionSetArchitecture, OutputChecker, VersionedTestCases};
+use crate::level::{Level, LevelType, StaticTestCase};
+
+static CACHE_SUBFOLDER: &str = "test-cases";
+
+///
+/// Test cases generated by a level script, saved so they don't need to be generated again
+///   The values are stored before validation, so changes to the level limits don't need a new cache
+///   Levels with a checker still load the script for it, but don't run the generator
+///   Any change to the script or the modules it requires will generate the test cases again
+///
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedTestCases<T> {
+  #[serde(flatten)]
+  key: CacheKey,
+  file_hashes: BTreeMap<String, String>,
+  has_checker: bool,
+  test_cases: Vec<T>,
+}
+
+#[derive(PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheKey {
+  level_type: LevelType,
+  folder: String,
+  lua_file: String,
+  seed: u32,
+  count: usize,
+}
+
+///
+/// Load the test cases from the cache, or run the Lua code to generate them
+///   Test cases written in the pack file come first, and are never cached
+///   Pass None for the cache folder to always run the Lua code
+///   A failure to read or write the cache is not an error, the test cases are just generated again
+///   Also returns the version of the test cases, which changes whenever the scripts or pack file test cases do
+///
+pub fn generate_test_cases<ISA: InstructionSetArchitecture>(
+  folder: &str,
+  level: &Level,
+  seed: u32,
+  n: usize,
+  cache_folder: Option<&Path>,
+) -> Result<VersionedTestCases<ISA::Puzzle>, Box<dyn Error>> {
+  let lua_file = level.lua_file();
+  let key = CacheKey::new::<ISA>(folder, lua_file, seed, n);
+  let cache_file = cache_folder.map(|cache_folder| key.cache_file(cache_folder));
+  let cached = cache_file
+    .as_deref()
+    .and_then(|cache_file| read_cache::<ISA::TestCase>(cache_file, &key, folder));
+
+  let (test_cases, file_hashes, checker) = match cached {
+    Some(cached) => {
+      let checker = cached_checker(folder, lua_file, seed, &cached)?;
+      (cached.test_cases, cached.file_hashes, checker)
+    },
+    None => {
+      let (lua, test_cases) = run_generator::<ISA>(folder, lua_file, seed, n)?;
+      let files = loaded_files(&lua).map_err(|e| script_error(lua_file, &e))?;
+
+      // The script can also define a "checkOutputs" function to judge the outputs instead
+      let checker = OutputChecker::from_script(lua, lua_file)?;
+
+      let cached = CachedTestCases {
+        key,
+        file_hashes: files
+          .into_iter()
+          .filter_map(|file| hash_file(folder, &file).map(|hash| (file, hash)))
+          .collect(),
+        has_checker: checker.is_some(),
+        test_cases,
+      };
+      if let Some(ref cache_file) = cache_file {
+        let _ = write_cache(cache_file, &cached);
+      }
+      (cached.test_cases, cached.file_hashes, checker)
+    },
+  };
+
+  into_puzzles::<ISA>(level, seed, n, test_cases, &file_hashes, checker)
+}
+
+///
+/// Load the test cases from the cache alone, without running the generator
+///   Returns None if the level has never been cached or the cache is out of date
+///
+pub fn cached_test_cases<ISA: InstructionSetArchitecture>(
+  folder: &str,
+  level: &Level,
+  seed: u32,
+  n: usize,
+  cache_folder: &Path,
+) -> Option<Result<VersionedTestCases<ISA::Puzzle>, String>> {
+  let lua_file = level.lua_file();
+  let key = CacheKey::new::<ISA>(folder, lua_file, seed, n);
+  let cached = read_cache::<ISA::TestCase>(&key.cache_file(cache_folder), &key, folder)?;
+
+  Some(
+    cached_checker(folder, lua_file, seed, &cached)
+      .and_then(|checker| into_puzzles::<ISA>(level, seed, n, cached.test_cases, &cached.file_hashes, checker))
+      .map_err(|e| e.to_string()),
+  )
+}
+
+// The checker is a Lua function, so the script still gets loaded for it, but nothing is generated
+fn cached_checker<T>(
+  folder: &str,
+  lua_file: &str,
+  seed: u32,
+  cached: &CachedTestCases<T>,
+) -> Result<Option<OutputChecker>, Box<dyn Error>> {
+  if !cached.has_checker {
+    return Ok(None);
+  }
+  Ok(OutputChecker::from_script(
+    load_level_script(folder, lua_file, seed)?,
+    lua_file,
+  )?)
+}
+
+// Validate the test cases after the ones written in the pack file, and give them the checker and version
+fn into_puzzles<ISA: InstructionSetArchitecture>(
+  level: &Level,
+  seed: u32,
+  n: usize,
+  test_cases: Vec<ISA::TestCase>,
+  file_hashes: &BTreeMap<String, String>,
+  checker: Option<OutputChecker>,
+) -> Result<VersionedTestCases<ISA::Puzzle>, Box<dyn Error>> {
+  let version = test_case_version(level.lua_file(), seed, n, file_hashes, level.static_test_cases());
+
+  let static_test_cases = level
+    .static_test_cases()
+    .iter()
+    .map(ISA::static_test_case)
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let limits = level.limits();
+  let mut puzzles = static_test_cases
+    .into_iter()
+    .chain(test_cases)
+    .map(|test_case| ISA::new_puzzle(test_case, &limits))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  if let Some(checker) = checker {
+    let checker = Rc::new(checker);
+    for puzzle in puzzles.iter_mut() {
+      ISA::set_checker(puzzle, Rc::clone(&checker));
+    }
+  }
+
+  Ok((puzzles, version))
+}
+
+///
+/// Find the version of the test cases from the cache, without running the Lua code or loading the test cases
+///   The files the script required last time are hashed again, so any change to them gives a new version
+///   Returns None if the level has never been cached, the version is found when the level is opened instead
+///
+pub fn cached_test_case_version<ISA: InstructionSetArchitecture>(
+  folder: &str,
+  level: &Level,
+  seed: u32,
+  n: usize,
+  cache_folder: &Path,
+) -> Option<String> {
+  let lua_file = level.lua_file();
+  let key = CacheKey::new::<ISA>(folder, lua_file, seed, n);
+
+  let reader = BufReader::new(File::open(key.cache_file(cache_folder)).ok()?);
+  let cached: CachedTestCases<IgnoredAny> = serde_json::from_reader(reader).ok()?;
+  if cached.key != key || cached.file_hashes.is_empty() {
+    return None;
+  }
+
+  // A missing file hashes to nothing, which never matches the cached version
+  let file_hashes = cached
+    .file_hashes
+    .into_keys()
+    .map(|file| {
+      let hash = hash_file(folder, &file).unwrap_or_default();
+      (file, hash)
+    })
+    .collect();
+  Some(test_case_version(
+    lua_file,
+    seed,
+    n,
+    &file_hashes,
+    level.static_test_cases(),
+  ))
+}
+
+///
+/// Run the level script to generate test cases, without the cache or any validation
+///   Returns the Lua state too, since it may also define the output checker
+///
+pub fn run_generator<ISA: InstructionSetArchitecture>(
+  folder: &str,
+  lua_file: &str,
+  seed: u32,
+  n: usize,
+) -> Result<(Lua, Vec<ISA::TestCase>), String> {
+  // Load the script into a sandbox
+  //  This should define a global function named "generateTestCase"
+  let lua = load_level_script(folder, lua_file, seed)?;
+
+  // Generate the test cases one-by-one
+  let test_cases = lua
+    .context(|ctx| {
+      let generate_test_case: LuaFunction = ctx.globals().get("generateTestCase")?;
+      (0..n)
+        .map(|_| generate_test_case.call::<_, ISA::TestCase>(()))
+        .collect::<LuaResult<Vec<_>>>()
+    })
+    .map_err(|e| script_error(lua_file, &e))?;
+
+  Ok((lua, test_cases))
+}
+
+///
+/// Identify the test cases from everything used to make them
+///   The folder is left out, so moving the game doesn't change the version
+///
+fn test_case_version(
+  lua_file: &str,
+  seed: u32,
+  n: usize,
+  file_hashes: &BTreeMap<String, String>,
+  static_test_cases: &[StaticTestCase],
+) -> String {
+  let mut text = format!("{lua_file}\n{seed}\n{n}\n");
+  for (file, hash) in file_hashes {
+    text.push_str(&format!("{file}:{hash}\n"));
+  }
+  text.push_str(&serde_json::to_string(static_test_cases).unwrap_or_default());
+
+  format!("{:016x}", stable_hash(text.as_bytes()))
+}
+
+impl CacheKey {
+  fn new<ISA: InstructionSetArchitecture>(folder: &str, lua_file: &str, seed: u32, n: usize) -> Self {
+    Self {
+      level_type: ISA::LEVEL_TYPE,
+      folder: fs::canonicalize(folder).map_or_else(|_| folder.to_string(), |path| path.display().to_string()),
+      lua_file: lua_file.to_string(),
+      seed,
+      count: n,
+    }
+  }
+
+  // Each level gets its own file, which gets replaced when the scripts change
+  fn cache_file(&self, cache_folder: &Path) -> PathBuf {
+    let key = format!("{}\n{}\n{}\n{}", self.folder, self.lua_file, self.seed, self.count);
+    let level_type = match self.level_type {
+      LevelType::Standard => "standard",
+      LevelType::Parallel => "parallel",
+    };
+
+    cache_folder
+      .join(CACHE_SUBFOLDER)
+      .join(format!("{level_type}-{:016x}.json", stable_hash(key.as_bytes())))
+  }
+}
+
+// Returns None if the cache is missing or out of date
+fn read_cache<T: DeserializeOwned>(cache_file: &Path, key: &CacheKey, folder: &str) -> Option<CachedTestCases<T>> {
+  let reader = BufReader::new(File::open(cache_file).ok()?);
+  let cached: CachedTestCases<T> = serde_json::from_reader(reader).ok()?;
+
+  let is_current = cached.key == *key
+    && cached.test_cases.len() == key.count
+    && !cached.file_hashes.is_empty()
+    && cached
+      .file_hashes
+      .iter()
+      .all(|(file, hash)| hash_file(folder, file).as_ref() == Some(hash));
+
+  is_current.then_some(cached)
+}
+
+// Written to a temporary file first, so two copies of the game can't leave a half-written cache
+fn write_cache<T: Serialize>(cache_file: &Path, cached: &CachedTestCases<T>) -> io::Result<()> {
+  if let Some(folder) = cache_file.parent() {
+    fs::create_dir_all(folder)?;
+  }
+
+  let temp_file = cache_file.with_extension("json.tmp");
+  let mut writer = BufWriter::new(File::create(&temp_file)?);
+  serde_json::to_writer(&mut writer, cached)?;
+  writer.flush()?;
+
+  fs::rename(temp_file, cache_file)
+}
+
+fn hash_file(folder: &str, file: &str) -> Option<String> {
+  let contents = fs::read(format!("{folder}/{file}")).ok()?;
+  Some(format!("{:016x}", stable_hash(&contents)))
+}
+
+// FNV-1a, since the standard library hasher can change between Rust versions
+pub(super) fn stable_hash(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+    (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::isa::Standard;
+  use uuid::Uuid;
+
+  // A level folder with a script that requires a module, removed again when dropped
+  struct TempFolder(PathBuf);
+
+  impl TempFolder {
+    fn new() -> Self {
+      let folder = std::env::temp_dir().join(format!("funge-test-{}", Uuid::new_v4()));
+      fs::create_dir_all(&folder).unwrap();
+      fs::write(folder.join("level.lua"), "require(\"helpers\")").unwrap();
+      fs::write(folder.join("helpers.lua"), "return {}").unwrap();
+      Self(folder)
+    }
+
+    fn path(&self) -> &str {
+      self.0.to_str().unwrap()
+    }
+  }
+
+  impl Drop for TempFolder {
+    fn drop(&mut self) {
+      let _ = fs::remove_dir_all(&self.0);
+    }
+  }
+
+  fn write_test_cache(folder: &str, key: CacheKey, test_cases: Vec<i16>) -> PathBuf {
+    let cache_file = key.cache_file(&Path::new(folder).join("cache"));
+    let cached = CachedTestCases {
+      key,
+      file_hashes: ["level.lua", "helpers.lua"]
+        .into_iter()
+        .map(|file| (file.to_string(), hash_file(folder, file).unwrap()))
+        .collect(),
+      has_checker: false,
+      test_cases,
+    };
+    write_cache(&cache_file, &cached).unwrap();
+    cache_file
+  }
+
+  #[test]
+  fn changed_module_invalidates_the_cache() {
+    let temp = TempFolder::new();
+    let folder = temp.path();
+    let key = || CacheKey::new::<Standard>(folder, "level.lua", 1, 2);
+    let cache_file = write_test_cache(folder, key(), vec![3, 4]);
+
+    let cached = read_cache::<i16>(&cache_file, &key(), folder).unwrap();
+    assert_eq!(cached.test_cases, vec![3, 4]);
+
+    fs::write(temp.0.join("helpers.lua"), "return { changed = true }").unwrap();
+    assert!(read_cache::<i16>(&cache_file, &key(), folder).is_none());
+
+    fs::write(temp.0.join("helpers.lua"), "return {}").unwrap();
+    assert!(read_cache::<i16>(&cache_file, &key(), folder).is_some());
+
+    fs::remove_file(temp.0.join("helpers.lua")).unwrap();
+    assert!(read_cache::<i16>(&cache_file, &key(), folder).is_none());
+  }
+
+  #[test]
+  fn different_count_invalidates_the_cache() {
+    let temp = TempFolder::new();
+    let folder = temp.path();
+    let key = |n| CacheKey::new::<Standard>(folder, "level.lua", 1, n);
+
+    let cache_file = write_test_cache(folder, key(2), vec![3, 4]);
+    assert!(read_cache::<i16>(&cache_file, &key(3), folder).is_none());
+    assert!(read_cache::<i16>(&cache_file, &key(2), folder).is_some());
+
+    // A cache that doesn't hold as many test cases as it says isn't used either
+    let cache_file = write_test_cache(folder, key(2), vec![3, 4, 5]);
+    assert!(read_cache::<i16>(&cache_file, &key(2), folder).is_none());
+  }
+
+  #[test]
+  fn stable_hash_doesnt_change() {
+    // FNV-1a test vectors, cache file names depend on these staying the same
+    assert_eq!(stable_hash(b""), 0xcbf29ce484222325);
+    assert_eq!(stable_hash(b"a"), 0xaf63dc4c8601ec8c);
+    assert_eq!(stable_hash(b"foobar"), 0x85944171f73967e8);
+
+    let key = CacheKey {
+      level_type: LevelType::Standard,
+      folder: "/levels".to_string(),
+      lua_file: "level.lua".to_string(),
+      seed: 1,
+      count: 2,
+    };
+    assert_eq!(
+      key.cache_file(Path::new("cache")),
+      Path::new("cache/test-cases/standard-17a707ff609d0980.json")
+    );
+  }
+}
@@ -0,0 +1,755 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::isa::{self, parse_command};
+
+/// Something a key can be bound to, other than placing a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+  MoveUp,
+  MoveDown,
+  MoveLeft,
+  MoveRight,
+
+  Step,
+  Run,
+  PreviousTestCase,
+  NextTestCase,
+  Breakpoint,
+  SecondBreakpoint,
+  Undo,
+  Redo,
+  Clear,
+  SetStart,
+  Annotate,
+  EditNote,
+  ToggleAnnotations,
+
+  Select,
+  Copy,
+  CopyAll,
+  Cut,
+  Paste,
+  MoveAreaUp,
+  MoveAreaDown,
+  MoveAreaLeft,
+  MoveAreaRight,
+  ShiftUp,
+  ShiftDown,
+  ShiftLeft,
+  ShiftRight,
+  RotateClockwise,
+  RotateCounterClockwise,
+  MirrorHorizontally,
+  MirrorVertically,
+
+  StepBack,
+  StepBackToBreakpoint,
+  ScrubBack,
+  ScrubForward,
+  Speed1,
+  Speed2,
+  Speed3,
+  Speed4,
+  Speed5,
+  Speed6,
+}
+
+///
+/// Every action with its name in the keybindings file and its default keys
+///   Keys are written the same way as in the file, see Key::parse
+///
+static ACTIONS: &[(Action, &str, &[&str])] = &[
+  (Action::MoveUp, "moveUp", &["Up", "k"]),
+  (Action::MoveDown, "moveDown", &["Down", "j"]),
+  (Action::MoveLeft, "moveLeft", &["Left", "h"]),
+  (Action::MoveRight, "moveRight", &["Right", "l"]),
+  (Action::Step, "step", &["Tab"]),
+  (Action::Run, "run", &["Space"]),
+  (Action::PreviousTestCase, "previousTestCase", &["["]),
+  (Action::NextTestCase, "nextTestCase", &["]"]),
+  (Action::Breakpoint, "breakpoint", &[","]),
+  (Action::SecondBreakpoint, "secondBreakpoint", &["."]),
+  (Action::Undo, "undo", &["u", "Ctrl-z"]),
+  (Action::Redo, "redo", &["Ctrl-r", "Ctrl-y"]),
+  (Action::Clear, "clear", &["Delete", "Backspace", "x"]),
+  (Action::SetStart, "setStart", &["b"]),
+  (Action::Annotate, "annotate", &[";"]),
+  (Action::EditNote, "editNote", &["N"]),
+  (Action::ToggleAnnotations, "toggleAnnotations", &[":"]),
+  (Action::Select, "select", &["V"]),
+  (Action::Copy, "copy", &["y"]),
+  (Action::CopyAll, "copyAll", &["Y"]),
+  (Action::Cut, "cut", &["X"]),
+  (Action::Paste, "paste", &["P"]),
+  (Action::MoveAreaUp, "moveAreaUp", &["K", "Shift-Up"]),
+  (Action::MoveAreaDown, "moveAreaDown", &["J", "Shift-Down"]),
+  (Action::MoveAreaLeft, "moveAreaLeft", &["H", "Shift-Left"]),
+  (Action::MoveAreaRight, "moveAreaRight", &["L", "Shift-Right"]),
+  (Action::ShiftUp, "shiftUp", &["Alt-k", "Alt-Up"]),
+  (Action::ShiftDown, "shiftDown", &["Alt-j", "Alt-Down"]),
+  (Action::ShiftLeft, "shiftLeft", &["Alt-h", "Alt-Left"]),
+  (Action::ShiftRight, "shiftRight", &["Alt-l", "Alt-Right"]),
+  (Action::RotateClockwise, "rotateClockwise", &[")"]),
+  (Action::RotateCounterClockwise, "rotateCounterClockwise", &["("]),
+  (Action::MirrorHorizontally, "mirrorHorizontally", &["|"]),
+  (Action::MirrorVertically, "mirrorVertically", &["_"]),
+  (Action::StepBack, "stepBack", &["BackTab"]),
+  (Action::StepBackToBreakpoint, "stepBackToBreakpoint", &["Backspace"]),
+  (Action::ScrubBack, "scrubBack", &["["]),
+  (Action::ScrubForward, "scrubForward", &["]"]),
+  (Action::Speed1, "speed1", &["1"]),
+  (Action::Speed2, "speed2", &["2"]),
+  (Action::Speed3, "speed3", &["3"]),
+  (Action::Speed4, "speed4", &["4"]),
+  (Action::Speed5, "speed5", &["5"]),
+  (Action::Speed6, "speed6", &["6"]),
+];
+
+// Actions are only looked up in the screens that use them, so the same key can do different things in each
+static MENU_ACTIONS: &[Action] = &[Action::MoveUp, Action::MoveDown, Action::MoveLeft, Action::MoveRight];
+
+// Keys that do the same thing everywhere, and can't be bound to anything else
+static FIXED_KEYS: &[&str] = &["Esc", "Ctrl-c"];
+
+// The menus also use letters for managing profiles and solutions
+static MENU_FIXED_KEYS: &[&str] = &["Esc", "Ctrl-c", "Enter", "n", "r", "c", "e", "i", "x", "^", "v"];
+
+static EDITOR_ACTIONS: &[Action] = &[
+  Action::MoveUp,
+  Action::MoveDown,
+  Action::MoveLeft,
+  Action::MoveRight,
+  Action::Step,
+  Action::Run,
+  Action::PreviousTestCase,
+  Action::NextTestCase,
+  Action::Breakpoint,
+  Action::SecondBreakpoint,
+  Action::Undo,
+  Action::Redo,
+  Action::Clear,
+  Action::SetStart,
+  Action::Annotate,
+  Action::EditNote,
+  Action::ToggleAnnotations,
+  Action::Select,
+  Action::Copy,
+  Action::CopyAll,
+  Action::Cut,
+  Action::Paste,
+  Action::MoveAreaUp,
+  Action::MoveAreaDown,
+  Action::MoveAreaLeft,
+  Action::MoveAreaRight,
+  Action::ShiftUp,
+  Action::ShiftDown,
+  Action::ShiftLeft,
+  Action::ShiftRight,
+  Action::RotateClockwise,
+  Action::RotateCounterClockwise,
+  Action::MirrorHorizontally,
+  Action::MirrorVertically,
+];
+
+static EXECUTE_ACTIONS: &[Action] = &[
+  Action::Step,
+  Action::Run,
+  Action::StepBack,
+  Action::StepBackToBreakpoint,
+  Action::ScrubBack,
+  Action::ScrubForward,
+  Action::Speed1,
+  Action::Speed2,
+  Action::Speed3,
+  Action::Speed4,
+  Action::Speed5,
+  Action::Speed6,
+  Action::Breakpoint,
+  Action::SecondBreakpoint,
+];
+
+impl Action {
+  fn name(self) -> &'static str {
+    ACTIONS
+      .iter()
+      .find(|(action, _, _)| *action == self)
+      .map(|(_, name, _)| *name)
+      .expect("every action has a name")
+  }
+
+  fn from_name(name: &str) -> Option<Self> {
+    ACTIONS
+      .iter()
+      .find(|(_, action_name, _)| *action_name == name)
+      .map(|(action, _, _)| *action)
+  }
+}
+
+/// A key with any modifiers held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+  code: KeyCode,
+  modifiers: KeyModifiers,
+}
+
+impl Key {
+  ///
+  /// Read a key like "a", "Tab", "Ctrl-z" or "Shift-Up"
+  ///   Modifiers can be joined with "-" or "+", and names are not case sensitive
+  ///   Returns a printable error string if the key isn't recognized
+  ///
+  pub fn parse(text: &str) -> Result<Self, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = text;
+    while let Some((modifier, name)) = rest.split_once(['-', '+']).filter(|(_, name)| !name.is_empty()) {
+      modifiers |= match modifier.to_lowercase().as_str() {
+        "ctrl" | "control" => KeyModifiers::CONTROL,
+        "alt" => KeyModifiers::ALT,
+        "shift" => KeyModifiers::SHIFT,
+        _ => break,
+      };
+      rest = name;
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+      // Terminals send letters typed with Ctrl in lowercase
+      (Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => KeyCode::Char(c.to_ascii_lowercase()),
+      (Some(c), None) => KeyCode::Char(c),
+      _ => match rest.to_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "enter" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+          Some(n @ 1..=12) => KeyCode::F(n),
+          _ => return Err(format!("Unknown key \"{text}\"")),
+        },
+      },
+    };
+
+    // Shift is already part of the character, and it's what makes Tab into BackTab
+    let code = match code {
+      KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+      code => code,
+    };
+    if matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+      modifiers.remove(KeyModifiers::SHIFT);
+    }
+
+    Ok(Self { code, modifiers })
+  }
+
+  pub fn matches(&self, key: &KeyEvent) -> bool {
+    let mut modifiers = key.modifiers & (KeyModifiers::SHIFT | KeyModifiers::CONTROL | KeyModifiers::ALT);
+    if matches!(key.code, KeyCode::Char(_) | KeyCode::BackTab) {
+      modifiers.remove(KeyModifiers::SHIFT);
+    }
+    self.code == key.code && self.modifiers == modifiers
+  }
+
+  // Without the modifiers, so keys with the same modifiers can be written together
+  fn code_name(&self) -> String {
+    match self.code {
+      KeyCode::Char(' ') => "Space".into(),
+      KeyCode::Char(c) => c.to_string(),
+      KeyCode::Tab => "Tab".into(),
+      KeyCode::BackTab => "S-Tab".into(),
+      KeyCode::Backspace => "Bksp".into(),
+      KeyCode::Delete => "Del".into(),
+      KeyCode::Insert => "Ins".into(),
+      KeyCode::Enter => "Enter".into(),
+      KeyCode::Esc => "Esc".into(),
+      KeyCode::Up => "↑".into(),
+      KeyCode::Down => "↓".into(),
+      KeyCode::Left => "←".into(),
+      KeyCode::Right => "→".into(),
+      KeyCode::Home => "Home".into(),
+      KeyCode::End => "End".into(),
+      KeyCode::PageUp => "PgUp".into(),
+      KeyCode::PageDown => "PgDn".into(),
+      KeyCode::F(n) => format!("F{n}"),
+      _ => "?".into(),
+    }
+  }
+
+  fn modifier_prefix(&self) -> String {
+    let mut prefix = String::new();
+    if self.modifiers.contains(KeyModifiers::CONTROL) {
+      prefix.push('^');
+    }
+    if self.modifiers.contains(KeyModifiers::ALT) {
+      prefix.push_str("Alt+");
+    }
+    if self.modifiers.contains(KeyModifiers::SHIFT) {
+      prefix.push_str("S-");
+    }
+    prefix
+  }
+}
+
+impl Display for Key {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = self.code_name();
+    // Control letters are usually written in uppercase, like ^Z
+    match self.code {
+      KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::CONTROL) => {
+        write!(f, "{}{}", self.modifier_prefix(), c.to_ascii_uppercase())
+      },
+      _ => write!(f, "{}{name}", self.modifier_prefix()),
+    }
+  }
+}
+
+///
+/// Keys for several actions or commands on one line of instructions
+///   Separated by spaces if that fits in the width, then as a range like "1-6" if the keys are in order,
+///   then written together, sharing any modifiers like "Alt+hjkl"
+///
+fn keys_text(keys: &[Key], width: usize) -> String {
+  let names: Vec<String> = keys.iter().map(ToString::to_string).collect();
+  let mut options = vec![names.join(" ")];
+  if let (true, Some(first), Some(last)) = (is_range(keys), names.first(), names.last()) {
+    options.push(format!("{first}-{last}"));
+  }
+  options.push(names.join(""));
+  if keys
+    .iter()
+    .all(|key| key.modifiers == keys[0].modifiers && !key.modifiers.is_empty())
+  {
+    let codes: Vec<String> = keys.iter().map(Key::code_name).collect();
+    options.push(format!("{}{}", keys[0].modifier_prefix(), codes.join("")));
+  }
+
+  options
+    .iter()
+    .find(|text| text.chars().count() <= width)
+    .or_else(|| options.iter().min_by_key(|text| text.chars().count()))
+    .cloned()
+    .unwrap_or_default()
+}
+
+// Keys like "0123456789" or "F1 F2 F3", that can be written as a range
+fn is_range(keys: &[Key]) -> bool {
+  keys.len() > 2
+    && keys.windows(2).all(|pair| {
+      let next = match (pair[0].code, pair[1].code) {
+        (KeyCode::Char(a), KeyCode::Char(b)) => a as u32 + 1 == b as u32,
+        (KeyCode::F(a), KeyCode::F(b)) => a + 1 == b,
+        _ => false,
+      };
+      next && pair[0].modifiers == pair[1].modifiers
+    })
+}
+
+/// Formats one line of the instructions shown next to the grid, like "│Tab    = Step"
+fn instruction(keys: &[Key], label: &str, width: usize) -> String {
+  format!("│{:<width$} = {label}", keys_text(keys, width))
+}
+
+/// Keys for placing each command of one level type
+#[derive(Debug, Clone)]
+pub struct CommandKeys<C> {
+  keys: Vec<(C, Vec<Key>)>,
+}
+
+impl<C> CommandKeys<C>
+where
+  C: Copy + PartialEq + Display + DeserializeOwned,
+{
+  // The default keys, before any from the keybindings file
+  fn new(from_key: impl Fn(char) -> Option<C>) -> Self {
+    let mut keys: Vec<(C, Vec<Key>)> = Vec::new();
+    for c in '!'..='~' {
+      let Some(command) = from_key(c) else {
+        continue;
+      };
+      let key = Key {
+        code: KeyCode::Char(c),
+        modifiers: KeyModifiers::NONE,
+      };
+      match keys.iter_mut().find(|(existing, _)| *existing == command) {
+        Some((_, existing_keys)) => existing_keys.push(key),
+        None => keys.push((command, vec![key])),
+      }
+    }
+    Self { keys }
+  }
+
+  fn bind(&mut self, name: &str, keys: Vec<Key>) -> Result<(), String> {
+    let command = parse_command(name)?;
+    match self.keys.iter_mut().find(|(existing, _)| *existing == command) {
+      Some((_, existing_keys)) => *existing_keys = keys,
+      None => self.keys.push((command, keys)),
+    }
+    Ok(())
+  }
+
+  pub fn command(&self, key: &KeyEvent) -> Option<C> {
+    self
+      .keys
+      .iter()
+      .find(|(_, keys)| keys.iter().any(|k| k.matches(key)))
+      .map(|(command, _)| *command)
+  }
+
+  /// The command typed with this character and no modifiers, for reading pasted text
+  pub fn typed_with(&self, c: char) -> Option<C> {
+    self.command(&KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))
+  }
+
+  /// Instruction lines for each group of commands, using the first key of each one
+  pub fn instructions(&self, lines: &[(&str, &[C])], width: usize) -> Vec<String> {
+    lines
+      .iter()
+      .filter_map(|(label, commands)| {
+        let keys: Vec<Key> = commands.iter().filter_map(|command| self.first_key(*command)).collect();
+        (!keys.is_empty()).then(|| instruction(&keys, label, width))
+      })
+      .collect()
+  }
+
+  fn first_key(&self, command: C) -> Option<Key> {
+    self
+      .keys
+      .iter()
+      .find(|(existing, _)| *existing == command)
+      .and_then(|(_, keys)| keys.first().copied())
+  }
+
+  fn describe(&self) -> Vec<(String, Key)> {
+    self
+      .keys
+      .iter()
+      .flat_map(|(command, keys)| keys.iter().map(move |key| (format!("the {command} command"), *key)))
+      .collect()
+  }
+}
+
+/// Key or keys for one entry in the keybindings file
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+  One(String),
+  Many(Vec<String>),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct KeyBindingsFile {
+  actions: BTreeMap<String, KeyList>,
+  standard: BTreeMap<String, KeyList>,
+  parallel: BTreeMap<String, KeyList>,
+}
+
+///
+/// Keys for every action and command, loaded from "keybindings.toml"
+///   Anything not in the file keeps its default keys
+///
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+  actions: HashMap<Action, Vec<Key>>,
+  standard: CommandKeys<isa::standard::Command>,
+  parallel: CommandKeys<isa::parallel::Command>,
+}
+
+impl Default for KeyBindings {
+  fn default() -> Self {
+    let actions = ACTIONS
+      .iter()
+      .map(|(action, _, keys)| {
+        let keys = keys
+          .iter()
+          .map(|key| Key::parse(key).expect("default keys are valid"))
+          .collect();
+        (*action, keys)
+      })
+      .collect();
+
+    Self {
+      actions,
+      standard: CommandKeys::new(isa::standard::Command::from_key),
+      parallel: CommandKeys::new(isa::parallel::Command::from_key),
+    }
+  }
+}
+
+impl KeyBindings {
+  ///
+  /// Load the keybindings file, if there is one
+  ///   Also returns a printable error string for each entry that can't be used, and each key bound twice
+  ///
+  pub fn load(file: &Path) -> (Self, Vec<String>) {
+    let mut bindings = Self::default();
+    let mut problems = match fs::read_to_string(file) {
+      Ok(file_data) => bindings.apply(&file_data),
+      Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+      Err(e) => vec![e.to_string()],
+    };
+
+    // The defaults are checked too, even without a file
+    problems.extend(bindings.conflicts());
+    (bindings, problems)
+  }
+
+  // Bind the keys from the file's text, returning the entries that can't be used
+  fn apply(&mut self, file_data: &str) -> Vec<String> {
+    let bindings_file: KeyBindingsFile = match toml::from_str(file_data) {
+      Ok(bindings_file) => bindings_file,
+      Err(e) => return vec![e.to_string()],
+    };
+
+    let mut problems = Vec::new();
+    let mut parse_keys = |name: &str, keys: KeyList| {
+      let keys = match keys {
+        KeyList::One(key) => vec![key],
+        KeyList::Many(keys) => keys,
+      };
+      keys
+        .iter()
+        .map(|key| Key::parse(key))
+        .collect::<Result<Vec<Key>, String>>()
+        .map_err(|e| problems.push(format!("{name}: {e}")))
+        .ok()
+    };
+
+    let mut unknown = Vec::new();
+    for (name, keys) in bindings_file.actions {
+      match (Action::from_name(&name), parse_keys(&name, keys)) {
+        (Some(action), Some(keys)) => {
+          self.actions.insert(action, keys);
+        },
+        (None, _) => unknown.push(format!("Unknown action \"{name}\"")),
+        (Some(_), None) => {},
+      }
+    }
+    for (name, keys) in bindings_file.standard {
+      if let Some(keys) = parse_keys(&name, keys) {
+        self
+          .standard
+          .bind(&name, keys)
+          .unwrap_or_else(|e| unknown.push(format!("[standard] {e}")));
+      }
+    }
+    for (name, keys) in bindings_file.parallel {
+      if let Some(keys) = parse_keys(&name, keys) {
+        self
+          .parallel
+          .bind(&name, keys)
+          .unwrap_or_else(|e| unknown.push(format!("[parallel] {e}")));
+      }
+    }
+
+    problems.extend(unknown);
+    problems
+  }
+
+  ///
+  /// Every key that does two things on the same screen
+  ///   Actions are checked before commands, and in the order they're listed, so the first one wins
+  ///
+  fn conflicts(&self) -> Vec<String> {
+    let describe = |fixed_keys: &[&str], actions: &[Action]| -> Vec<(String, Key)> {
+      let fixed = fixed_keys.iter().map(|key| {
+        (
+          "a key that can't be changed".to_string(),
+          Key::parse(key).expect("fixed keys are valid"),
+        )
+      });
+      let bound = actions.iter().flat_map(|action| {
+        self
+          .keys(*action)
+          .iter()
+          .map(|key| (format!("\"{}\"", action.name()), *key))
+      });
+      fixed.chain(bound).collect()
+    };
+
+    let mut problems = Vec::new();
+    let mut check = |screen: &str, bindings: Vec<(String, Key)>, first_checked: usize| {
+      for (i, (second, key)) in bindings.iter().enumerate().skip(first_checked) {
+        if let Some((first, _)) = bindings[..i].iter().find(|(_, other)| other == key) {
+          problems.push(format!(
+            "Key \"{key}\" is bound to both {first} and {second} in the {screen}"
+          ));
+        }
+      }
+    };
+
+    check("menus", describe(MENU_FIXED_KEYS, MENU_ACTIONS), 0);
+    check("editor", describe(FIXED_KEYS, EDITOR_ACTIONS), 0);
+    check("execution screen", describe(FIXED_KEYS, EXECUTE_ACTIONS), 0);
+
+    // Conflicts between the editor actions were already found above
+    let editor = describe(FIXED_KEYS, EDITOR_ACTIONS);
+    let num_actions = editor.len();
+    check(
+      "standard editor",
+      editor.iter().cloned().chain(self.standard.describe()).collect(),
+      num_actions,
+    );
+    check(
+      "parallel editor",
+      editor.into_iter().chain(self.parallel.describe()).collect(),
+      num_actions,
+    );
+
+    problems
+  }
+
+  fn keys(&self, action: Action) -> &[Key] {
+    self.actions.get(&action).map_or(&[], Vec::as_slice)
+  }
+
+  fn action(&self, actions: &[Action], key: &KeyEvent) -> Option<Action> {
+    actions
+      .iter()
+      .copied()
+      .find(|action| self.keys(*action).iter().any(|k| k.matches(key)))
+  }
+
+  pub fn menu_action(&self, key: &KeyEvent) -> Option<Action> {
+    self.action(MENU_ACTIONS, key)
+  }
+
+  pub fn editor_action(&self, key: &KeyEvent) -> Option<Action> {
+    self.action(EDITOR_ACTIONS, key)
+  }
+
+  pub fn execute_action(&self, key: &KeyEvent) -> Option<Action> {
+    self.action(EXECUTE_ACTIONS, key)
+  }
+
+  pub fn standard_commands(&self) -> &CommandKeys<isa::standard::Command> {
+    &self.standard
+  }
+
+  pub fn parallel_commands(&self) -> &CommandKeys<isa::parallel::Command> {
+    &self.parallel
+  }
+
+  ///
+  /// Instruction lines for the actions, using the first key of each one
+  ///   Lines without any actions are blank, and lines where none of the actions have keys are left out
+  ///
+  pub fn instructions(&self, lines: &[(&[Action], &str)], width: usize) -> Vec<String> {
+    lines
+      .iter()
+      .filter_map(|(actions, label)| {
+        if actions.is_empty() {
+          return Some("│".to_string());
+        }
+        let keys: Vec<Key> = actions
+          .iter()
+          .filter_map(|action| self.keys(*action).first().copied())
+          .collect();
+        (!keys.is_empty()).then(|| instruction(&keys, label, width))
+      })
+      .collect()
+  }
+
+  /// First key of each action written together, for instructions that don't fit the usual form
+  pub fn keys_text(&self, actions: &[Action], width: usize) -> String {
+    let keys: Vec<Key> = actions
+      .iter()
+      .filter_map(|action| self.keys(*action).first().copied())
+      .collect();
+    keys_text(&keys, width)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::isa::standard::Command;
+
+  fn key(code: KeyCode, modifiers: KeyModifiers) -> Key {
+    Key { code, modifiers }
+  }
+
+  #[test]
+  fn key_names() {
+    let expected = [
+      ("a", key(KeyCode::Char('a'), KeyModifiers::NONE)),
+      ("N", key(KeyCode::Char('N'), KeyModifiers::NONE)),
+      ("-", key(KeyCode::Char('-'), KeyModifiers::NONE)),
+      ("Ctrl-Z", key(KeyCode::Char('z'), KeyModifiers::CONTROL)),
+      ("ctrl--", key(KeyCode::Char('-'), KeyModifiers::CONTROL)),
+      ("Alt+Space", key(KeyCode::Char(' '), KeyModifiers::ALT)),
+      ("Shift-Up", key(KeyCode::Up, KeyModifiers::SHIFT)),
+      ("Shift-Tab", key(KeyCode::BackTab, KeyModifiers::NONE)),
+      (
+        "Ctrl-Alt-delete",
+        key(KeyCode::Delete, KeyModifiers::CONTROL | KeyModifiers::ALT),
+      ),
+      ("F12", key(KeyCode::F(12), KeyModifiers::NONE)),
+    ];
+    for (text, key) in expected {
+      assert_eq!(Key::parse(text), Ok(key), "{text}");
+    }
+
+    for text in ["", "F13", "Ctrl-", "Hyper-a", "Up Down"] {
+      assert!(Key::parse(text).is_err(), "{text}");
+    }
+  }
+
+  #[test]
+  fn default_keys_dont_conflict() {
+    assert_eq!(KeyBindings::default().conflicts(), Vec::<String>::new());
+  }
+
+  #[test]
+  fn rebound_keys_are_checked() {
+    let mut bindings = KeyBindings::default();
+    let problems = bindings.apply(
+      r#"
+      [actions]
+      undo = "w"
+      jump = "g"
+
+      [standard]
+      add = ["A", "Ctrl-Banana"]
+      out = "O"
+      "#,
+    );
+    assert_eq!(
+      problems,
+      vec![
+        "add: Unknown key \"Ctrl-Banana\"".to_string(),
+        "Unknown action \"jump\"".to_string(),
+      ]
+    );
+    assert_eq!(
+      bindings.conflicts(),
+      vec![
+        "Key \"w\" is bound to both \"undo\" and the ↑ command in the standard editor".to_string(),
+        "Key \"w\" is bound to both \"undo\" and the ↑ command in the parallel editor".to_string(),
+      ]
+    );
+
+    // Pasted text is read with the new keys
+    let commands = bindings.standard_commands();
+    assert_eq!(commands.typed_with('O'), Some(Command::Out));
+    assert_eq!(commands.typed_with('o'), None);
+    assert_eq!(commands.typed_with('+'), Some(Command::Add));
+  }
+}
@@ -8,9 +8,14 @@ use std::path::Path;
 use uuid::Uuid;
 
 use crate::global_state::GlobalState;
+use crate::isa::{self, InstructionSetArchitecture};
+use crate::statistics::Par;
 
-const LEVELS_FOLDER: &str = "levels";
-const PACK_FILE: &str = "pack.toml";
+pub const LEVELS_FOLDER: &str = "levels";
+pub const PACK_FILE: &str = "pack.toml";
+
+// Programs that run longer than this are stopped, unless the level sets its own limit
+pub const DEFAULT_MAX_CYCLES: u32 = 100_000;
 
 /// Stores all details about the levels
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -53,6 +58,64 @@ pub struct Level {
   #[serde(default)]
   r#type: LevelType,
   lua_file: String,
+
+  // Limits left out of the pack file use the defaults for the level type
+  #[serde(default)]
+  grid_rows: Option<usize>,
+  #[serde(default)]
+  grid_cols: Option<usize>,
+  #[serde(default)]
+  stack_size: Option<usize>,
+  #[serde(default)]
+  max_inputs: Option<usize>,
+  #[serde(default)]
+  max_outputs: Option<usize>,
+  #[serde(default)]
+  max_cycles: Option<u32>,
+
+  // Scores for players to aim for, and a solution that proves the level can be solved
+  #[serde(default)]
+  par_cycles: Option<u32>,
+  #[serde(default)]
+  par_symbols: Option<usize>,
+  #[serde(default)]
+  reference_solution: Option<String>,
+
+  // Command names can be either the symbol or the alias, like "+" or "add"
+  #[serde(default)]
+  allowed_commands: Option<Vec<String>>,
+  #[serde(default)]
+  forbidden_commands: Vec<String>,
+
+  // Run before the generated test cases
+  #[serde(default)]
+  test_cases: Vec<StaticTestCase>,
+}
+
+///
+/// Hand-written test case from the pack file, for edge cases that random generation rarely hits
+///   Standard levels use "inputs" and "outputs", parallel levels use the values for each processor
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StaticTestCase {
+  pub inputs: Option<Vec<i16>>,
+  pub outputs: Option<Vec<i16>>,
+  pub p0_inputs: Option<Vec<i16>>,
+  pub p0_outputs: Option<Vec<i16>>,
+  pub p1_inputs: Option<Vec<i16>>,
+  pub p1_outputs: Option<Vec<i16>>,
+}
+
+/// Size of the program grid, stack, and puzzle values for a level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelLimits {
+  pub grid_rows: usize,
+  pub grid_cols: usize,
+  pub stack_size: usize,
+  pub max_inputs: usize,
+  pub max_outputs: usize,
+  pub max_cycles: u32,
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -63,6 +126,22 @@ pub enum LevelType {
   Parallel,
 }
 
+impl LevelType {
+  pub fn default_limits(self) -> LevelLimits {
+    match self {
+      LevelType::Standard => isa::Standard::DEFAULT_LIMITS,
+      LevelType::Parallel => isa::Parallel::DEFAULT_LIMITS,
+    }
+  }
+
+  pub fn max_limits(self) -> LevelLimits {
+    match self {
+      LevelType::Standard => isa::Standard::MAX_LIMITS,
+      LevelType::Parallel => isa::Parallel::MAX_LIMITS,
+    }
+  }
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct LevelIndex {
   pack_index: usize,
@@ -88,6 +167,31 @@ impl LevelPack {
     &self.groups[index]
   }
 
+  /// List every level in the pack, including challenge levels
+  pub fn level_indexes(&self, pack_index: usize) -> Vec<LevelIndex> {
+    let mut indexes = Vec::new();
+    for (group, level_group) in self.groups.iter().enumerate() {
+      for (level_in_group, main_level) in level_group.levels.iter().enumerate() {
+        indexes.push(LevelIndex::new(pack_index, group, level_in_group));
+        indexes.extend(
+          (0..main_level.challenge_levels.len())
+            .map(|challenge| LevelIndex::new_challenge(pack_index, group, level_in_group, challenge)),
+        );
+      }
+    }
+    indexes
+  }
+
+  pub fn level(&self, index: LevelIndex) -> &Level {
+    let main_level = self
+      .level_group(index.get_group())
+      .main_level(index.get_level_in_group());
+    match index.get_challenge() {
+      Some(challenge) => main_level.challenge_level(challenge),
+      None => main_level.level(),
+    }
+  }
+
   pub fn get_absolute_index(&self, level_index: LevelIndex) -> usize {
     (0..level_index.group)
       .map(|group_index| self.groups[group_index].len())
@@ -102,7 +206,7 @@ impl LevelPack {
   /// Load a level pack from a folder
   ///   Returns an error if there are no levels inside the pack file
   ///
-  fn from_file<P: AsRef<Path>>(toml_pack_file: P) -> io::Result<Self> {
+  pub fn from_file<P: AsRef<Path>>(toml_pack_file: P) -> io::Result<Self> {
     let parent_folder = toml_pack_file
       .as_ref()
       .parent()
@@ -128,6 +232,15 @@ impl LevelPack {
       ))?;
     }
 
+    // Make sure every level fits on the screen and only uses known commands
+    for main_level in me.groups.iter().flat_map(|g| g.levels.iter()) {
+      for level in iter::once(&main_level.level).chain(main_level.challenge_levels.iter()) {
+        level
+          .validate()
+          .map_err(|e| io::Error::new(ErrorKind::InvalidData, format!("Level \"{}\": {e}", level.name)))?;
+      }
+    }
+
     Ok(me)
   }
 }
@@ -185,11 +298,97 @@ impl Level {
     self.r#type
   }
 
-  #[allow(unused)]
   pub fn lua_file(&self) -> &str {
     &self.lua_file
   }
 
+  /// Only these commands may be used if set
+  pub fn allowed_commands(&self) -> Option<&[String]> {
+    self.allowed_commands.as_deref()
+  }
+
+  pub fn forbidden_commands(&self) -> &[String] {
+    &self.forbidden_commands
+  }
+
+  pub fn static_test_cases(&self) -> &[StaticTestCase] {
+    &self.test_cases
+  }
+
+  pub fn par(&self) -> Par {
+    Par {
+      cycles: self.par_cycles,
+      symbols: self.par_symbols,
+    }
+  }
+
+  /// Path to a ".fit" file, relative to the level pack folder
+  pub fn reference_solution(&self) -> Option<&str> {
+    self.reference_solution.as_deref()
+  }
+
+  /// Limits from the pack file, filled in with the defaults for the level type
+  pub fn limits(&self) -> LevelLimits {
+    let defaults = self.r#type.default_limits();
+    LevelLimits {
+      grid_rows: self.grid_rows.unwrap_or(defaults.grid_rows),
+      grid_cols: self.grid_cols.unwrap_or(defaults.grid_cols),
+      stack_size: self.stack_size.unwrap_or(defaults.stack_size),
+      max_inputs: self.max_inputs.unwrap_or(defaults.max_inputs),
+      max_outputs: self.max_outputs.unwrap_or(defaults.max_outputs),
+      max_cycles: self.max_cycles.unwrap_or(defaults.max_cycles),
+    }
+  }
+
+  // Returns a printable error string if any limit is out of range, a command is unknown, or a test case is invalid
+  fn validate(&self) -> Result<(), String> {
+    self.validate_limits()?;
+    match self.r#type {
+      LevelType::Standard => self.validate_for_isa::<isa::Standard>(),
+      LevelType::Parallel => self.validate_for_isa::<isa::Parallel>(),
+    }
+  }
+
+  fn validate_limits(&self) -> Result<(), String> {
+    let limits = self.limits();
+    let max = self.r#type.max_limits();
+
+    let checks = [
+      ("gridRows", limits.grid_rows, 1, max.grid_rows),
+      ("gridCols", limits.grid_cols, 1, max.grid_cols),
+      ("stackSize", limits.stack_size, 1, max.stack_size),
+      ("maxInputs", limits.max_inputs, 0, max.max_inputs),
+      ("maxOutputs", limits.max_outputs, 0, max.max_outputs),
+    ];
+    for (name, value, min, max) in checks {
+      if !(min..=max).contains(&value) {
+        return Err(format!("{name} must be between {min} and {max}, {value} given"));
+      }
+    }
+
+    if limits.max_cycles == 0 {
+      return Err("maxCycles must be at least 1".into());
+    }
+    if self.par_cycles == Some(0) {
+      return Err("parCycles must be at least 1".into());
+    }
+
+    Ok(())
+  }
+
+  fn validate_for_isa<ISA: InstructionSetArchitecture>(&self) -> Result<(), String> {
+    ISA::command_palette(self)?;
+
+    let limits = self.limits();
+    for (i, test_case) in self.test_cases.iter().enumerate() {
+      ISA::static_test_case(test_case)
+        .and_then(|test_case| ISA::new_puzzle(test_case, &limits))
+        .map_err(|e| format!("Test case {}: {e}", i + 1))?;
+    }
+
+    Ok(())
+  }
+
   pub fn get_title(&self, level_index: LevelIndex) -> String {
     if level_index.challenge.is_some() {
       format!("Challenge {} - {}", level_index, self.name())
@@ -259,13 +458,13 @@ impl fmt::Display for LevelIndex {
   }
 }
 
-pub fn load_all_level_packs() -> io::Result<Vec<LevelPack>> {
-  let mut level_packs: Vec<LevelPack> = fs::read_dir(LEVELS_FOLDER)?
+pub fn load_all_level_packs(levels_folder: &Path) -> io::Result<Vec<LevelPack>> {
+  let mut level_packs: Vec<LevelPack> = fs::read_dir(levels_folder)?
     // Skip any errors from traversing the directory
     .filter_map(|entry| match entry {
       Ok(entry) => Some(entry),
       Err(e) => {
-        println!("Failed to traverse \"{LEVELS_FOLDER}\" directory: {e}");
+        eprintln!("Failed to traverse \"{}\" directory: {e}", levels_folder.display());
         None
       },
     })
@@ -281,11 +480,11 @@ pub fn load_all_level_packs() -> io::Result<Vec<LevelPack>> {
     // Skip level packs that fail to load
     .filter_map(|pack_file_path| match LevelPack::from_file(&pack_file_path) {
       Ok(level_pack) => {
-        println!("Loaded level pack: {}", pack_file_path.to_string_lossy());
+        eprintln!("Loaded level pack: {}", pack_file_path.to_string_lossy());
         Some(level_pack)
       },
       Err(e) => {
-        println!(
+        eprintln!(
           "Failed to load level pack: {}\n  - Error: {e}",
           pack_file_path.to_string_lossy()
         );
@@ -298,7 +497,10 @@ pub fn load_all_level_packs() -> io::Result<Vec<LevelPack>> {
   if level_packs.is_empty() {
     Err(io::Error::new(
       ErrorKind::InvalidData,
-      format!("No valid level packs found in the \"{LEVELS_FOLDER}\" folder"),
+      format!(
+        "No valid level packs found in the \"{}\" folder",
+        levels_folder.display()
+      ),
     ))?;
   }
 
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+use uuid::Uuid;
+
+use crate::cli::LintPackArgs;
+use crate::global_state::GlobalState;
+use crate::isa::{self, InstructionSetArchitecture, Solution};
+use crate::level::{Level, LevelPack, LevelType, PACK_FILE};
+use crate::solution_file::SolutionFile;
+use crate::state::{DESCRIPTION_LINES, DESCRIPTION_WIDTH};
+use crate::statistics::Statistics;
+
+const EXIT_FAILED: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+///
+/// Run the "lint-pack" command-line mode
+///   Exit code is 0 if the pack has no problems, 1 if any are found, and 2 if the pack file can't be loaded
+///
+pub fn run(args: &LintPackArgs, levels_folder: &Path) -> ExitCode {
+  let pack_file = Path::new(&args.folder).join(PACK_FILE);
+  let level_pack = match LevelPack::from_file(&pack_file) {
+    Ok(level_pack) => level_pack,
+    Err(e) => {
+      eprintln!("Error: Failed to load {}: {e}", pack_file.display());
+      return ExitCode::from(EXIT_ERROR);
+    },
+  };
+
+  println!("{} ({})", level_pack.name(), level_pack.folder());
+  let mut num_problems = 0;
+
+  let pack_problems = check_duplicate_ids(&level_pack, levels_folder);
+  print_problems("  ", &pack_problems);
+  num_problems += pack_problems.len();
+
+  for level_index in level_pack.level_indexes(0) {
+    let level = level_pack.level(level_index);
+    let problems = check_level(args, level_pack.folder(), level);
+    if problems.is_empty() {
+      println!("  {}: OK", level.get_title(level_index));
+    } else {
+      println!("  {}:", level.get_title(level_index));
+      print_problems("    ", &problems);
+    }
+    num_problems += problems.len();
+  }
+
+  if num_problems == 0 {
+    println!("No problems found");
+    ExitCode::SUCCESS
+  } else {
+    println!("Found {num_problems} problem(s)");
+    ExitCode::from(EXIT_FAILED)
+  }
+}
+
+fn print_problems(indent: &str, problems: &[String]) {
+  for problem in problems {
+    println!("{indent}- {problem}");
+  }
+}
+
+///
+/// Check that no two levels share an id, in this pack or any other pack in the levels folder
+///   Solutions and statistics are saved by level id, so a duplicate would mix them up
+///
+fn check_duplicate_ids(level_pack: &LevelPack, levels_folder: &Path) -> Vec<String> {
+  let mut problems = Vec::new();
+
+  let mut titles: HashMap<Uuid, String> = HashMap::new();
+  for level_index in level_pack.level_indexes(0) {
+    let level = level_pack.level(level_index);
+    let title = level.get_title(level_index);
+    if let Some(other_title) = titles.get(&level.id()) {
+      problems.push(format!("{title} has the same id as {other_title}"));
+    } else {
+      titles.insert(level.id(), title);
+    }
+  }
+
+  for other_pack in other_level_packs(levels_folder, level_pack.folder()) {
+    for level_index in other_pack.level_indexes(0) {
+      let level = other_pack.level(level_index);
+      if let Some(title) = titles.get(&level.id()) {
+        problems.push(format!(
+          "{title} has the same id as {} in \"{}\"",
+          level.get_title(level_index),
+          other_pack.name()
+        ));
+      }
+    }
+  }
+
+  problems
+}
+
+// Packs that fail to load are skipped, since their problems don't belong to this pack
+fn other_level_packs(levels_folder: &Path, pack_folder: &str) -> Vec<LevelPack> {
+  let Ok(entries) = fs::read_dir(levels_folder) else {
+    return Vec::new();
+  };
+  let pack_folder = fs::canonicalize(pack_folder).ok();
+
+  entries
+    .filter_map(Result::ok)
+    .filter(|entry| fs::canonicalize(entry.path()).ok() != pack_folder)
+    .map(|entry| entry.path().join(PACK_FILE))
+    .filter(|pack_file| pack_file.is_file())
+    .filter_map(|pack_file| LevelPack::from_file(pack_file).ok())
+    .collect()
+}
+
+fn check_level(args: &LintPackArgs, folder: &str, level: &Level) -> Vec<String> {
+  let mut problems = check_description(level.description());
+
+  if !Path::new(folder).join(level.lua_file()).is_file() {
+    problems.push(format!("Lua file \"{}\" does not exist", level.lua_file()));
+    return problems;
+  }
+
+  problems.extend(match level.level_type() {
+    LevelType::Standard => check_test_cases::<isa::Standard>(args, folder, level),
+    LevelType::Parallel => check_test_cases::<isa::Parallel>(args, folder, level),
+  });
+  problems
+}
+
+// The description is printed as-is on the help screen, so it needs to fit at the minimum terminal size
+fn check_description(description: &str) -> Vec<String> {
+  let mut problems = Vec::new();
+
+  for (line_number, line) in description.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+    let width = line.chars().count();
+    if width > DESCRIPTION_WIDTH {
+      problems.push(format!(
+        "Description line {line_number} is {width} characters wide, the help screen only fits {DESCRIPTION_WIDTH}"
+      ));
+    }
+  }
+
+  let num_lines = description.lines().count();
+  if num_lines > DESCRIPTION_LINES {
+    problems.push(format!(
+      "Description is {num_lines} lines long, the help screen only fits {DESCRIPTION_LINES}"
+    ));
+  }
+
+  problems
+}
+
+///
+/// Run the level script the same way the game does, and check everything it returns
+///   The scored seed has to give the same test cases every time, or saved statistics won't match
+///   Other seeds are checked too, so a generator that only sometimes goes out of bounds gets caught
+///
+fn check_test_cases<ISA: InstructionSetArchitecture>(args: &LintPackArgs, folder: &str, level: &Level) -> Vec<String> {
+  let generate = |seed| ISA::generate_raw_test_cases(folder, level.lua_file(), seed, isa::NUM_TEST_CASES);
+
+  let (first, second) = match generate(isa::SEED).and_then(|first| Ok((first, generate(isa::SEED)?))) {
+    Ok(test_cases) => test_cases,
+    Err(e) => return vec![e],
+  };
+  let to_json = |test_case: &ISA::TestCase| serde_json::to_value(test_case).ok();
+  if let Some(i) = (0..first.len()).find(|&i| to_json(&first[i]) != to_json(&second[i])) {
+    return vec![format!(
+      "Generated test case {} changes between runs with the same seed, \
+       the script might depend on table order or addresses",
+      i + 1
+    )];
+  }
+
+  let limits = level.limits();
+  for seed in (0..args.seeds).map(|i| isa::SEED.wrapping_add(i)) {
+    let test_cases = match generate(seed) {
+      Ok(test_cases) => test_cases,
+      Err(e) => return vec![format!("Seed {seed}: {e}")],
+    };
+    for (i, test_case) in test_cases.into_iter().enumerate() {
+      if let Err(e) = ISA::new_puzzle(test_case, &limits) {
+        return vec![format!("Seed {seed}, generated test case {}: {e}", i + 1)];
+      }
+    }
+  }
+
+  if level.reference_solution().is_none() {
+    return Vec::new();
+  }
+
+  let test_cases = match ISA::generate_test_cases(folder, level, isa::SEED, isa::NUM_TEST_CASES, None) {
+    Ok(test_cases) => test_cases,
+    Err(e) => return vec![format!("Failed to generate test cases: {e}")],
+  };
+  match run_reference_solution::<ISA>(folder, level, &test_cases) {
+    Some(Err(e)) => vec![e],
+    Some(Ok(_)) | None => Vec::new(),
+  }
+}
+
+///
+/// Check that every level with a reference solution can be solved, when the game starts
+///   Only levels with cached test cases are checked, so no level scripts need to generate them
+///   Failures are only printed, since the level can still be played
+///
+pub fn check_reference_solutions(global_state: &GlobalState) {
+  for pack_index in 0..global_state.num_level_packs() {
+    let level_pack = global_state.get_level_pack(pack_index);
+    for level_index in level_pack.level_indexes(pack_index) {
+      let level = level_pack.level(level_index);
+      if level.reference_solution().is_none() {
+        continue;
+      }
+
+      let result = match level.level_type() {
+        LevelType::Standard => check_reference_solution::<isa::Standard>(global_state, level_pack.folder(), level),
+        LevelType::Parallel => check_reference_solution::<isa::Parallel>(global_state, level_pack.folder(), level),
+      };
+      if let Err(e) = result {
+        eprintln!(
+          "Level may not be solvable: {} ({})\n  - Error: {e}",
+          level.get_title(level_index),
+          level_pack.name()
+        );
+      }
+    }
+  }
+}
+
+// Levels that haven't been cached yet are skipped, lint-pack always checks them
+fn check_reference_solution<ISA: InstructionSetArchitecture>(
+  global_state: &GlobalState,
+  folder: &str,
+  level: &Level,
+) -> Result<(), String> {
+  let Some(test_cases) = ISA::cached_test_cases(
+    folder,
+    level,
+    isa::SEED,
+    isa::NUM_TEST_CASES,
+    &global_state.cache_folder(),
+  ) else {
+    return Ok(());
+  };
+  let test_cases = test_cases.map_err(|e| format!("Failed to load test cases: {e}"))?;
+
+  run_reference_solution::<ISA>(folder, level, &test_cases).map_or(Ok(()), |result| result.map(|_| ()))
+}
+
+///
+/// Load the reference solution named in the pack file and run it against the test cases
+///   Returns None if the level doesn't have one, or a printable error string if it fails
+///
+pub fn run_reference_solution<ISA: InstructionSetArchitecture>(
+  folder: &str,
+  level: &Level,
+  test_cases: &[ISA::Puzzle],
+) -> Option<Result<Statistics, String>> {
+  let path = level.reference_solution()?;
+
+  let result = fs::read_to_string(Path::new(folder).join(path))
+    .map_err(|e| e.to_string())
+    .and_then(|text| SolutionFile::parse(&text))
+    .and_then(|file| {
+      if file.level_id != level.id() {
+        return Err("Solution is for a different level".into());
+      }
+      if file.level_type != ISA::LEVEL_TYPE {
+        return Err("Solution is for a different type of level".into());
+      }
+
+      let limits = level.limits();
+      let solution = ISA::Solution::from_programs(file.name.as_str(), &file.programs, &limits)?;
+      ISA::command_palette(level)?.check(ISA::commands_used(&solution))?;
+      ISA::run_solution(&solution, test_cases, &limits).map_err(|failure| {
+        format!(
+          "Test Case {}, Cycle {}: {}",
+          failure.test_case, failure.cycle, failure.message
+        )
+      })
+    });
+
+  Some(result.map_err(|e| format!("Reference solution \"{path}\" fails: {e}")))
+}
@@ -1,30 +1,86 @@
+use cli::{Command, Options};
 use global_state::GlobalState;
+use keybindings::KeyBindings;
 use level::load_all_level_packs;
 use state::TitleState;
+use std::env;
+use std::process::ExitCode;
 
+mod cli;
+mod clipboard;
 mod global_state;
 mod grid;
 mod isa;
+mod keybindings;
 mod level;
+mod lint;
+mod migration;
+mod paths;
 mod printable;
+mod revalidate;
+mod solution_file;
 mod state;
 mod statistics;
+mod transfer;
+mod undo;
+mod verify;
 
-fn main() {
-  let all_level_packs = match load_all_level_packs() {
+fn main() -> ExitCode {
+  let options = match Options::parse(env::args().skip(1)) {
+    Ok(o) => o,
+    Err(e) => {
+      eprintln!("{e}\n\n{}", cli::USAGE);
+      return ExitCode::from(2);
+    },
+  };
+
+  if let Command::Help = options.command {
+    println!("{}", cli::USAGE);
+    return ExitCode::SUCCESS;
+  }
+
+  let levels_folder = options.levels_folder.unwrap_or_else(paths::default_levels_folder);
+
+  // The pack being checked might not load, so this runs before the other level packs are loaded
+  if let Command::LintPack(ref args) = options.command {
+    return lint::run(args, &levels_folder);
+  }
+
+  let all_level_packs = match load_all_level_packs(&levels_folder) {
     Ok(l) => l,
     Err(e) => {
       println!("Failed to load levels: {e}");
-      return;
+      return ExitCode::FAILURE;
     },
   };
 
-  let mut global_state = GlobalState::load(all_level_packs);
+  let save_folder = options.save_folder.unwrap_or_else(paths::default_save_folder);
+  let mut global_state = GlobalState::load(all_level_packs, save_folder);
+
+  match options.command {
+    Command::Verify(args) => verify::run(&args, &global_state),
+    Command::Export(args) => transfer::export(&args, &global_state),
+    Command::Import(args) => transfer::import(&args, &mut global_state),
+    Command::Play | Command::Help | Command::LintPack(_) => {
+      lint::check_reference_solutions(&global_state);
+      revalidate::check_test_case_versions(&mut global_state);
 
-  state::run(Box::new(TitleState::new()), &mut global_state).ok();
+      let keybindings_file = options.keybindings_file.unwrap_or_else(paths::default_keybindings_file);
+      let (keybindings, problems) = KeyBindings::load(&keybindings_file);
+      for problem in problems {
+        eprintln!("{}: {problem}", keybindings_file.display());
+      }
+      global_state.set_keybindings(keybindings);
 
-  match global_state.save() {
-    Ok(_) => println!("Saved progress!"),
-    Err(e) => println!("Failed to save progress: {e}"),
+      let title_state = TitleState::new(&global_state);
+      state::run(Box::new(title_state), &mut global_state).ok();
+
+      match global_state.save() {
+        Ok(_) => println!("Saved progress!"),
+        Err(e) => println!("Failed to save progress: {e}"),
+      }
+
+      ExitCode::SUCCESS
+    },
   }
 }
@@ -0,0 +1,110 @@
+use serde_json::{Map, Value};
+
+// Bump this and add a migration whenever the save file format changes
+pub const SAVE_VERSION: u32 = 2;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+// MIGRATIONS[i] upgrades a save file from version i + 1 to version i + 2
+static MIGRATIONS: [Migration; SAVE_VERSION as usize - 1] = [migrate_v1_to_v2];
+
+///
+/// Upgrade a parsed save file to the current version
+///   Save files without a version number are treated as version 1
+///
+pub fn migrate(save: &mut Value) -> Result<(), String> {
+  let save = save.as_object_mut().ok_or("Save file is not a JSON object")?;
+
+  let version = match save.get("version") {
+    None => 1,
+    Some(v) => v
+      .as_u64()
+      .and_then(|v| u32::try_from(v).ok())
+      .filter(|v| *v >= 1)
+      .ok_or("Invalid save file version")?,
+  };
+
+  if version > SAVE_VERSION {
+    return Err(format!(
+      "Save file version {version} is newer than this game supports (version {SAVE_VERSION})"
+    ));
+  }
+
+  for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+    migration(save)?;
+  }
+
+  save.insert("version".into(), SAVE_VERSION.into());
+  Ok(())
+}
+
+// Version 1 could store a single solution per level instead of a list
+fn migrate_v1_to_v2(save: &mut Map<String, Value>) -> Result<(), String> {
+  let Some(solutions) = save.get_mut("solutions") else {
+    return Ok(());
+  };
+
+  let solutions = solutions.as_object_mut().ok_or("Invalid \"solutions\" in save file")?;
+  for level_solutions in solutions.values_mut() {
+    if level_solutions.is_object() {
+      *level_solutions = Value::Array(vec![level_solutions.take()]);
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn single_solutions_become_lists() {
+    let mut save = json!({
+      "solutions": {
+        "1A": { "name": "First" },
+        "1B": [{ "name": "Second" }, { "name": "Third" }],
+      },
+    });
+    migrate(&mut save).unwrap();
+    assert_eq!(
+      save,
+      json!({
+        "version": SAVE_VERSION,
+        "solutions": {
+          "1A": [{ "name": "First" }],
+          "1B": [{ "name": "Second" }, { "name": "Third" }],
+        },
+      })
+    );
+
+    // Already current, so nothing changes
+    let migrated = save.clone();
+    migrate(&mut save).unwrap();
+    assert_eq!(save, migrated);
+  }
+
+  #[test]
+  fn save_without_solutions() {
+    let mut save = json!({ "version": 1, "profile": "Player" });
+    migrate(&mut save).unwrap();
+    assert_eq!(save, json!({ "version": SAVE_VERSION, "profile": "Player" }));
+  }
+
+  #[test]
+  fn newer_or_invalid_versions() {
+    let mut save = json!({ "version": SAVE_VERSION + 1, "solutions": {} });
+    let error = migrate(&mut save).unwrap_err();
+    assert!(error.contains("newer than this game supports"), "{error}");
+    assert_eq!(save["version"], json!(SAVE_VERSION + 1));
+
+    for version in [json!(0), json!(-1), json!("2"), json!(1.5), json!(u64::MAX)] {
+      let mut save = json!({ "version": version });
+      assert_eq!(migrate(&mut save), Err("Invalid save file version".to_string()));
+    }
+
+    assert!(migrate(&mut json!([])).is_err());
+    assert!(migrate(&mut json!({ "solutions": [] })).is_err());
+  }
+}
@@ -0,0 +1,78 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::level::LEVELS_FOLDER;
+
+const APP_FOLDER: &str = "funge-it-together";
+const KEYBINDINGS_FILE: &str = "keybindings.toml";
+
+///
+/// Folder for the save files, following the XDG base directory spec
+///   Uses "$XDG_DATA_HOME/funge-it-together", then "~/.local/share/funge-it-together"
+///   Falls back to the current directory if neither is set
+///
+pub fn default_save_folder() -> PathBuf {
+  data_home()
+    .map(|dir| dir.join(APP_FOLDER))
+    .unwrap_or_else(|| PathBuf::from("."))
+}
+
+///
+/// Folder containing the level packs
+///   A "levels" folder in the current directory or next to the executable takes priority,
+///   otherwise the levels are expected in the data folder
+///
+pub fn default_levels_folder() -> PathBuf {
+  let local = PathBuf::from(LEVELS_FOLDER);
+  if local.is_dir() {
+    return local;
+  }
+
+  let next_to_exe = env::current_exe()
+    .ok()
+    .and_then(|exe| exe.parent().map(|dir| dir.join(LEVELS_FOLDER)))
+    .filter(|dir| dir.is_dir());
+
+  next_to_exe
+    .or_else(|| data_home().map(|dir| dir.join(APP_FOLDER).join(LEVELS_FOLDER)))
+    .unwrap_or(local)
+}
+
+///
+/// File with the keybindings, following the XDG base directory spec
+///   Uses "$XDG_CONFIG_HOME/funge-it-together/keybindings.toml", then "~/.config/funge-it-together/keybindings.toml"
+///   Falls back to the current directory if neither is set
+///
+pub fn default_keybindings_file() -> PathBuf {
+  config_home()
+    .map(|dir| dir.join(APP_FOLDER))
+    .unwrap_or_else(|| PathBuf::from("."))
+    .join(KEYBINDINGS_FILE)
+}
+
+fn data_home() -> Option<PathBuf> {
+  // The spec says relative paths should be ignored
+  let xdg_data_home = env::var_os("XDG_DATA_HOME")
+    .map(PathBuf::from)
+    .filter(|dir| dir.is_absolute());
+
+  xdg_data_home.or_else(|| {
+    env::var_os("HOME")
+      .map(PathBuf::from)
+      .filter(|dir| dir.is_absolute())
+      .map(|home| home.join(Path::new(".local/share")))
+  })
+}
+
+fn config_home() -> Option<PathBuf> {
+  let xdg_config_home = env::var_os("XDG_CONFIG_HOME")
+    .map(PathBuf::from)
+    .filter(|dir| dir.is_absolute());
+
+  xdg_config_home.or_else(|| {
+    env::var_os("HOME")
+      .map(PathBuf::from)
+      .filter(|dir| dir.is_absolute())
+      .map(|home| home.join(Path::new(".config")))
+  })
+}
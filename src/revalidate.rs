@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use crate::global_state::GlobalState;
+use crate::isa::{self, InstructionSetArchitecture, SolutionManager};
+use crate::level::{Level, LevelIndex, LevelType};
+use crate::statistics::{RunResult, Statistics};
+
+///
+/// Find the current version of every level's test cases, when the game starts
+///   Levels whose scripts changed since they were solved get flagged in the level select screen
+///   Only the test case cache is read, so this stays quick no matter how many levels there are
+///   Levels without a cache are skipped, their version is found when they are opened
+///
+pub fn check_test_case_versions(global_state: &mut GlobalState) {
+  let cache_folder = global_state.cache_folder();
+
+  let mut versions = Vec::new();
+  for pack_index in 0..global_state.num_level_packs() {
+    let level_pack = global_state.get_level_pack(pack_index);
+    for level_index in level_pack.level_indexes(pack_index) {
+      let level = level_pack.level(level_index);
+      let version = match level.level_type() {
+        LevelType::Standard => test_case_version::<isa::Standard>(level_pack.folder(), level, &cache_folder),
+        LevelType::Parallel => test_case_version::<isa::Parallel>(level_pack.folder(), level, &cache_folder),
+      };
+      if let Some(version) = version {
+        versions.push((level.id(), version));
+      }
+    }
+  }
+
+  for (level_id, version) in versions {
+    global_state.set_test_case_version(level_id, version);
+  }
+}
+
+fn test_case_version<ISA: InstructionSetArchitecture>(
+  folder: &str,
+  level: &Level,
+  cache_folder: &Path,
+) -> Option<String> {
+  ISA::cached_test_case_version(folder, level, isa::SEED, isa::NUM_TEST_CASES, cache_folder)
+}
+
+///
+/// Run every saved solution for the level against its current test cases, and replace the statistics
+///   Each run is added to the solution's history too
+///   Returns a printable error string if the test cases fail to generate, or no solution passes anymore
+///
+pub fn recompute_statistics(global_state: &mut GlobalState, level_index: LevelIndex) -> Result<(), String> {
+  match global_state.level(level_index).level_type() {
+    LevelType::Standard => recompute::<isa::Standard>(global_state, level_index),
+    LevelType::Parallel => recompute::<isa::Parallel>(global_state, level_index),
+  }
+}
+
+fn recompute<ISA: InstructionSetArchitecture>(
+  global_state: &mut GlobalState,
+  level_index: LevelIndex,
+) -> Result<(), String>
+where
+  GlobalState: SolutionManager<ISA>,
+{
+  let level_pack = global_state.get_level_pack(level_index.get_level_pack_index());
+  let level = level_pack.level(level_index);
+  let (level_id, title, limits, par) = (level.id(), level.get_title(level_index), level.limits(), level.par());
+
+  let (test_cases, version) = ISA::generate_versioned_test_cases(
+    level_pack.folder(),
+    level,
+    isa::SEED,
+    isa::NUM_TEST_CASES,
+    Some(&global_state.cache_folder()),
+  )
+  .map_err(|e| format!("Failed to generate test cases: {e}"))?;
+
+  // Solutions using commands the level doesn't allow anymore can't count, so they aren't run
+  let palette = ISA::command_palette(level)?;
+  let results: Vec<(usize, RunResult)> = global_state
+    .get_all_solutions(level_id)
+    .iter()
+    .enumerate()
+    .filter(|(_, solution)| palette.check(ISA::commands_used(solution)).is_ok())
+    .map(|(solution_index, solution)| {
+      let result = match ISA::run_solution(solution, &test_cases, &limits) {
+        Ok(mut statistics) => {
+          statistics.set_test_case_version(Some(version.clone()));
+          statistics.set_par(&par);
+          RunResult::Solved(statistics)
+        },
+        Err(failure) => RunResult::Failed(failure),
+      };
+      (solution_index, result)
+    })
+    .collect();
+
+  let mut best: Option<Statistics> = None;
+  for (solution_index, result) in results {
+    if let RunResult::Solved(ref statistics) = result {
+      match best {
+        Some(ref mut best) => best.set_to_best(statistics),
+        None => best = Some(statistics.clone()),
+      }
+    }
+    global_state.record_run(level_id, solution_index, result);
+  }
+
+  global_state.set_test_case_version(level_id, version);
+  let is_complete = best.is_some();
+  global_state.replace_statistics(level_id, best);
+
+  if is_complete {
+    Ok(())
+  } else {
+    Err(format!(
+      "No saved solution solves {title} anymore, so its scores are still out of date"
+    ))
+  }
+}
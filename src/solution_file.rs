@@ -0,0 +1,442 @@
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::grid::{Annotation, Direction, Grid, Start};
+use crate::isa;
+use crate::level::{LevelIndex, LevelType};
+
+pub const FILE_EXTENSION: &str = "fit";
+pub const EXPORT_FOLDER: &str = "solutions";
+
+///
+/// Human-editable text version of a solution, stored in ".fit" files
+///   Header lines use "key: value", and each program starts with its own "start:" line
+///   The start can be followed by the way it goes first, when that isn't right
+///   Grid rows are wrapped in "|" so editors don't strip any trailing spaces
+///
+/// name: Add Seven
+/// level: b42f7f23-62aa-4b90-91b4-22cb5745211d
+/// type: standard
+/// note: Reads, adds, writes
+///
+/// start: 0,0
+/// breakpoints: 0,3
+/// annotation: 0,2 Add seven
+/// |Ї7+Θ      |
+/// |          |
+///
+#[derive(Debug, Clone)]
+pub struct SolutionFile {
+  pub name: String,
+  pub level_id: Uuid,
+  pub level_type: LevelType,
+  pub note: String,
+  pub programs: Vec<ProgramText>,
+}
+
+/// A single program grid from a solution file
+#[derive(Debug, Clone, Default)]
+pub struct ProgramText {
+  start: Start,
+  breakpoints: Vec<(usize, usize)>,
+  annotations: Vec<Annotation>,
+  rows: Vec<Vec<char>>,
+}
+
+impl SolutionFile {
+  pub fn parse(text: &str) -> Result<Self, String> {
+    let mut name = None;
+    let mut level_id = None;
+    let mut level_type = None;
+    let mut note = String::new();
+    let mut programs: Vec<ProgramText> = Vec::new();
+
+    for (line_number, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      // Grid rows belong to the last program
+      if let Some(row) = line.strip_prefix('|') {
+        let row = row
+          .strip_suffix('|')
+          .ok_or_else(|| format!("Line {line_number}: Grid row is missing the closing \"|\""))?;
+        programs
+          .last_mut()
+          .ok_or_else(|| format!("Line {line_number}: Grid row before the first \"start:\" line"))?
+          .rows
+          .push(row.chars().collect());
+        continue;
+      }
+
+      let (key, value) = line
+        .split_once(':')
+        .ok_or_else(|| format!("Line {line_number}: Expected \"key: value\""))?;
+      let value = value.trim();
+
+      match key.trim() {
+        "name" => name = Some(value.to_string()),
+        "level" => {
+          level_id = Some(Uuid::parse_str(value).map_err(|_| format!("Line {line_number}: Invalid level id"))?)
+        },
+        "type" => level_type = Some(parse_level_type(value).ok_or(format!("Line {line_number}: Unknown level type"))?),
+        "note" => {
+          if value.chars().count() > isa::MAX_NOTE_LEN {
+            return Err(format!(
+              "Line {line_number}: Note must be at most {} characters",
+              isa::MAX_NOTE_LEN
+            ));
+          }
+          note = value.to_string();
+        },
+        "start" => programs.push(ProgramText {
+          start: parse_start(value).ok_or(format!("Line {line_number}: Invalid start position"))?,
+          ..Default::default()
+        }),
+        "breakpoints" => {
+          let program = programs
+            .last_mut()
+            .ok_or_else(|| format!("Line {line_number}: Breakpoints before the first \"start:\" line"))?;
+          for point in value.split_whitespace() {
+            program
+              .breakpoints
+              .push(parse_point(point).ok_or(format!("Line {line_number}: Invalid breakpoint \"{point}\""))?);
+          }
+        },
+        "annotation" => {
+          let program = programs
+            .last_mut()
+            .ok_or_else(|| format!("Line {line_number}: Annotation before the first \"start:\" line"))?;
+          let (point, text) = value
+            .split_once(' ')
+            .ok_or_else(|| format!("Line {line_number}: Expected \"annotation: row,col text\""))?;
+          let (row, col) = parse_point(point).ok_or(format!("Line {line_number}: Invalid annotation position"))?;
+          let text = text.trim();
+          if text.chars().count() > isa::MAX_ANNOTATION_LEN {
+            return Err(format!(
+              "Line {line_number}: Annotation must be at most {} characters",
+              isa::MAX_ANNOTATION_LEN
+            ));
+          }
+          program.annotations.push(Annotation {
+            row,
+            col,
+            text: text.to_string(),
+          });
+        },
+        other => return Err(format!("Line {line_number}: Unknown key \"{other}\"")),
+      }
+    }
+
+    let name = name.ok_or("Missing \"name:\" line")?;
+    if name.is_empty() || name.chars().count() > isa::MAX_SOLUTION_NAME_LEN {
+      return Err(format!(
+        "Solution name must be between 1 and {} characters",
+        isa::MAX_SOLUTION_NAME_LEN
+      ));
+    }
+
+    Ok(Self {
+      name,
+      level_id: level_id.ok_or("Missing \"level:\" line")?,
+      level_type: level_type.unwrap_or_default(),
+      note,
+      programs,
+    })
+  }
+}
+
+impl Display for SolutionFile {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "name: {}", self.name)?;
+    writeln!(f, "level: {}", self.level_id)?;
+    writeln!(f, "type: {}", level_type_name(self.level_type))?;
+    if !self.note.is_empty() {
+      writeln!(f, "note: {}", self.note)?;
+    }
+
+    for program in self.programs.iter() {
+      writeln!(f)?;
+
+      writeln!(f, "start: {}", start_text(&program.start))?;
+
+      if !program.breakpoints.is_empty() {
+        let breakpoints: Vec<String> = program.breakpoints.iter().map(|(r, c)| format!("{r},{c}")).collect();
+        writeln!(f, "breakpoints: {}", breakpoints.join(" "))?;
+      }
+
+      for annotation in program.annotations.iter() {
+        writeln!(
+          f,
+          "annotation: {},{} {}",
+          annotation.row, annotation.col, annotation.text
+        )?;
+      }
+
+      for row in program.rows.iter() {
+        writeln!(f, "|{}|", row.iter().collect::<String>())?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl ProgramText {
+  pub fn from_grid<C>(grid: &Grid<C>, start: Start, to_char: impl Fn(&C) -> char) -> Self {
+    let mut breakpoints: Vec<(usize, usize)> = grid.breakpoints().collect();
+    breakpoints.sort();
+
+    Self {
+      start,
+      breakpoints,
+      annotations: grid.annotations().cloned().collect(),
+      rows: (0..grid.rows())
+        .map(|row| (0..grid.cols()).map(|col| to_char(grid.get_value(row, col))).collect())
+        .collect(),
+    }
+  }
+
+  /// The start position and every row, leaving out breakpoints and annotations since they don't change what the program does
+  pub fn code(&self) -> String {
+    let rows: Vec<String> = self.rows.iter().map(|row| row.iter().collect()).collect();
+    format!("{}\n{}\n", start_text(&self.start), rows.join("\n"))
+  }
+
+  ///
+  /// Convert the text back into a grid of the given size
+  ///   Returns the grid and start, or a printable error string
+  ///
+  pub fn to_grid<C>(
+    &self,
+    rows: usize,
+    cols: usize,
+    from_char: impl Fn(char) -> Option<C>,
+  ) -> Result<(Grid<C>, Start), String>
+  where
+    C: Default + Clone,
+  {
+    if self.rows.len() != rows || self.rows.iter().any(|row| row.len() != cols) {
+      return Err(format!("Program grid must be {rows} rows by {cols} columns"));
+    }
+
+    let mut grid = Grid::new(rows, cols);
+    for (row_index, row) in self.rows.iter().enumerate() {
+      for (col_index, c) in row.iter().enumerate() {
+        let command = from_char(*c).ok_or_else(|| format!("Unknown command '{c}'"))?;
+        grid.set_value(row_index, col_index, command);
+      }
+    }
+
+    let in_bounds = |&(row, col): &(usize, usize)| row < rows && col < cols;
+    for point in self.breakpoints.iter() {
+      if !in_bounds(point) {
+        return Err(format!("Breakpoint {},{} is outside the grid", point.0, point.1));
+      }
+      if !grid.has_breakpoint(point.0, point.1) {
+        grid.toggle_breakpoint(point.0, point.1);
+      }
+    }
+
+    for annotation in self.annotations.iter() {
+      if !in_bounds(&(annotation.row, annotation.col)) {
+        return Err(format!(
+          "Annotation {},{} is outside the grid",
+          annotation.row, annotation.col
+        ));
+      }
+      grid.set_annotation(annotation.row, annotation.col, annotation.text.clone());
+    }
+
+    if !in_bounds(&(self.start.row, self.start.col)) {
+      return Err(format!(
+        "Start position {},{} is outside the grid",
+        self.start.row, self.start.col
+      ));
+    }
+
+    Ok((grid, self.start))
+  }
+}
+
+/// Path used when exporting from the game, like "<folder>/1A-Solution_1.fit"
+pub fn export_path(folder: &Path, level_index: LevelIndex, solution_name: &str) -> PathBuf {
+  let file_name: String = solution_name
+    .chars()
+    .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+    .collect();
+  folder.join(format!("{level_index}-{file_name}.{FILE_EXTENSION}"))
+}
+
+fn parse_point(value: &str) -> Option<(usize, usize)> {
+  let (row, col) = value.split_once(',')?;
+  Some((row.trim().parse().ok()?, col.trim().parse().ok()?))
+}
+
+// A position, then the way it goes first if it isn't right, like "0,0" or "2,5 down"
+fn parse_start(value: &str) -> Option<Start> {
+  let mut parts = value.split_whitespace();
+  let (row, col) = parse_point(parts.next()?)?;
+  let direction = match parts.next() {
+    Some(direction) => parse_direction(direction)?,
+    None => Direction::Right,
+  };
+  parts.next().is_none().then_some(Start::new(row, col, direction))
+}
+
+// Programs that start out moving right are written the same way as before starts had a direction
+fn start_text(start: &Start) -> String {
+  match start.direction {
+    Direction::Right => format!("{},{}", start.row, start.col),
+    direction => format!("{},{} {}", start.row, start.col, direction_name(direction)),
+  }
+}
+
+fn parse_direction(value: &str) -> Option<Direction> {
+  match value {
+    "up" => Some(Direction::Up),
+    "down" => Some(Direction::Down),
+    "left" => Some(Direction::Left),
+    "right" => Some(Direction::Right),
+    _ => None,
+  }
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+  match direction {
+    Direction::Up => "up",
+    Direction::Down => "down",
+    Direction::Left => "left",
+    Direction::Right => "right",
+  }
+}
+
+fn parse_level_type(value: &str) -> Option<LevelType> {
+  match value {
+    "standard" => Some(LevelType::Standard),
+    "parallel" => Some(LevelType::Parallel),
+    _ => None,
+  }
+}
+
+fn level_type_name(level_type: LevelType) -> &'static str {
+  match level_type {
+    LevelType::Standard => "standard",
+    LevelType::Parallel => "parallel",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::grid::Transform;
+  use crate::isa::{parallel, standard, CommandPalette, InstructionSetArchitecture, Solution};
+
+  // Write the programs out as a file and read them back in, like an export then an import
+  fn round_trip(level_type: LevelType, programs: Vec<ProgramText>) -> Vec<ProgramText> {
+    let file = SolutionFile {
+      name: "Round Trip".into(),
+      level_id: Uuid::new_v4(),
+      level_type,
+      note: "Reads, adds, writes".into(),
+      programs,
+    };
+    let parsed = SolutionFile::parse(&file.to_string()).unwrap();
+    assert_eq!(parsed.name, file.name);
+    assert_eq!(parsed.note, file.note);
+    assert_eq!(parsed.level_id, file.level_id);
+    assert_eq!(parsed.level_type, level_type);
+    parsed.programs
+  }
+
+  #[test]
+  fn standard_solution_round_trip() {
+    let limits = isa::Standard::DEFAULT_LIMITS;
+    let mut solution = standard::Solution::new("Round Trip", &limits);
+    solution.set_grid_value(0, 0, standard::Command::In);
+    solution.set_grid_value(0, 1, standard::Command::Seven);
+    solution.set_grid_value(0, 2, standard::Command::Add);
+    solution.set_grid_value(9, 9, standard::Command::Out);
+    solution.set_start(0, 1);
+    solution.toggle_breakpoint(0, 2);
+    solution.toggle_breakpoint(9, 9);
+    solution.grid_mut().set_annotation(0, 2, "Add seven".into());
+
+    let programs = round_trip(LevelType::Standard, solution.to_programs());
+    let imported = standard::Solution::from_programs("Round Trip", &programs, &limits).unwrap();
+    assert!(imported.grid() == solution.grid());
+    assert_eq!(
+      (imported.start_row(), imported.start_col()),
+      (solution.start_row(), solution.start_col())
+    );
+    assert!(imported.grid().has_breakpoint(0, 2));
+    assert!(imported.grid().has_breakpoint(9, 9));
+    assert_eq!(imported.grid().annotation(0, 2), Some("Add seven"));
+  }
+
+  #[test]
+  fn parallel_solution_round_trip() {
+    let limits = isa::Parallel::DEFAULT_LIMITS;
+    let mut solution = parallel::Solution::new("Round Trip", &limits);
+    solution.program_0().set_grid_value(0, 0, parallel::Command::In);
+    solution.program_0().set_grid_value(0, 1, parallel::Command::Transmit);
+    solution.program_0().set_start(1, 2);
+    solution.program_0().toggle_breakpoint(0, 1);
+    solution.program_1().set_grid_value(2, 3, parallel::Command::Receive);
+    solution.program_1().set_grid_value(2, 4, parallel::Command::Multiply);
+    solution.program_1().set_start(2, 3);
+    solution.program_1().toggle_breakpoint(2, 4);
+    solution
+      .program_1()
+      .grid_mut()
+      .set_annotation(2, 3, "Wait for a value".into());
+    solution
+      .program_1()
+      .transform(None, Transform::RotateCounterClockwise, &CommandPalette::default());
+
+    let programs = round_trip(LevelType::Parallel, solution.to_programs());
+    let imported = parallel::Solution::from_programs("Round Trip", &programs, &limits).unwrap();
+    let (imported_0, imported_1) = imported.into_programs();
+    let (original_0, original_1) = solution.into_programs();
+    for (imported, original) in [(&imported_0, &original_0), (&imported_1, &original_1)] {
+      assert!(imported.grid() == original.grid());
+      assert_eq!(
+        (imported.start_row(), imported.start_col()),
+        (original.start_row(), original.start_col())
+      );
+      assert_eq!(imported.start_direction(), original.start_direction());
+    }
+    assert!(imported_0.grid().has_breakpoint(0, 1));
+    assert!(imported_1.grid().has_breakpoint(2, 3));
+    assert_eq!(imported_1.grid().annotation(3, 3), Some("Wait for a value"));
+    assert_eq!(imported_1.start_direction(), Direction::Up);
+  }
+
+  #[test]
+  fn only_the_second_processor_can_multiply() {
+    let limits = isa::Parallel::DEFAULT_LIMITS;
+    let mut solution = parallel::Solution::new("Round Trip", &limits);
+    solution.program_0().set_grid_value(0, 0, parallel::Command::Multiply);
+    assert!(solution.check_rules().is_err());
+
+    let programs = round_trip(LevelType::Parallel, solution.to_programs());
+    let error = parallel::Solution::from_programs("Round Trip", &programs, &limits).unwrap_err();
+    assert_eq!(error, "Only the second processor can multiply");
+  }
+
+  #[test]
+  fn grid_size_must_match_the_level() {
+    let limits = isa::Standard::DEFAULT_LIMITS;
+    let solution = standard::Solution::new("Round Trip", &limits);
+    let programs = round_trip(LevelType::Standard, solution.to_programs());
+
+    let rows = limits.grid_rows;
+    let cols = limits.grid_cols;
+    let from_char = standard::Command::from_char;
+    assert!(programs[0].to_grid(rows, cols, from_char).is_ok());
+    assert!(programs[0].to_grid(rows + 1, cols, from_char).is_err());
+    assert!(programs[0].to_grid(rows, cols - 1, from_char).is_err());
+    assert!(standard::Solution::from_programs("Too Small", &programs, &isa::Parallel::DEFAULT_LIMITS).is_err());
+  }
+}
@@ -8,11 +8,11 @@ use crossterm::{cursor, event, QueueableCommand};
 use super::{print_string, ShowHelpState, State, MIN_TERMINAL_WIDTH};
 use crate::global_state::GlobalState;
 use crate::isa::{self, InstructionSetArchitecture};
+use crate::keybindings::Action;
 use crate::level::{Level, LevelIndex, LevelType};
+use crate::revalidate;
 use crate::statistics::Statistics;
 
-const SEED: u32 = 0xdeadbeef;
-const NUM_TEST_CASES: usize = 25;
 const LEVELS_PER_PAGE: usize = 12;
 
 static TITLE: &str = r#"  ___            ___  ___    _ ___    ___  __   ___  ___ ___       ___  ___ 
@@ -33,19 +33,22 @@ enum LevelListEntry<'l> {
   NormalLevel {
     level: &'l Level,
     statistics: Option<Statistics>,
+    stale: bool,
   },
   ChallengeLevel {
     level: &'l Level,
     statistics: Option<Statistics>,
+    stale: bool,
   },
   LockedChallenge,
 }
 
 macro_rules! level_select_list {
-  (($self:ident, $level_pack:expr, $level_index:expr, $level:expr), [ $(($match_type:pat, $isa_type:ty),)+ ]) => {
+  (($self:ident, $global_state:ident, $level_pack:expr, $level_index:expr, $level:expr, $cache_folder:expr), [ $(($match_type:pat, $isa_type:ty),)+ ]) => {
     match $level.level_type() { $(
       $match_type => {
-        let test_cases = match <$isa_type as InstructionSetArchitecture>::generate_test_cases($level_pack.folder(), $level.lua_file(), SEED, NUM_TEST_CASES) {
+        let (level_id, level_index) = ($level.id(), *$level_index);
+        let (test_cases, version) = match <$isa_type as InstructionSetArchitecture>::generate_versioned_test_cases($level_pack.folder(), $level, isa::SEED, isa::NUM_TEST_CASES, Some(&$cache_folder)) {
           Ok(t) => t,
           Err(e) => {
             $self.last_error = Some(format!("Failed to generate test cases: {e}"));
@@ -53,7 +56,10 @@ macro_rules! level_select_list {
           },
         };
 
-        return Ok(Some(Box::new(ShowHelpState::<$isa_type>::new(*$level_index, 0, test_cases))));
+        // Levels that weren't cached when the game started only get their version now
+        $global_state.set_test_case_version(level_id, version);
+
+        return Ok(Some(Box::new(ShowHelpState::<$isa_type>::new(level_index, 0, test_cases))));
       }
     )+ }
   };
@@ -98,6 +104,12 @@ impl LevelSelectState {
   fn get_flattened_level_list<'l>(&self, global_state: &'l GlobalState) -> Vec<(LevelListEntry<'l>, LevelIndex)> {
     use LevelListEntry::*;
 
+    let is_stale = |level: &Level| {
+      global_state
+        .get_statistics(level.id())
+        .is_some_and(|statistics| global_state.is_stale(level.id(), &statistics))
+    };
+
     let mut take_next_group = true; // We always take the first group
     let unlocked_groups: Vec<_> = global_state
       .get_level_pack(self.selected_level_pack_index)
@@ -126,6 +138,7 @@ impl LevelSelectState {
                 ChallengeLevel {
                   level: cl,
                   statistics: global_state.get_statistics(cl.id()),
+                  stale: is_stale(cl),
                 }
               } else {
                 LockedChallenge
@@ -142,6 +155,7 @@ impl LevelSelectState {
               NormalLevel {
                 level: level.level(),
                 statistics: global_state.get_statistics(level.level().id()),
+                stale: is_stale(level.level()),
               },
               LevelIndex::new(self.selected_level_pack_index, group, level_in_group),
             ))
@@ -221,6 +235,13 @@ impl State for LevelSelectState {
         .queue(style::SetForegroundColor(Color::Red))?;
       print_string(err)?;
       stdout.queue(style::ResetColor)?;
+    } else if level_list[selected_level_index].0.is_stale() {
+      stdout.queue(cursor::MoveToNextLine(1))?;
+      write!(
+        stdout,
+        "{}",
+        "Test cases changed since this was solved, press r to score the solutions again".dark_cyan()
+      )?;
     }
 
     stdout.flush()?;
@@ -256,41 +277,10 @@ impl State for LevelSelectState {
           // Close the game
           KeyCode::Esc => return Ok(None),
 
-          // Level Movement
-          KeyCode::Up | KeyCode::Char('k') => {
-            self.last_error = None;
-            self.selected_level_indexes[self.selected_level_pack_index] =
-              (selected_level_index as isize - 1).rem_euclid(num_options as isize) as usize;
-            self.fix_page_offset();
-
-            return Ok(Some(self));
-          },
-          KeyCode::Down | KeyCode::Char('j') => {
-            self.last_error = None;
-            self.selected_level_indexes[self.selected_level_pack_index] =
-              (selected_level_index as isize + 1).rem_euclid(num_options as isize) as usize;
-            self.fix_page_offset();
-
-            return Ok(Some(self));
-          },
-
-          // Level pack movement
-          KeyCode::Left | KeyCode::Char('h') if self.selected_level_pack_index > 0 => {
-            self.last_error = None;
-            self.selected_level_pack_index =
-              (self.selected_level_pack_index as isize - 1).rem_euclid(num_level_packs as isize) as usize;
-            self.page_offset = 0;
-            self.fix_page_offset();
-
-            return Ok(Some(self));
-          },
-          KeyCode::Right | KeyCode::Char('l') if self.selected_level_pack_index < num_level_packs - 1 => {
-            self.last_error = None;
-            self.selected_level_pack_index =
-              (self.selected_level_pack_index as isize + 1).rem_euclid(num_level_packs as isize) as usize;
-            self.page_offset = 0;
-            self.fix_page_offset();
-
+          // Score the saved solutions against the new test cases
+          KeyCode::Char('r') if selected_level.is_stale() => {
+            let level_index = *level_index;
+            self.last_error = revalidate::recompute_statistics(global_state, level_index).err();
             return Ok(Some(self));
           },
 
@@ -299,7 +289,14 @@ impl State for LevelSelectState {
             let level_pack = global_state.get_level_pack(self.selected_level_pack_index);
             let level = global_state.level(*level_index);
             level_select_list!(
-              (self, level_pack, level_index, level),
+              (
+                self,
+                global_state,
+                level_pack,
+                level_index,
+                level,
+                global_state.cache_folder()
+              ),
               [
                 (LevelType::Standard, isa::Standard),
                 (LevelType::Parallel, isa::Parallel),
@@ -307,7 +304,47 @@ impl State for LevelSelectState {
             );
           },
 
-          _ => {},
+          _ => match global_state.keybindings().menu_action(&key) {
+            // Level Movement
+            Some(Action::MoveUp) => {
+              self.last_error = None;
+              self.selected_level_indexes[self.selected_level_pack_index] =
+                (selected_level_index as isize - 1).rem_euclid(num_options as isize) as usize;
+              self.fix_page_offset();
+
+              return Ok(Some(self));
+            },
+            Some(Action::MoveDown) => {
+              self.last_error = None;
+              self.selected_level_indexes[self.selected_level_pack_index] =
+                (selected_level_index as isize + 1).rem_euclid(num_options as isize) as usize;
+              self.fix_page_offset();
+
+              return Ok(Some(self));
+            },
+
+            // Level pack movement
+            Some(Action::MoveLeft) if self.selected_level_pack_index > 0 => {
+              self.last_error = None;
+              self.selected_level_pack_index =
+                (self.selected_level_pack_index as isize - 1).rem_euclid(num_level_packs as isize) as usize;
+              self.page_offset = 0;
+              self.fix_page_offset();
+
+              return Ok(Some(self));
+            },
+            Some(Action::MoveRight) if self.selected_level_pack_index < num_level_packs - 1 => {
+              self.last_error = None;
+              self.selected_level_pack_index =
+                (self.selected_level_pack_index as isize + 1).rem_euclid(num_level_packs as isize) as usize;
+              self.page_offset = 0;
+              self.fix_page_offset();
+
+              return Ok(Some(self));
+            },
+
+            _ => {},
+          },
         },
         _ => {},
       }
@@ -324,6 +361,14 @@ impl<'l> LevelListEntry<'l> {
     )
   }
 
+  /// Were the statistics scored against test cases the level no longer has?
+  pub fn is_stale(&self) -> bool {
+    matches!(
+      self,
+      LevelListEntry::NormalLevel { stale: true, .. } | LevelListEntry::ChallengeLevel { stale: true, .. }
+    )
+  }
+
   /// Challenge levels are tabbed
   pub fn is_challenge(&self) -> bool {
     matches!(
@@ -338,9 +383,17 @@ impl<'l> LevelListEntry<'l> {
     let mut stdout = io::stdout();
     let challenge_index = level_index.get_challenge().unwrap_or(0) + 1;
 
-    let (level, statistics) = match self {
-      NormalLevel { level, statistics } => (level, statistics),
-      ChallengeLevel { level, statistics } => (level, statistics),
+    let (level, statistics, stale) = match self {
+      NormalLevel {
+        level,
+        statistics,
+        stale,
+      } => (level, statistics, *stale),
+      ChallengeLevel {
+        level,
+        statistics,
+        stale,
+      } => (level, statistics, *stale),
       LockedChallenge => {
         return write!(stdout, "  Challenge {}: Locked", challenge_index);
       },
@@ -364,6 +417,13 @@ impl<'l> LevelListEntry<'l> {
         "Symbols:".dark_cyan(),
         statistics.symbols_used()
       )?;
+
+      // Levels beaten at or under par get a star, unless the scores are out of date
+      if stale {
+        write!(stdout, " {}", "?".dark_grey())?;
+      } else if statistics.beat_par(&level.par()) {
+        write!(stdout, " {}", "★".yellow())?;
+      }
     } else {
       write!(stdout, "{}", level_text.yellow())?;
     }
@@ -3,12 +3,13 @@ use std::io::{self, Write};
 
 mod level_select_state;
 mod show_help_state;
+#[allow(clippy::module_inception)]
 mod state;
 mod success_state;
 mod title_state;
 
 pub use level_select_state::LevelSelectState;
-pub use show_help_state::ShowHelpState;
+pub use show_help_state::{ShowHelpState, DESCRIPTION_LINES, DESCRIPTION_WIDTH};
 #[allow(unused)]
 pub use state::{run, State, MIN_TERMINAL_HEIGHT, MIN_TERMINAL_WIDTH};
 pub use success_state::SuccessState;
@@ -26,3 +27,30 @@ pub fn print_string(s: &str) -> io::Result<()> {
 
   Ok(())
 }
+
+// Word wrap a string to fit within the given width, words longer than the width are left as-is
+//  Anything past the maximum number of lines is added to the last line instead
+pub fn wrap_string(s: &str, width: usize, max_lines: usize) -> String {
+  let mut lines: Vec<String> = Vec::new();
+  for word in s.split_whitespace() {
+    let is_last_line = lines.len() >= max_lines;
+    match lines.last_mut() {
+      Some(line) if is_last_line || line.chars().count() + 1 + word.chars().count() <= width => {
+        line.push(' ');
+        line.push_str(word);
+      },
+      _ => lines.push(word.to_string()),
+    }
+  }
+  lines.join("\n")
+}
+
+// Cut a string down to the given width, ending in "…" if anything was left out
+pub fn truncate_string(s: &str, width: usize) -> String {
+  if s.chars().count() <= width {
+    return s.to_string();
+  }
+  let mut truncated: String = s.chars().take(width.saturating_sub(1)).collect();
+  truncated.push('…');
+  truncated
+}
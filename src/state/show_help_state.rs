@@ -4,21 +4,37 @@ use crossterm::{
   style::Stylize,
   QueueableCommand,
 };
+use std::fs;
 use std::io::{self, Write};
 
-use super::{LevelSelectState, State};
+use super::{truncate_string, LevelSelectState, State, MIN_TERMINAL_WIDTH};
 use crate::isa::{self, InstructionSetArchitecture, Solution, SolutionManager};
+use crate::keybindings::Action;
+use crate::solution_file::{self, SolutionFile};
+use crate::statistics::{RunResult, SolutionRun};
 use crate::{global_state::GlobalState, level::LevelIndex};
 
 const SOLUTIONS_PER_PAGE: usize = 3;
 
+// Columns for the solution list, which has to fit left of the instructions
+const SYMBOLS_COL: u16 = 34;
+const LAST_RUN_COL: u16 = 43;
+
+// The level description fills the rows between the title and the instructions
+//  The instructions start with a blank line, so the description can use that row too
+const DESCRIPTION_ROW: u16 = 2;
+const STATUS_ROW: u16 = 16;
+const INSTRUCTIONS_ROW: u16 = 17;
+pub const DESCRIPTION_LINES: usize = (INSTRUCTIONS_ROW + 1 - DESCRIPTION_ROW) as usize;
+pub const DESCRIPTION_WIDTH: usize = MIN_TERMINAL_WIDTH as usize;
+
 static SELECT_INSTRUCTIONS: &str = r#"
 ───────────────────────────────────────────────────────┬────────────────────────
                                                        │c   = Make a Copy
                                                        │r   = Rename
                                                        │^ v = Rearrange Up/Down
                                                        │x   = Delete
-                                                       │"#;
+                                                       │e/i = Export/Import"#;
 
 pub struct ShowHelpState<ISA: InstructionSetArchitecture> {
   level_index: LevelIndex,
@@ -26,7 +42,14 @@ pub struct ShowHelpState<ISA: InstructionSetArchitecture> {
   page_offset: usize,
   test_cases: Vec<ISA::Puzzle>,
 
-  in_rename: Option<String>,
+  in_prompt: Option<(Prompt, String)>,
+  status: Option<Result<String, String>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+  Rename,
+  Import,
 }
 
 impl<ISA: InstructionSetArchitecture> ShowHelpState<ISA> {
@@ -36,7 +59,8 @@ impl<ISA: InstructionSetArchitecture> ShowHelpState<ISA> {
       selected_solution_index,
       page_offset: 0,
       test_cases,
-      in_rename: None,
+      in_prompt: None,
+      status: None,
     };
     state.fix_page_offset();
     state
@@ -55,6 +79,47 @@ impl<ISA: InstructionSetArchitecture> ShowHelpState<ISA> {
       }
     }
   }
+
+  // Returns a printable message for the status line
+  fn export_solution(&self, global_state: &GlobalState) -> Result<String, String>
+  where
+    GlobalState: SolutionManager<ISA>,
+  {
+    let level_id = global_state.level(self.level_index).id();
+    let file = global_state.export_solution(level_id, self.selected_solution_index);
+    let folder = global_state.export_folder();
+    let path = solution_file::export_path(&folder, self.level_index, &file.name);
+
+    fs::create_dir_all(&folder).map_err(|e| format!("Failed to export solution: {e}"))?;
+    fs::write(&path, file.to_string()).map_err(|e| format!("Failed to export solution: {e}"))?;
+
+    // The save folder can be relative if it was given on the command line
+    let path = fs::canonicalize(&path).unwrap_or(path);
+    Ok(format!("Exported to {}", path.display()))
+  }
+
+  // Returns a printable message for the status line
+  fn import_solution(&mut self, path: &str, global_state: &mut GlobalState) -> Result<String, String>
+  where
+    GlobalState: SolutionManager<ISA>,
+  {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to import solution: {e}"))?;
+    let file = SolutionFile::parse(&text).map_err(|e| format!("Failed to import solution: {e}"))?;
+
+    if file.level_id != global_state.level(self.level_index).id() {
+      return Err("Failed to import solution: Solution is for a different level".into());
+    }
+
+    let level = global_state.level(self.level_index);
+    let limits = level.limits();
+    let palette = ISA::command_palette(level).map_err(|e| format!("Failed to import solution: {e}"))?;
+    self.selected_solution_index = global_state
+      .import_solution(&file, &limits, &palette)
+      .map_err(|e| format!("Failed to import solution: {e}"))?;
+    self.fix_page_offset();
+
+    Ok(format!("Imported \"{}\"", file.name))
+  }
 }
 
 impl<ISA: InstructionSetArchitecture> State for ShowHelpState<ISA>
@@ -69,14 +134,40 @@ where
     let level = global_state.level(self.level_index);
 
     write!(stdout, "{}", level.get_title(self.level_index).as_str().yellow())?;
-    stdout.queue(cursor::MoveToNextLine(2))?;
+    stdout.queue(cursor::MoveTo(0, DESCRIPTION_ROW))?;
 
     for line in level.description().lines() {
       write!(stdout, "{}", line)?;
       stdout.queue(cursor::MoveToNextLine(1))?;
     }
 
-    stdout.queue(cursor::MoveTo(0, 17))?;
+    stdout.queue(cursor::MoveTo(0, STATUS_ROW))?;
+    match self.in_prompt {
+      Some((Prompt::Import, ref path)) => write!(stdout, "{} {}_", "Import File:".dark_cyan(), path)?,
+      None | Some((Prompt::Rename, _)) => match self.status {
+        Some(Ok(ref message)) => write!(stdout, "{}", message.as_str().green())?,
+        Some(Err(ref message)) => write!(stdout, "{}", message.as_str().red())?,
+        // Otherwise remind them what the selected solution was about
+        None => {
+          let level_id = level.id();
+          let note = global_state
+            .get_all_solutions(level_id)
+            .get(self.selected_solution_index)
+            .map_or("", |solution| solution.note());
+          if !note.is_empty() {
+            let width = DESCRIPTION_WIDTH - "Note: ".len();
+            write!(
+              stdout,
+              "{} {}",
+              "Note:".dark_cyan(),
+              truncate_string(note, width).dark_grey()
+            )?;
+          }
+        },
+      },
+    }
+
+    stdout.queue(cursor::MoveTo(0, INSTRUCTIONS_ROW))?;
     for line in SELECT_INSTRUCTIONS.lines() {
       write!(stdout, "{}", line.dark_cyan())?;
       stdout.queue(cursor::MoveToNextLine(1))?;
@@ -86,11 +177,18 @@ where
     if self.page_offset > 0 {
       write!(stdout, "↑")?;
     }
-    stdout.queue(cursor::MoveToNextLine(1))?;
 
     let level_id = global_state.level(self.level_index).id();
     let solutions = global_state.get_all_solutions(level_id);
 
+    if !solutions.is_empty() {
+      stdout.queue(cursor::MoveToColumn(SYMBOLS_COL))?;
+      write!(stdout, "{}", "Symbols".dark_cyan())?;
+      stdout.queue(cursor::MoveToColumn(LAST_RUN_COL))?;
+      write!(stdout, "{}", "Last Run".dark_cyan())?;
+    }
+    stdout.queue(cursor::MoveToNextLine(1))?;
+
     for (solution, solution_number) in solutions
       .iter()
       .skip(self.page_offset)
@@ -98,9 +196,9 @@ where
       .zip((self.page_offset + 1)..)
     {
       if (self.selected_solution_index + 1) == solution_number {
-        match self.in_rename {
-          None => write!(stdout, "{}", "►".green())?,
-          Some(ref cur_name) => {
+        match self.in_prompt {
+          None | Some((Prompt::Import, _)) => write!(stdout, "{}", "►".green())?,
+          Some((Prompt::Rename, ref cur_name)) => {
             let color_fn = |s: &'static str| match cur_name.len() {
               0 => s.red(),
               _ => s.stylize(),
@@ -112,11 +210,23 @@ where
         write!(stdout, " ")?;
       }
 
-      if self.in_rename.is_none() || (self.selected_solution_index + 1) != solution_number {
+      if self.in_prompt.as_ref().map(|(prompt, _)| *prompt) != Some(Prompt::Rename)
+        || (self.selected_solution_index + 1) != solution_number
+      {
         write!(stdout, " {}", solution.name())?;
       }
-      stdout.queue(cursor::MoveToColumn(41))?;
-      write!(stdout, "{} {}", "Symbols:".dark_cyan(), solution.symbols_used(),)?;
+      stdout.queue(cursor::MoveToColumn(SYMBOLS_COL))?;
+      write!(stdout, "{}", solution.symbols_used())?;
+
+      stdout.queue(cursor::MoveToColumn(LAST_RUN_COL))?;
+      let runs = global_state.get_solution_runs(level_id, solution_number - 1);
+      let is_current = runs.last().is_some_and(|run| {
+        run.program_hash == solution.program_hash()
+          && run
+            .statistics()
+            .is_none_or(|statistics| !global_state.is_stale(level_id, statistics))
+      });
+      write_last_run(&mut stdout, runs.last(), is_current)?;
 
       stdout.queue(cursor::MoveToNextLine(1))?;
     }
@@ -145,7 +255,9 @@ where
     let level_id = global_state.level(self.level_index).id();
     let num_options = global_state.get_all_solutions(level_id).len() + 1;
 
-    if let Some(cur_name) = self.in_rename.as_mut() {
+    if let Some((prompt, cur_text)) = self.in_prompt.as_mut() {
+      let prompt = *prompt;
+
       loop {
         // `read()` blocks until an `Event` is available
         let event = match event::read() {
@@ -163,25 +275,33 @@ where
               return Ok(None);
             },
 
-            // Cancel rename
+            // Cancel rename or import
             KeyCode::Esc => {
-              self.in_rename = None;
+              self.in_prompt = None;
               return Ok(Some(self));
             },
 
             // Save rename
-            KeyCode::Enter if !cur_name.is_empty() => {
-              global_state.rename_solution(level_id, self.selected_solution_index, self.in_rename.take().unwrap());
+            KeyCode::Enter if prompt == Prompt::Rename && !cur_text.is_empty() => {
+              let (_, new_name) = self.in_prompt.take().unwrap();
+              global_state.rename_solution(level_id, self.selected_solution_index, new_name);
+              return Ok(Some(self));
+            },
+
+            // Import the file
+            KeyCode::Enter if prompt == Prompt::Import && !cur_text.is_empty() => {
+              let (_, path) = self.in_prompt.take().unwrap();
+              self.status = Some(self.import_solution(&path, global_state));
               return Ok(Some(self));
             },
 
             KeyCode::Backspace => {
-              cur_name.pop();
+              cur_text.pop();
               return Ok(Some(self));
             },
 
-            KeyCode::Char(c) if cur_name.len() < isa::MAX_SOLUTION_NAME_LEN => {
-              cur_name.push(c);
+            KeyCode::Char(c) if prompt == Prompt::Import || cur_text.len() < isa::MAX_SOLUTION_NAME_LEN => {
+              cur_text.push(c);
               return Ok(Some(self));
             },
 
@@ -192,6 +312,9 @@ where
       }
     }
 
+    // The status message only lasts until the next action
+    self.status = None;
+
     loop {
       // `read()` blocks until an `Event` is available
       let event = match event::read() {
@@ -214,29 +337,21 @@ where
             return Ok(Some(Box::new(LevelSelectState::new(self.level_index, global_state))));
           },
 
-          // Movement
-          KeyCode::Up | KeyCode::Char('k') => {
-            self.selected_solution_index =
-              (self.selected_solution_index as isize - 1).rem_euclid(num_options as isize) as usize;
-            self.fix_page_offset();
-
-            return Ok(Some(self));
-          },
-
-          KeyCode::Down | KeyCode::Char('j') => {
-            self.selected_solution_index =
-              (self.selected_solution_index as isize + 1).rem_euclid(num_options as isize) as usize;
-            self.fix_page_offset();
-
-            return Ok(Some(self));
-          },
-
           // Select Solution
           KeyCode::Enter => {
             if self.selected_solution_index == (num_options - 1) {
-              global_state.new_solution(level_id);
+              let limits = global_state.level(self.level_index).limits();
+              global_state.new_solution(level_id, &limits);
               return Ok(Some(self));
             } else {
+              let palette = match ISA::command_palette(global_state.level(self.level_index)) {
+                Ok(palette) => palette,
+                Err(e) => {
+                  self.status = Some(Err(e));
+                  return Ok(Some(self));
+                },
+              };
+
               let solution = global_state.get_all_solutions(level_id)[self.selected_solution_index].clone();
               return Ok(Some(Box::new(ISA::open_editor(
                 self.level_index,
@@ -244,6 +359,7 @@ where
                 solution,
                 self.test_cases,
                 0,
+                palette,
               ))));
             }
           },
@@ -258,7 +374,20 @@ where
 
           // Rename Solution
           KeyCode::Char('r') if self.selected_solution_index < (num_options - 1) => {
-            self.in_rename = Some(String::new());
+            self.in_prompt = Some((Prompt::Rename, String::new()));
+            return Ok(Some(self));
+          },
+
+          // Export Solution
+          KeyCode::Char('e') if self.selected_solution_index < (num_options - 1) => {
+            self.status = Some(self.export_solution(global_state));
+            return Ok(Some(self));
+          },
+
+          // Import Solution
+          KeyCode::Char('i') => {
+            let folder = global_state.export_folder();
+            self.in_prompt = Some((Prompt::Import, format!("{}/", folder.display())));
             return Ok(Some(self));
           },
 
@@ -288,10 +417,52 @@ where
             return Ok(Some(self));
           },
 
-          _ => {},
+          _ => match global_state.keybindings().menu_action(&key) {
+            // Movement
+            Some(Action::MoveUp) => {
+              self.selected_solution_index =
+                (self.selected_solution_index as isize - 1).rem_euclid(num_options as isize) as usize;
+              self.fix_page_offset();
+
+              return Ok(Some(self));
+            },
+
+            Some(Action::MoveDown) => {
+              self.selected_solution_index =
+                (self.selected_solution_index as isize + 1).rem_euclid(num_options as isize) as usize;
+              self.fix_page_offset();
+
+              return Ok(Some(self));
+            },
+
+            _ => {},
+          },
         },
         _ => {},
       }
     }
   }
 }
+
+///
+/// Show how the solution did the last time it ran
+///   A result only counts as valid while the programs and test cases are the same as when it ran
+///
+fn write_last_run(stdout: &mut io::Stdout, last_run: Option<&SolutionRun>, is_current: bool) -> io::Result<()> {
+  let Some(run) = last_run else {
+    return write!(stdout, "{}", "-".dark_grey());
+  };
+
+  let text = match run.result {
+    RunResult::Solved(ref statistics) => format!("{:.1}", statistics.average_cycles()),
+    RunResult::Failed(ref failure) => format!("Test {}", failure.test_case),
+  };
+
+  if !is_current {
+    write!(stdout, "{}", format!("? {text}").dark_grey())
+  } else if run.statistics().is_some() {
+    write!(stdout, "{}", format!("✓ {text}").green())
+  } else {
+    write!(stdout, "{}", format!("✗ {text}").red())
+  }
+}
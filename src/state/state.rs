@@ -24,6 +24,7 @@ pub fn run(mut state: Box<dyn State>, global_state: &mut GlobalState) -> io::Res
   let mut stdout = io::stdout();
   stdout
     .queue(terminal::EnterAlternateScreen)?
+    .queue(event::EnableBracketedPaste)?
     .execute(event::EnableMouseCapture)?;
   terminal::enable_raw_mode()?;
 
@@ -59,6 +60,7 @@ pub fn run(mut state: Box<dyn State>, global_state: &mut GlobalState) -> io::Res
     .queue(cursor::Show)?
     .queue(cursor::EnableBlinking)?
     .queue(event::DisableMouseCapture)?
+    .queue(event::DisableBracketedPaste)?
     .execute(terminal::LeaveAlternateScreen)?;
   Ok(())
 }
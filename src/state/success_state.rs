@@ -1,7 +1,8 @@
+use std::cmp::Ordering;
 use std::io::{self, Stdout, Write};
 
 use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
-use crossterm::style::Stylize;
+use crossterm::style::{StyledContent, Stylize};
 use crossterm::{cursor, QueueableCommand};
 
 use super::{LevelSelectState, State};
@@ -9,6 +10,10 @@ use crate::global_state::GlobalState;
 use crate::level::LevelIndex;
 use crate::statistics::Statistics;
 
+// Test cases are grouped together when there are more than this, so the histogram fits on the screen
+const MAX_BARS: usize = 40;
+const BAR_CHARS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
 pub struct SuccessState {
   level_index: LevelIndex,
   statistics: Statistics,
@@ -36,15 +41,21 @@ impl State for SuccessState {
     write!(stdout, "{}", level.get_title(self.level_index).yellow())?;
     stdout.queue(cursor::MoveToNextLine(2))?;
     write!(stdout, "{}", "☺☺☺ Success! ☺☺☺".green())?;
-    stdout.queue(cursor::MoveToNextLine(3))?;
-
-    write!(stdout, "Current Solution:")?;
-    stdout.queue(cursor::MoveToNextLine(1))?;
-    write_statistics(&mut stdout, &self.statistics)?;
+    stdout.queue(cursor::MoveToNextLine(2))?;
 
-    write!(stdout, "Personal Best:")?;
+    write!(stdout, "{:21}{:12}Personal Best", "", "Current")?;
     stdout.queue(cursor::MoveToNextLine(1))?;
-    write_statistics(&mut stdout, &self.best)?;
+    write_statistics(&mut stdout, &self.statistics, &self.best)?;
+    write_cycles_histogram(&mut stdout, &self.statistics, &self.best)?;
+
+    let par = level.par();
+    if par.is_set() {
+      write!(stdout, "{} {par}", "Par:".dark_green())?;
+      if self.statistics.beat_par(&par) {
+        write!(stdout, " {}", "★ Under Par!".green())?;
+      }
+      stdout.queue(cursor::MoveToNextLine(2))?;
+    }
 
     stdout.queue(cursor::MoveToNextLine(1))?;
     write!(stdout, "{} Continue", "►".green())?;
@@ -83,22 +94,141 @@ impl State for SuccessState {
   }
 }
 
-fn write_statistics(stdout: &mut Stdout, statistics: &Statistics) -> io::Result<()> {
-  write!(
-    stdout,
-    "∙ {} {:.2}",
-    "Average Cycles:".dark_yellow(),
-    statistics.average_cycles()
-  )?;
+// One line for each statistic, with the current solution next to the personal best
+fn write_statistics(stdout: &mut Stdout, current: &Statistics, best: &Statistics) -> io::Result<()> {
+  let mut rows = vec![
+    (
+      "Average Cycles:".dark_yellow(),
+      decimal(Some(current.average_cycles())),
+      decimal(Some(best.average_cycles())),
+    ),
+    (
+      "Worst Cycles:".dark_yellow(),
+      whole(current.worst_cycles()),
+      whole(best.worst_cycles()),
+    ),
+    (
+      "Symbols Used:".dark_cyan(),
+      whole(Some(current.symbols_used())),
+      whole(Some(best.symbols_used())),
+    ),
+    ("Area:".dark_cyan(), whole(current.area()), whole(best.area())),
+    (
+      "Instructions:".dark_magenta(),
+      decimal(current.average_instructions()),
+      decimal(best.average_instructions()),
+    ),
+    (
+      "Peak Stack Depth:".dark_magenta(),
+      whole(current.peak_stack_depth()),
+      whole(best.peak_stack_depth()),
+    ),
+  ];
+  if current.average_blocked_cycles().is_some() {
+    rows.push((
+      "Blocked Cycles:".dark_magenta(),
+      decimal(current.average_blocked_cycles()),
+      decimal(best.average_blocked_cycles()),
+    ));
+  }
+
+  for (label, current, best) in rows {
+    // Pad before styling, since the color codes would count towards the width
+    let padding = " ".repeat(18 - label.content().chars().count());
+    write!(stdout, "∙ {label}{padding} {current:<12}{best}")?;
+    stdout.queue(cursor::MoveToNextLine(1))?;
+  }
   stdout.queue(cursor::MoveToNextLine(1))?;
 
-  write!(
-    stdout,
-    "∙ {}   {}",
-    "Symbols Used:".dark_cyan(),
-    statistics.symbols_used()
-  )?;
+  Ok(())
+}
+
+///
+/// One bar for each test case's cycles, with the personal best underneath for comparison
+///   Bars are red where the current solution is slower than the best, and green where it is faster
+///
+fn write_cycles_histogram(stdout: &mut Stdout, current: &Statistics, best: &Statistics) -> io::Result<()> {
+  let current_cycles = current.test_case_cycles();
+  if current_cycles.is_empty() {
+    return Ok(());
+  }
+
+  // The best run might be from before the level had the same number of test cases
+  let best_cycles = Some(best.test_case_cycles()).filter(|cycles| cycles.len() == current_cycles.len());
+
+  let group_size = current_cycles.len().div_ceil(MAX_BARS);
+  let group = |cycles: &[u32]| -> Vec<u32> {
+    cycles
+      .chunks(group_size)
+      .map(|chunk| chunk.iter().copied().max().unwrap_or(0))
+      .collect()
+  };
+  let current_bars = group(current_cycles);
+  let best_bars = best_cycles.map(group);
+
+  let scale = current_bars
+    .iter()
+    .chain(best_bars.iter().flatten())
+    .copied()
+    .max()
+    .unwrap_or(0);
+  let bar = |cycles: u32| match scale {
+    0 => BAR_CHARS[0],
+    _ => BAR_CHARS[(cycles as usize * 8).div_ceil(scale as usize).clamp(1, 8)],
+  };
+
+  let width = 9 + current_bars.len() + 2;
+  write!(stdout, "{:width$}{:8}{:8}Max", "Cycles per Test Case:", "Min", "Median")?;
+  stdout.queue(cursor::MoveToNextLine(1))?;
+
+  write!(stdout, "{:9}", "Current")?;
+  for (i, &cycles) in current_bars.iter().enumerate() {
+    let styled: StyledContent<char> = match best_bars.as_ref().map(|best| cycles.cmp(&best[i])) {
+      Some(Ordering::Greater) => bar(cycles).red(),
+      Some(Ordering::Less) => bar(cycles).green(),
+      _ => bar(cycles).stylize(),
+    };
+    write!(stdout, "{styled}")?;
+  }
+  write_summary(stdout, current_cycles)?;
+
+  if let (Some(best_cycles), Some(best_bars)) = (best_cycles, best_bars) {
+    write!(stdout, "{:9}", "Best")?;
+    let bars: String = best_bars.into_iter().map(bar).collect();
+    write!(stdout, "{}", bars.dark_grey())?;
+    write_summary(stdout, best_cycles)?;
+  }
+
+  // Point out the slowest input, since that's usually the one to work on
+  let (slowest, cycles) =
+    current_cycles.iter().enumerate().fold(
+      (0, 0),
+      |slowest, (i, &cycles)| if cycles > slowest.1 { (i, cycles) } else { slowest },
+    );
+  write!(stdout, "Slowest: Test Case {} ({cycles} cycles)", slowest + 1)?;
   stdout.queue(cursor::MoveToNextLine(2))?;
 
   Ok(())
 }
+
+// Min, median, and max after the bars, then move to the next line
+fn write_summary(stdout: &mut Stdout, cycles: &[u32]) -> io::Result<()> {
+  let mut sorted = cycles.to_vec();
+  sorted.sort_unstable();
+
+  // The lower middle value is used when there is an even number of test cases
+  let median = sorted[(sorted.len() - 1) / 2];
+  write!(stdout, "  {:<8}{:<8}{}", sorted[0], median, sorted[sorted.len() - 1])?;
+  stdout.queue(cursor::MoveToNextLine(1))?;
+
+  Ok(())
+}
+
+// Statistics missing from older save files are shown as a dash
+fn whole<T: ToString>(value: Option<T>) -> String {
+  value.map_or_else(|| "-".into(), |value| value.to_string())
+}
+
+fn decimal(value: Option<f64>) -> String {
+  value.map_or_else(|| "-".into(), |value| format!("{value:.2}"))
+}
@@ -6,7 +6,8 @@ use crossterm::style::Stylize;
 use crossterm::{cursor, event, QueueableCommand};
 
 use super::{LevelSelectState, State};
-use crate::global_state::GlobalState;
+use crate::global_state::{self, GlobalState};
+use crate::keybindings::Action;
 use crate::level::LevelIndex;
 
 static TITLE: &str = r#"
@@ -26,19 +27,68 @@ static TITLE: &str = r#"
 static VERSION: &str = concat!("v", env!("CARGO_PKG_VERSION"));
 static CREATOR: &str = "Created by Bryan McClain";
 static COPYRIGHT: &str = "© Comprosoft 2023";
+static PROFILE_INSTRUCTIONS: &str = "n = New Profile   Enter = Start";
 
 pub struct TitleState {
   now: Instant,
+
+  profiles: Vec<String>,
+  selected_profile: usize,
+  new_profile: Option<String>,
+  last_error: Option<String>,
+
+  // Stop the automatic start once the player picks a profile
+  waiting: bool,
 }
 
 impl TitleState {
-  pub fn new() -> Self {
-    Self { now: Instant::now() }
+  pub fn new(global_state: &GlobalState) -> Self {
+    let profiles = global_state.list_profiles();
+    let selected_profile = profiles
+      .iter()
+      .position(|p| p == global_state.profile())
+      .unwrap_or_default();
+
+    Self {
+      now: Instant::now(),
+      profiles,
+      selected_profile,
+      new_profile: None,
+      last_error: None,
+      waiting: false,
+    }
+  }
+
+  fn start_game(mut self: Box<Self>, global_state: &mut GlobalState) -> Box<dyn State> {
+    if let Err(e) = global_state.switch_profile(&self.profiles[self.selected_profile]) {
+      self.last_error = Some(e);
+      self.waiting = true;
+      return self;
+    }
+
+    Box::new(LevelSelectState::new(LevelIndex::default(), global_state))
+  }
+
+  // Returns false if the name is not allowed
+  fn add_profile(&mut self, name: &str) -> bool {
+    let name = name.trim();
+    if !global_state::is_valid_profile_name(name) {
+      return false;
+    }
+
+    self.selected_profile = match self.profiles.iter().position(|p| p == name) {
+      Some(index) => index,
+      None => {
+        self.profiles.push(name.to_string());
+        self.profiles.len() - 1
+      },
+    };
+    true
   }
 }
 
 impl State for TitleState {
-  fn render(&mut self, _global_state: &mut GlobalState) -> io::Result<()> {
+  fn render(&mut self, global_state: &mut GlobalState) -> io::Result<()> {
     let mut stdout = io::stdout();
     stdout.queue(cursor::Hide)?.queue(cursor::MoveTo(0, 0))?;
 
@@ -52,19 +102,46 @@ impl State for TitleState {
     write!(stdout, "                    {}", CREATOR.dark_yellow())?;
     stdout.queue(cursor::MoveToNextLine(1))?;
     write!(stdout, "                       {}", COPYRIGHT.dark_green())?;
+    stdout.queue(cursor::MoveToNextLine(2))?;
+
+    match self.new_profile {
+      Some(ref name) => write!(stdout, "                    {} {}_", "New Profile:".dark_cyan(), name)?,
+      None => write!(
+        stdout,
+        "                    {} ← {} →",
+        "Profile:".dark_cyan(),
+        self.profiles[self.selected_profile]
+      )?,
+    }
+
+    stdout.queue(cursor::MoveToNextLine(2))?;
+    let keybindings = global_state.keybindings();
+    let change_profile = format!(
+      "{}/{} = Change Profile   ",
+      keybindings.keys_text(&[Action::MoveLeft], 1),
+      keybindings.keys_text(&[Action::MoveRight], 1)
+    );
+    write!(
+      stdout,
+      "{}{}",
+      change_profile.dark_cyan(),
+      PROFILE_INSTRUCTIONS.dark_cyan()
+    )?;
+
+    if let Some(ref err) = self.last_error {
+      stdout.queue(cursor::MoveToNextLine(2))?;
+      write!(stdout, "{}", err.as_str().red())?;
+    }
 
     stdout.flush()?;
 
     Ok(())
   }
 
-  fn execute(self: Box<Self>, global_state: &mut GlobalState) -> io::Result<Option<Box<dyn State>>> {
+  fn execute(mut self: Box<Self>, global_state: &mut GlobalState) -> io::Result<Option<Box<dyn State>>> {
     let elapsed = self.now.elapsed();
-    if elapsed > Duration::from_secs(2) {
-      return Ok(Some(Box::new(LevelSelectState::new(
-        LevelIndex::default(),
-        global_state,
-      ))));
+    if !self.waiting && elapsed > Duration::from_secs(2) {
+      return Ok(Some(self.start_game(global_state)));
     }
 
     if event::poll(Duration::from_millis(100))? {
@@ -84,22 +161,57 @@ impl State for TitleState {
             return Ok(None);
           },
 
-          KeyCode::Enter => {
-            return Ok(Some(Box::new(LevelSelectState::new(
-              LevelIndex::default(),
-              global_state,
-            ))))
+          // Typing a new profile name
+          KeyCode::Esc if self.new_profile.is_some() => {
+            self.new_profile = None;
+          },
+          KeyCode::Enter if self.new_profile.is_some() => {
+            let name = self.new_profile.take().unwrap();
+            if !self.add_profile(&name) {
+              self.last_error = Some("Profile names can only use letters, numbers, spaces, '-' or '_'".into());
+            }
           },
+          KeyCode::Backspace if self.new_profile.is_some() => {
+            self.new_profile.as_mut().unwrap().pop();
+          },
+          KeyCode::Char(c) if self.new_profile.is_some() => {
+            let name = self.new_profile.as_mut().unwrap();
+            if name.chars().count() < global_state::MAX_PROFILE_NAME_LEN {
+              name.push(c);
+            }
+          },
+
+          KeyCode::Enter => return Ok(Some(self.start_game(global_state))),
           KeyCode::Esc => return Ok(None),
 
-          _ => {},
+          KeyCode::Char('n') => {
+            self.waiting = true;
+            self.last_error = None;
+            self.new_profile = Some(String::new());
+          },
+
+          _ => match global_state.keybindings().menu_action(&key) {
+            // Profile selection
+            Some(Action::MoveLeft) => {
+              self.waiting = true;
+              self.last_error = None;
+              self.selected_profile = (self.selected_profile + self.profiles.len() - 1) % self.profiles.len();
+            },
+            Some(Action::MoveRight) => {
+              self.waiting = true;
+              self.last_error = None;
+              self.selected_profile = (self.selected_profile + 1) % self.profiles.len();
+            },
+
+            _ => {},
+          },
         },
 
         _ => {},
       }
 
-      // Then clear all pending events
-      while event::poll(Duration::from_secs(0))? {
+      // Then clear all pending events, unless they are typing
+      while self.new_profile.is_none() && event::poll(Duration::from_secs(0))? {
         event::read()?;
       }
     }
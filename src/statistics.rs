@@ -1,17 +1,76 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::isa::TestCaseFailure;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Statistics {
   average_cycles: f64,
   symbols_used: usize,
+
+  // Levels completed in older versions of the game don't have these
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  worst_cycles: Option<u32>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  area: Option<usize>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  average_instructions: Option<f64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  peak_stack_depth: Option<usize>,
+
+  // Only parallel levels have processors that can wait on each other
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  average_blocked_cycles: Option<f64>,
+
+  // Cycles for each test case in order, from the run with the best average
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  test_case_cycles: Vec<u32>,
+
+  // Version of the test cases the statistics were scored against, see InstructionSetArchitecture
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  test_case_version: Option<String>,
+
+  // Whether any single run was at or under par, since the bests can come from different runs
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  beat_par: Option<bool>,
+}
+
+/// Measurements from running a solution against a single test case
+#[derive(Debug, Clone, Copy)]
+pub struct TestCaseScore {
+  pub cycles: u32,
+  pub instructions: u32,
+  pub peak_stack_depth: usize,
+  pub blocked_cycles: Option<u32>,
 }
 
 impl Statistics {
-  pub fn new(average_cycles: f64, symbols_used: usize) -> Self {
+  pub fn new(scores: &[TestCaseScore], symbols_used: usize, area: usize) -> Self {
+    debug_assert!(!scores.is_empty());
+
+    let average = |value: fn(&TestCaseScore) -> u32| {
+      scores.iter().map(|score| value(score) as f64).sum::<f64>() / scores.len() as f64
+    };
+    let average_blocked_cycles = scores
+      .iter()
+      .map(|score| score.blocked_cycles.map(|cycles| cycles as f64))
+      .sum::<Option<f64>>()
+      .map(|total| total / scores.len() as f64);
+
     Self {
-      average_cycles,
+      average_cycles: average(|score| score.cycles),
       symbols_used,
+      worst_cycles: scores.iter().map(|score| score.cycles).max(),
+      area: Some(area),
+      average_instructions: Some(average(|score| score.instructions)),
+      peak_stack_depth: scores.iter().map(|score| score.peak_stack_depth).max(),
+      average_blocked_cycles,
+      test_case_cycles: scores.iter().map(|score| score.cycles).collect(),
+      test_case_version: None,
+      beat_par: None,
     }
   }
 
@@ -23,12 +82,167 @@ impl Statistics {
     self.symbols_used
   }
 
+  pub fn worst_cycles(&self) -> Option<u32> {
+    self.worst_cycles
+  }
+
+  /// Size of the rectangle around every symbol, added up for each program
+  pub fn area(&self) -> Option<usize> {
+    self.area
+  }
+
+  /// Non-empty instructions run per test case, not counting skipped or blocked ones
+  pub fn average_instructions(&self) -> Option<f64> {
+    self.average_instructions
+  }
+
+  pub fn peak_stack_depth(&self) -> Option<usize> {
+    self.peak_stack_depth
+  }
+
+  /// Cycles per test case that processors spent waiting to transmit or receive, added up for each processor
+  pub fn average_blocked_cycles(&self) -> Option<f64> {
+    self.average_blocked_cycles
+  }
+
+  /// Empty for levels completed in older versions of the game
+  pub fn test_case_cycles(&self) -> &[u32] {
+    &self.test_case_cycles
+  }
+
+  /// None for statistics saved before test cases had versions
+  pub fn test_case_version(&self) -> Option<&str> {
+    self.test_case_version.as_deref()
+  }
+
+  pub fn set_test_case_version(&mut self, version: Option<String>) {
+    self.test_case_version = version;
+  }
+
+  /// Remember whether this run was at or under par, before it gets combined with other runs
+  pub fn set_par(&mut self, par: &Par) {
+    self.beat_par = Some(self.is_at_or_under(par));
+  }
+
+  ///
+  /// Whether a single run was at or under every par score the level sets, see set_par
+  ///   Statistics saved before runs were checked on their own fall back to the combined bests
+  ///
+  pub fn beat_par(&self, par: &Par) -> bool {
+    self.beat_par.unwrap_or_else(|| self.is_at_or_under(par))
+  }
+
+  fn is_at_or_under(&self, par: &Par) -> bool {
+    par.is_set()
+      && par.cycles.is_none_or(|cycles| self.average_cycles <= cycles as f64)
+      && par.symbols.is_none_or(|symbols| self.symbols_used <= symbols)
+  }
+
+  /// Lower is better for every statistic, and each one keeps its own best
   pub fn set_to_best(&mut self, other: &Statistics) {
-    if other.average_cycles < self.average_cycles {
-      self.average_cycles = other.average_cycles;
+    // Scores from different test cases can't be compared, so the new ones replace the old ones
+    if let (Some(version), Some(other_version)) = (&self.test_case_version, &other.test_case_version) {
+      if version != other_version {
+        *self = other.clone();
+        return;
+      }
     }
-    if other.symbols_used < self.symbols_used {
-      self.symbols_used = other.symbols_used;
+
+    // The cycles for each test case only make sense together, so they come from a single run
+    let is_best_run = match other.average_cycles.partial_cmp(&self.average_cycles) {
+      Some(Ordering::Less) => true,
+      Some(Ordering::Equal) => self.test_case_cycles.is_empty(),
+      _ => false,
+    };
+    if is_best_run {
+      self.test_case_cycles = other.test_case_cycles.clone();
+    }
+
+    self.average_cycles = self.average_cycles.min(other.average_cycles);
+    self.symbols_used = self.symbols_used.min(other.symbols_used);
+    self.worst_cycles = lowest(self.worst_cycles, other.worst_cycles);
+    self.area = lowest(self.area, other.area);
+    self.average_instructions = lowest(self.average_instructions, other.average_instructions);
+    self.peak_stack_depth = lowest(self.peak_stack_depth, other.peak_stack_depth);
+    self.average_blocked_cycles = lowest(self.average_blocked_cycles, other.average_blocked_cycles);
+
+    // Any run at or under par is enough, but a run that wasn't can't tell if an older unchecked one was
+    self.beat_par = match (self.beat_par, other.beat_par) {
+      (Some(true), _) | (_, Some(true)) => Some(true),
+      (Some(false), Some(false)) => Some(false),
+      _ => None,
+    };
+  }
+}
+
+// A missing value never counts as the best
+fn lowest<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Option<T> {
+  match (a, b) {
+    (Some(a), Some(b)) => Some(if b < a { b } else { a }),
+    (a, b) => a.or(b),
+  }
+}
+
+/// A single run of a solution against every test case, kept in the history for that solution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SolutionRun {
+  /// Seconds since the Unix epoch
+  pub timestamp: u64,
+  /// Hash of the solution's programs when it ran, so later edits can be detected
+  pub program_hash: String,
+  pub result: RunResult,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RunResult {
+  Solved(Statistics),
+  Failed(TestCaseFailure),
+}
+
+impl SolutionRun {
+  pub fn new(program_hash: String, result: RunResult) -> Self {
+    let timestamp = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or_default();
+
+    Self {
+      timestamp,
+      program_hash,
+      result,
+    }
+  }
+
+  pub fn statistics(&self) -> Option<&Statistics> {
+    match self.result {
+      RunResult::Solved(ref statistics) => Some(statistics),
+      RunResult::Failed(_) => None,
+    }
+  }
+}
+
+/// Target scores for a level, set in the pack file
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Par {
+  pub cycles: Option<u32>,
+  pub symbols: Option<usize>,
+}
+
+impl Par {
+  pub fn is_set(&self) -> bool {
+    self.cycles.is_some() || self.symbols.is_some()
+  }
+}
+
+impl fmt::Display for Par {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match (self.cycles, self.symbols) {
+      (Some(cycles), Some(symbols)) => write!(f, "{cycles} cycles / {symbols} symbols"),
+      (Some(cycles), None) => write!(f, "{cycles} cycles"),
+      (None, Some(symbols)) => write!(f, "{symbols} symbols"),
+      (None, None) => Ok(()),
     }
   }
 }
@@ -0,0 +1,128 @@
+use std::fs;
+use std::process::ExitCode;
+
+use crate::cli::{ExportArgs, ImportArgs};
+use crate::global_state::GlobalState;
+use crate::isa::{self, InstructionSetArchitecture, SolutionManager};
+use crate::level::{LevelIndex, LevelType};
+use crate::solution_file::SolutionFile;
+
+const EXIT_ERROR: u8 = 2;
+
+///
+/// Run the "export" command-line mode
+///   Prints the solution unless an output file is given
+///
+pub fn export(args: &ExportArgs, global_state: &GlobalState) -> ExitCode {
+  let text = match export_solution(args, global_state) {
+    Ok(text) => text,
+    Err(e) => {
+      eprintln!("Error: {e}");
+      return ExitCode::from(EXIT_ERROR);
+    },
+  };
+
+  match args.output {
+    None => print!("{text}"),
+    Some(ref output) => {
+      if let Err(e) = fs::write(output, text) {
+        eprintln!("Error: Failed to write {output}: {e}");
+        return ExitCode::from(EXIT_ERROR);
+      }
+      eprintln!("Exported solution to {output}");
+    },
+  }
+
+  ExitCode::SUCCESS
+}
+
+fn export_solution(args: &ExportArgs, global_state: &GlobalState) -> Result<String, String> {
+  let level_index = global_state.find_level(&args.level, args.pack)?;
+  let level = global_state.level(level_index);
+
+  let num_solutions = match level.level_type() {
+    LevelType::Standard => SolutionManager::<isa::Standard>::get_all_solutions(global_state, level.id()).len(),
+    LevelType::Parallel => SolutionManager::<isa::Parallel>::get_all_solutions(global_state, level.id()).len(),
+  };
+  if args.solution >= num_solutions {
+    return Err(format!(
+      "Solution {} does not exist, {} only has {num_solutions} solution(s)",
+      args.solution + 1,
+      level.get_title(level_index)
+    ));
+  }
+
+  let file = match level.level_type() {
+    LevelType::Standard => SolutionManager::<isa::Standard>::export_solution(global_state, level.id(), args.solution),
+    LevelType::Parallel => SolutionManager::<isa::Parallel>::export_solution(global_state, level.id(), args.solution),
+  };
+  Ok(file.to_string())
+}
+
+///
+/// Run the "import" command-line mode
+///   Every file is imported even if some of them fail
+///
+pub fn import(args: &ImportArgs, global_state: &mut GlobalState) -> ExitCode {
+  let mut any_imported = false;
+  let mut any_failed = false;
+
+  for path in args.files.iter() {
+    match import_solution(path, global_state) {
+      Ok(message) => {
+        any_imported = true;
+        println!("{message}");
+      },
+      Err(e) => {
+        any_failed = true;
+        eprintln!("Error: Failed to import {path}: {e}");
+      },
+    }
+  }
+
+  if any_imported {
+    if let Err(e) = global_state.save() {
+      eprintln!("Error: Failed to save progress: {e}");
+      return ExitCode::from(EXIT_ERROR);
+    }
+  }
+
+  if any_failed {
+    ExitCode::from(EXIT_ERROR)
+  } else {
+    ExitCode::SUCCESS
+  }
+}
+
+fn import_solution(path: &str, global_state: &mut GlobalState) -> Result<String, String> {
+  let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+  let file = SolutionFile::parse(&text)?;
+
+  let level_index = global_state.find_level(&file.level_id.to_string(), None)?;
+  let solution_index = match global_state.level(level_index).level_type() {
+    LevelType::Standard => import_into_level::<isa::Standard>(global_state, &file, level_index)?,
+    LevelType::Parallel => import_into_level::<isa::Parallel>(global_state, &file, level_index)?,
+  };
+
+  Ok(format!(
+    "Imported \"{}\" as solution {} of {}",
+    file.name,
+    solution_index + 1,
+    global_state.level(level_index).get_title(level_index)
+  ))
+}
+
+fn import_into_level<ISA>(
+  global_state: &mut GlobalState,
+  file: &SolutionFile,
+  level_index: LevelIndex,
+) -> Result<usize, String>
+where
+  ISA: InstructionSetArchitecture,
+  GlobalState: SolutionManager<ISA>,
+{
+  let level = global_state.level(level_index);
+  let limits = level.limits();
+  let palette = ISA::command_palette(level)?;
+  global_state.import_solution(file, &limits, &palette)
+}
@@ -0,0 +1,95 @@
+// Oldest edits are forgotten after this many, a solution is small so this is still cheap
+const MAX_UNDO_STEPS: usize = 500;
+
+///
+/// Undo and redo history for the program editors
+///   Whole copies are kept instead of individual changes, so edits to any number of cells undo the same way
+///
+#[derive(Debug, Clone)]
+pub struct UndoHistory<T> {
+  undo_stack: Vec<T>,
+  redo_stack: Vec<T>,
+}
+
+impl<T: Clone + PartialEq> UndoHistory<T> {
+  pub fn new() -> Self {
+    Self {
+      undo_stack: Vec::new(),
+      redo_stack: Vec::new(),
+    }
+  }
+
+  /// Remember the state from before an edit, unless the edit didn't change anything
+  pub fn record(&mut self, before: T, after: &T) {
+    if before == *after {
+      return;
+    }
+
+    if self.undo_stack.len() >= MAX_UNDO_STEPS {
+      self.undo_stack.remove(0);
+    }
+    self.undo_stack.push(before);
+    self.redo_stack.clear();
+  }
+
+  /// Returns the state to go back to, or None if there is nothing to undo
+  pub fn undo(&mut self, current: &T) -> Option<T> {
+    let previous = self.undo_stack.pop()?;
+    self.redo_stack.push(current.clone());
+    Some(previous)
+  }
+
+  /// Returns the state to go forward to, or None if there is nothing to redo
+  pub fn redo(&mut self, current: &T) -> Option<T> {
+    let next = self.redo_stack.pop()?;
+    self.undo_stack.push(current.clone());
+    Some(next)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn undo_and_redo() {
+    let mut history = UndoHistory::new();
+    history.record(1, &2);
+    history.record(2, &3);
+    history.record(3, &3);
+
+    assert_eq!(history.undo(&3), Some(2));
+    assert_eq!(history.undo(&2), Some(1));
+    assert_eq!(history.undo(&1), None);
+    assert_eq!(history.redo(&1), Some(2));
+    assert_eq!(history.redo(&2), Some(3));
+    assert_eq!(history.redo(&3), None);
+    assert_eq!(history.undo(&3), Some(2));
+  }
+
+  #[test]
+  fn new_edit_clears_redo() {
+    let mut history = UndoHistory::new();
+    history.record(1, &2);
+    assert_eq!(history.undo(&2), Some(1));
+
+    history.record(1, &5);
+    assert_eq!(history.redo(&5), None);
+    assert_eq!(history.undo(&5), Some(1));
+  }
+
+  #[test]
+  fn oldest_edits_are_forgotten() {
+    let mut history = UndoHistory::new();
+    let edits = MAX_UNDO_STEPS + 10;
+    for i in 0..edits {
+      history.record(i, &(i + 1));
+    }
+
+    let mut current = edits;
+    while let Some(previous) = history.undo(&current) {
+      current = previous;
+    }
+    assert_eq!(current, 10);
+  }
+}
@@ -0,0 +1,190 @@
+use serde::Serialize;
+use std::process::ExitCode;
+use uuid::Uuid;
+
+use crate::cli::VerifyArgs;
+use crate::global_state::GlobalState;
+use crate::isa::{self, InstructionSetArchitecture, Solution, SolutionManager, TestCaseFailure};
+use crate::level::{LevelIndex, LevelLimits, LevelType};
+
+const EXIT_FAILED: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyReport {
+  level_id: Uuid,
+  level: String,
+  solutions: Vec<SolutionReport>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SolutionReport {
+  solution: usize,
+  name: String,
+  passed: bool,
+  average_cycles: Option<f64>,
+  symbols_used: usize,
+  failure: Option<TestCaseFailure>,
+  disallowed_commands: Vec<String>,
+  broken_rule: Option<String>,
+}
+
+///
+/// Run the "verify" command-line mode
+///   Exit code is 0 if every solution passes, 1 if any fail, and 2 on errors
+///
+pub fn run(args: &VerifyArgs, global_state: &GlobalState) -> ExitCode {
+  let report = match build_report(args, global_state) {
+    Ok(report) => report,
+    Err(e) => {
+      eprintln!("Error: {e}");
+      return ExitCode::from(EXIT_ERROR);
+    },
+  };
+
+  if args.json {
+    match serde_json::to_string_pretty(&report) {
+      Ok(json) => println!("{json}"),
+      Err(e) => {
+        eprintln!("Error: {e}");
+        return ExitCode::from(EXIT_ERROR);
+      },
+    }
+  } else {
+    print_report(&report);
+  }
+
+  if report.solutions.iter().all(|s| s.passed) {
+    ExitCode::SUCCESS
+  } else {
+    ExitCode::from(EXIT_FAILED)
+  }
+}
+
+fn build_report(args: &VerifyArgs, global_state: &GlobalState) -> Result<VerifyReport, String> {
+  let level_index = global_state.find_level(&args.level, args.pack)?;
+  let level = global_state.level(level_index);
+
+  let solutions = match level.level_type() {
+    LevelType::Standard => verify_solutions::<isa::Standard>(args, global_state, level_index)?,
+    LevelType::Parallel => verify_solutions::<isa::Parallel>(args, global_state, level_index)?,
+  };
+
+  Ok(VerifyReport {
+    level_id: level.id(),
+    level: level.get_title(level_index),
+    solutions,
+  })
+}
+
+fn verify_solutions<ISA>(
+  args: &VerifyArgs,
+  global_state: &GlobalState,
+  level_index: LevelIndex,
+) -> Result<Vec<SolutionReport>, String>
+where
+  ISA: InstructionSetArchitecture,
+  GlobalState: SolutionManager<ISA>,
+{
+  let level = global_state.level(level_index);
+  let all_solutions = global_state.get_all_solutions(level.id());
+  if all_solutions.is_empty() {
+    return Err(format!("No saved solutions for {}", level.get_title(level_index)));
+  }
+
+  let selected: Vec<usize> = match args.solution {
+    Some(i) if i < all_solutions.len() => vec![i],
+    Some(i) => {
+      return Err(format!(
+        "Solution {} does not exist, level only has {} solution(s)",
+        i + 1,
+        all_solutions.len()
+      ))
+    },
+    None => (0..all_solutions.len()).collect(),
+  };
+
+  let level_limits = level.limits();
+  let limits = LevelLimits {
+    max_cycles: args.max_cycles.unwrap_or(level_limits.max_cycles),
+    ..level_limits
+  };
+
+  let palette = ISA::command_palette(level)?;
+
+  let level_pack = global_state.get_level_pack(level_index.get_level_pack_index());
+  let test_cases = ISA::generate_test_cases(
+    level_pack.folder(),
+    level,
+    isa::SEED,
+    isa::NUM_TEST_CASES,
+    Some(&global_state.cache_folder()),
+  )
+  .map_err(|e| format!("Failed to generate test cases: {e}"))?;
+
+  let reports = selected
+    .into_iter()
+    .map(|index| {
+      let solution = &all_solutions[index];
+
+      // Solutions using commands the level doesn't allow, or breaking the editor's rules, fail without being run
+      let disallowed_commands: Vec<String> = palette
+        .disallowed(ISA::commands_used(solution))
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+      let broken_rule = solution.check_rules().err();
+      let result = (disallowed_commands.is_empty() && broken_rule.is_none())
+        .then(|| ISA::run_solution(solution, &test_cases, &limits));
+
+      SolutionReport {
+        solution: index + 1,
+        name: solution.name().to_string(),
+        passed: matches!(result, Some(Ok(_))),
+        average_cycles: result
+          .as_ref()
+          .and_then(|r| r.as_ref().ok())
+          .map(|s| s.average_cycles()),
+        symbols_used: solution.symbols_used(),
+        failure: result.and_then(Result::err),
+        disallowed_commands,
+        broken_rule,
+      }
+    })
+    .collect();
+
+  Ok(reports)
+}
+
+fn print_report(report: &VerifyReport) {
+  println!("{}", report.level);
+
+  for solution in report.solutions.iter() {
+    print!("  Solution {} - {}: ", solution.solution, solution.name);
+    if !solution.disallowed_commands.is_empty() {
+      println!(
+        "FAIL (Uses commands not allowed in this level: {})",
+        solution.disallowed_commands.join(" ")
+      );
+      continue;
+    }
+    if let Some(ref broken_rule) = solution.broken_rule {
+      println!("FAIL ({broken_rule})");
+      continue;
+    }
+
+    match (&solution.failure, solution.average_cycles) {
+      (Some(failure), _) => println!(
+        "FAIL (Test Case {}, Cycle {}: {})",
+        failure.test_case, failure.cycle, failure.message
+      ),
+      (None, Some(average_cycles)) => println!(
+        "PASS (Average Cycles: {average_cycles:.2}, Symbols Used: {})",
+        solution.symbols_used
+      ),
+      (None, None) => println!("PASS"),
+    }
+  }
+}